@@ -0,0 +1,374 @@
+use std::fs;
+use std::path::Path;
+use log::error;
+use serde::{Deserialize, Serialize};
+use crate::atium::common::error::AtiumError;
+use crate::atium::common::model::{parse_image_output, ThumbnailRequest};
+use crate::converter::model::{ConversionInput, ConversionOutput, ConversionRequest, InputSourceType, output_container, OutputCodec, parse_audio_output, parse_clip_range, parse_codec, parse_loudness_target, parse_rate_control, parse_resolution, parse_rotation_mode, parse_scaling_mode, RotationMode, ScalingMode};
+
+/// Manifest formats:
+/// * Json  -> a list of jobs, or an object holding them in `jobs`
+/// * Yaml  -> same layout as JSON
+/// * Csv   -> one job per row, with a header naming the [`BatchJob`] fields
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ManifestFormat {
+    Json,
+    Yaml,
+    Csv
+}
+
+/// Returns a value of [`ManifestFormat`] based on input:
+/// Valid inputs are:
+/// * json       -> JSON
+/// * yaml, yml  -> YAML
+/// * csv        -> CSV
+///
+/// Fails on any other input
+pub fn parse_manifest_format(format_string: &str) -> Result<ManifestFormat, AtiumError> {
+    match format_string.to_lowercase().as_str() {
+        "json" => Ok(ManifestFormat::Json),
+        "yaml" | "yml" => Ok(ManifestFormat::Yaml),
+        "csv" => Ok(ManifestFormat::Csv),
+        _ => Err(AtiumError::InvalidRequest(format!("Manifest format [{}] is not supported, expected json/yaml/csv", format_string)))
+    }
+}
+
+/// Returns the requested [`ManifestFormat`] or, when missing, the one matching the extension of `manifest_file`.
+/// JSON is used when the manifest file has no extension.
+///
+/// Fails when the requested format, or the extension, is not supported
+pub fn manifest_format(format_string: Option<&str>, manifest_file: &str) -> Result<ManifestFormat, AtiumError> {
+    format_string
+        .or_else(|| Path::new(manifest_file).extension().and_then(|e| e.to_str()))
+        .map(parse_manifest_format)
+        .unwrap_or(Ok(ManifestFormat::Json))
+}
+
+/// A conversion job of a manifest, its fields match the `convert` CLI options
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct BatchJob {
+    /// Identifier used in the report, defaults to the position of the job
+    pub id: Option<String>,
+    pub input: String,
+    pub output: String,
+    pub resolution: String,
+    pub scaling: Option<String>,
+    pub rotation: Option<String>,
+    pub codec: Option<String>,
+    /// Defaults to the output file extension
+    pub container: Option<String>,
+    pub crf: Option<u32>,
+    pub bitrate: Option<u32>,
+    pub maxrate: Option<u32>,
+    pub bufsize: Option<u32>,
+    pub preset: Option<String>,
+    pub tune: Option<String>,
+    pub two_pass: Option<bool>,
+    pub audio_codec: Option<String>,
+    pub audio_bitrate: Option<u32>,
+    pub audio_channels: Option<String>,
+    pub sample_rate: Option<u32>,
+    pub loudness: Option<f64>,
+    pub true_peak: Option<f64>,
+    pub loudness_range: Option<f64>,
+    /// Start of the converted range, defaults to the start of the source
+    pub start: Option<String>,
+    /// End of the converted range, defaults to the end of the source
+    pub end: Option<String>,
+    /// Comma separated timestamps of the thumbnails, extracted from the converted video
+    pub thumb_ts: Option<String>,
+    pub thumb_out: Option<String>,
+    pub thumb_format: Option<String>,
+    pub thumb_width: Option<u32>,
+    pub thumb_height: Option<u32>,
+    pub thumb_quality: Option<u8>
+}
+
+impl BatchJob {
+    /// Builds the [`ConversionRequest`] of the job, failing on the first invalid option, e.g. an unknown codec or range bounds
+    pub fn to_request(&self) -> Result<ConversionRequest, AtiumError> {
+        Ok(ConversionRequest {
+            input: ConversionInput {
+                source_type: InputSourceType::Local,
+                file_name: self.input.clone()
+            },
+            output: ConversionOutput {
+                file: self.output.clone(),
+                resolution: parse_resolution(&self.resolution),
+                scaling: self.scaling.as_deref().map(parse_scaling_mode).transpose()?.unwrap_or(ScalingMode::Fit),
+                rotation: self.rotation.as_deref().map(parse_rotation_mode).transpose()?.unwrap_or(RotationMode::Bake),
+                codec: self.codec.as_deref().map(parse_codec).transpose()?.unwrap_or(OutputCodec::H264),
+                container: output_container(self.container.as_deref(), &self.output)?,
+                rate_control: parse_rate_control(self.crf, self.bitrate, self.maxrate, self.bufsize)?,
+                preset: self.preset.clone(),
+                tune: self.tune.clone(),
+                two_pass: self.two_pass.unwrap_or(false),
+                audio: parse_audio_output(self.audio_codec.as_deref(), self.audio_bitrate, self.audio_channels.as_deref(), self.sample_rate)?,
+                loudness: parse_loudness_target(self.loudness, self.true_peak, self.loudness_range),
+                range: parse_clip_range(self.start.as_deref(), self.end.as_deref())?,
+                segment_duration: None,
+                dash: None,
+                thumbnail_request: ThumbnailRequest::new(&self.thumb_ts, &None, &self.thumb_out)
+                    .map(|request| parse_image_output(
+                        self.thumb_format.as_deref(), self.thumb_out.as_deref(), self.thumb_width, self.thumb_height, self.thumb_quality
+                    ).map(|image| ThumbnailRequest { image, ..request }))
+                    .transpose()?
+            }
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Manifest {
+    Jobs(Vec<BatchJob>),
+    Object { jobs: Vec<BatchJob> }
+}
+
+/// Parses the jobs of a manifest
+pub fn load_manifest(content: &str, format: ManifestFormat) -> Result<Vec<BatchJob>, AtiumError> {
+    let invalid = |err: String| {
+        error!("Error when parsing manifest {}", err);
+        AtiumError::InvalidRequest(format!("Could not parse manifest: {}", err))
+    };
+
+    let manifest = match format {
+        ManifestFormat::Json => serde_json::from_str(content).map_err(|err| invalid(err.to_string()))?,
+        ManifestFormat::Yaml => serde_yaml::from_str(content).map_err(|err| invalid(err.to_string()))?,
+        ManifestFormat::Csv => {
+            let jobs = csv::ReaderBuilder::new()
+                .trim(csv::Trim::All)
+                .from_reader(content.as_bytes())
+                .deserialize()
+                .collect::<Result<Vec<BatchJob>, csv::Error>>()
+                .map_err(|err| invalid(err.to_string()))?;
+            Manifest::Jobs(jobs)
+        }
+    };
+
+    match manifest {
+        Manifest::Jobs(jobs) | Manifest::Object { jobs } => Ok(jobs)
+    }
+}
+
+/// Reads and parses the manifest at `manifest_file`
+pub fn load_manifest_file(manifest_file: &str, format: ManifestFormat) -> Result<Vec<BatchJob>, AtiumError> {
+    let content = fs::read_to_string(manifest_file)
+        .map_err(|err| AtiumError::Input { message: format!("Could not read manifest [{}]", manifest_file), source: Some(err) })?;
+
+    load_manifest(&content, format)
+}
+
+/// A batch of conversion jobs
+pub struct BatchRequest {
+    pub jobs: Vec<BatchJob>,
+    /// Number of conversions running in parallel, at least 1
+    pub workers: usize
+}
+
+/// Final state of a job
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Succeeded,
+    Failed,
+    Cancelled
+}
+
+/// Outcome of a single job of the batch
+#[derive(Clone, Debug, Serialize)]
+pub struct JobReport {
+    pub id: String,
+    pub input: String,
+    pub status: JobStatus,
+    /// Path of the converted file, when the job succeeded
+    pub output_file: Option<String>,
+    /// Paths of the extracted thumbnails, when requested and extracted
+    pub thumbnails: Vec<String>,
+    /// Start time, RFC 3339 formatted
+    pub started_at: String,
+    pub duration_secs: f64,
+    pub error: Option<String>,
+    /// Machine-readable category of the error, see [`crate::common::error::ErrorCategory`]
+    pub error_category: Option<&'static str>
+}
+
+/// Summary of a batch run, with the reports of its jobs in manifest order
+#[derive(Clone, Debug, Serialize)]
+pub struct BatchReport {
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+    pub workers: usize,
+    pub duration_secs: f64,
+    pub jobs: Vec<JobReport>
+}
+
+impl BatchReport {
+    /// Builds the summary of the given job reports
+    pub fn new(jobs: Vec<JobReport>, workers: usize, duration_secs: f64) -> Self {
+        let count = |status: JobStatus| jobs.iter().filter(|job| job.status == status).count();
+
+        BatchReport {
+            total: jobs.len(),
+            succeeded: count(JobStatus::Succeeded),
+            failed: count(JobStatus::Failed),
+            cancelled: count(JobStatus::Cancelled),
+            workers,
+            duration_secs,
+            jobs
+        }
+    }
+    /// Returns true when every job succeeded
+    pub fn is_success(&self) -> bool {
+        self.succeeded == self.total
+    }
+    /// Writes the report as pretty printed JSON
+    pub fn write_to(&self, report_file: &str) -> Result<(), AtiumError> {
+        let content = serde_json::to_string_pretty(self)
+            .map_err(|err| AtiumError::Output { message: format!("Could not serialize report: {}", err), source: None })?;
+
+        fs::write(report_file, content)
+            .map_err(|err| AtiumError::Output { message: format!("Could not write report [{}]", report_file), source: Some(err) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converter::model::{AudioCodec, OutputContainer, OutputResolution, RateControl};
+    use crate::atium::common::model::{ImageFormat, ImageOutput};
+
+    #[test]
+    fn test_load_json_manifest() {
+        let content = r#"{
+            "jobs": [
+                { "id": "intro", "input": "intro.mov", "output": "intro.webm", "resolution": "hd", "codec": "vp9", "crf": 31 },
+                { "input": "outro.mov", "output": "outro.mp4", "resolution": "fullhd", "two_pass": true, "bitrate": 4000 }
+            ]
+        }"#;
+
+        let jobs = load_manifest(content, ManifestFormat::Json).unwrap();
+
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].id, Some("intro".to_string()));
+        assert_eq!(jobs[0].crf, Some(31));
+        assert_eq!(jobs[1].id, None);
+        assert_eq!(jobs[1].two_pass, Some(true));
+
+        let bare = load_manifest(r#"[{ "input": "a.mov", "output": "a.mp4", "resolution": "hd" }]"#, ManifestFormat::Json).unwrap();
+        assert_eq!(bare.len(), 1);
+    }
+
+    #[test]
+    fn test_load_yaml_manifest() {
+        let content = "
+jobs:
+  - input: a.mov
+    output: a.mkv
+    resolution: uhd
+    audio_codec: opus
+    loudness: -16
+";
+
+        let jobs = load_manifest(content, ManifestFormat::Yaml).unwrap();
+
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].audio_codec, Some("opus".to_string()));
+        assert_eq!(jobs[0].loudness, Some(-16.0));
+    }
+
+    #[test]
+    fn test_load_csv_manifest() {
+        let content = "input,output,resolution,codec,crf,thumb_ts,thumb_out
+a.mov, a.mp4, hd, h265, 26, 00:00:05, a.jpg
+b.mov, b.webm, sd, vp9, ,,
+";
+
+        let jobs = load_manifest(content, ManifestFormat::Csv).unwrap();
+
+        assert_eq!(jobs.len(), 2);
+        assert_eq!(jobs[0].output, "a.mp4");
+        assert_eq!(jobs[0].crf, Some(26));
+        assert_eq!(jobs[1].crf, None);
+        assert_eq!(jobs[1].thumb_out, None);
+    }
+
+    #[test]
+    fn test_invalid_manifest() {
+        let err = load_manifest(r#"[{ "output": "a.mp4" }]"#, ManifestFormat::Json).unwrap_err();
+
+        assert!(matches!(err, AtiumError::InvalidRequest(_)));
+    }
+
+    #[test]
+    fn test_job_to_request() {
+        let job = BatchJob {
+            input: "a.mov".to_string(),
+            output: "a.webm".to_string(),
+            resolution: "hd".to_string(),
+            codec: Some("vp9".to_string()),
+            crf: Some(31),
+            audio_bitrate: Some(96),
+            ..BatchJob::default()
+        };
+
+        let request = job.to_request().unwrap();
+
+        assert_eq!(request.output.resolution, OutputResolution::Hd);
+        assert_eq!(request.output.codec, OutputCodec::Vp9);
+        assert_eq!(request.output.container, OutputContainer::WebM);
+        assert_eq!(request.output.rate_control, Some(RateControl::ConstantQuality(31)));
+        assert_eq!(request.output.audio.unwrap().codec, AudioCodec::Aac);
+        assert!(request.output.thumbnail_request.is_none());
+
+        let invalid = BatchJob { crf: Some(23), bitrate: Some(1000), ..job.clone() };
+        assert!(invalid.to_request().is_err());
+
+        let trimmed = BatchJob { start: Some("00:01:30".to_string()), ..job.clone() };
+        assert!(trimmed.to_request().unwrap().output.range.is_some());
+        let invalid = BatchJob { end: Some("later".to_string()), ..job.clone() };
+        assert!(invalid.to_request().is_err());
+
+        let thumbnail = BatchJob { thumb_out: Some("a.webp".to_string()), thumb_width: Some(320), thumb_quality: Some(80), ..job };
+        let image = thumbnail.to_request().unwrap().output.thumbnail_request.unwrap().image;
+        assert_eq!(image, ImageOutput { format: ImageFormat::WebP, width: Some(320), height: None, quality: Some(80) });
+    }
+
+    #[test]
+    fn test_manifest_format() {
+        assert_eq!(manifest_format(None, "jobs.yml").unwrap(), ManifestFormat::Yaml);
+        assert_eq!(manifest_format(None, "jobs.CSV").unwrap(), ManifestFormat::Csv);
+        assert_eq!(manifest_format(Some("yaml"), "jobs.txt").unwrap(), ManifestFormat::Yaml);
+        assert_eq!(manifest_format(None, "jobs").unwrap(), ManifestFormat::Json);
+        assert!(manifest_format(None, "jobs.txt").is_err());
+        assert!(manifest_format(Some("toml"), "jobs.json").is_err());
+    }
+
+    #[test]
+    fn test_batch_report() {
+        let job = |status: JobStatus| JobReport {
+            id: "job".to_string(),
+            input: "a.mov".to_string(),
+            status,
+            output_file: None,
+            thumbnails: Vec::new(),
+            started_at: "2023-01-01T00:00:00Z".to_string(),
+            duration_secs: 1.0,
+            error: None,
+            error_category: None
+        };
+
+        let report = BatchReport::new(vec![job(JobStatus::Succeeded), job(JobStatus::Failed), job(JobStatus::Succeeded)], 2, 3.5);
+
+        assert_eq!(report.total, 3);
+        assert_eq!(report.succeeded, 2);
+        assert_eq!(report.failed, 1);
+        assert_eq!(report.cancelled, 0);
+        assert!(!report.is_success());
+
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["jobs"][1]["status"], "failed");
+    }
+}
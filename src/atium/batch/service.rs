@@ -0,0 +1,113 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
+
+use chrono::{SecondsFormat, Utc};
+use log::{debug, error, info};
+
+use crate::atium::batch::model::{BatchJob, BatchReport, BatchRequest, JobReport, JobStatus};
+use crate::atium::common::command_manager::ExecutionControl;
+use crate::atium::common::error::{AtiumError, ErrorCategory};
+use crate::converter::service::FFMPEGConversionService;
+
+/// Runs batches of conversions sharing a single [`FFMPEGConversionService`],
+/// so that `ffmpeg` and the analysis backend are checked only once
+pub struct BatchService {
+    conversion_service: FFMPEGConversionService
+}
+
+impl BatchService {
+    fn run_job(&self, index: usize, job: &BatchJob, control: &ExecutionControl) -> JobReport {
+        let id = job.id.clone().unwrap_or_else(|| format!("job-{}", index + 1));
+        let started_at = Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true);
+        let started = Instant::now();
+
+        debug!("Starting job [{}] on [{}]", id, job.input);
+
+        // pending jobs are not started once the batch is cancelled
+        let result = if control.is_cancelled() {
+            Err(AtiumError::Cancelled { command: format!("job [{}]", id) })
+        } else {
+            job.to_request()
+                .and_then(|request| self.conversion_service.convert_with_control(request, control, |_| {}))
+        };
+
+        let duration_secs = started.elapsed().as_secs_f64();
+
+        match result {
+            Ok(response) => {
+                info!("Job [{}] converted to [{}] in {:.1}s", id, response.output_file, duration_secs);
+                JobReport {
+                    id,
+                    input: job.input.clone(),
+                    status: JobStatus::Succeeded,
+                    output_file: Some(response.output_file),
+                    thumbnails: response.thumbnail_response.map(|thumbnail| thumbnail.outputs).unwrap_or_default(),
+                    started_at,
+                    duration_secs,
+                    error: None,
+                    error_category: None
+                }
+            }
+            Err(err) => {
+                error!("Job [{}] failed: {}", id, err);
+                JobReport {
+                    id,
+                    input: job.input.clone(),
+                    status: match err.category() {
+                        ErrorCategory::Cancelled => JobStatus::Cancelled,
+                        _ => JobStatus::Failed
+                    },
+                    output_file: None,
+                    thumbnails: Vec::new(),
+                    started_at,
+                    duration_secs,
+                    error: Some(err.to_string()),
+                    error_category: Some(err.category().name())
+                }
+            }
+        }
+    }
+    /// Runs the jobs of the request on a pool of `workers` threads and returns the summary report.
+    ///
+    /// A failing job does not stop the others. Each external command is bound to `control`,
+    /// cancelling its token stops the running jobs and marks the pending ones as cancelled.
+    pub fn run(&self, request: BatchRequest, control: &ExecutionControl) -> BatchReport {
+        let workers = request.workers.clamp(1, request.jobs.len().max(1));
+        let started = Instant::now();
+        let next_job = AtomicUsize::new(0);
+        let reports: Mutex<Vec<Option<JobReport>>> = Mutex::new(vec![None; request.jobs.len()]);
+
+        info!("Running {} jobs on {} workers", request.jobs.len(), workers);
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let index = next_job.fetch_add(1, Ordering::SeqCst);
+                    let job = match request.jobs.get(index) {
+                        Some(job) => job,
+                        None => break
+                    };
+
+                    let report = self.run_job(index, job, control);
+                    if let Ok(mut reports) = reports.lock() {
+                        reports[index] = Some(report);
+                    }
+                });
+            }
+        });
+
+        let jobs = reports.into_inner()
+            .unwrap_or_default()
+            .into_iter()
+            .flatten()
+            .collect();
+
+        BatchReport::new(jobs, workers, started.elapsed().as_secs_f64())
+    }
+    /// Constructs a new instance of [`BatchService`]
+    pub fn new() -> Result<Self, AtiumError> {
+        Ok(Self { conversion_service: FFMPEGConversionService::new()? })
+    }
+}
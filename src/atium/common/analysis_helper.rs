@@ -1,17 +1,18 @@
-use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use std::time::Duration;
 use log::{debug, error};
 use rand::Rng;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use uuid::Uuid;
 use crate::atium::common::error::AtiumError;
+use crate::atium::common::media::{AudioTrack, GeneralTrack, MenuTrack, RawTrack, TextTrack, Track, VideoTrack};
 
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct Media {
-    pub track: Vec<HashMap<String,Value>>
+    pub track: Vec<RawTrack>
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -35,11 +36,74 @@ impl AnalysisOutput {
             Some(track) => {
                 track.get(field_name)
                     .map(|v| self.parse_string_value(v))
-                    .ok_or(AtiumError::IOError("Could not extract field from track".to_string()))
+                    .ok_or_else(|| AtiumError::probe("Could not extract field from track"))
             }
-            None => Err(AtiumError::IOError("Could not extract track".to_string()))
+            None => Err(AtiumError::probe("Could not extract track"))
         }
     }
+    /// Returns every track converted into its typed representation
+    pub fn tracks(&self) -> Vec<Track> {
+        self.media.track.iter().map(Track::from).collect()
+    }
+    /// Returns the [`GeneralTrack`], wherever it is placed in the track list
+    pub fn general_track(&self) -> Result<GeneralTrack, AtiumError> {
+        self.tracks().into_iter()
+            .find_map(|track| match track {
+                Track::General(general) => Some(general),
+                _ => None
+            })
+            .ok_or_else(|| AtiumError::probe("Could not find General track"))
+    }
+    /// Returns all the [`VideoTrack`]s in stream order
+    pub fn video_tracks(&self) -> Vec<VideoTrack> {
+        self.tracks().into_iter()
+            .filter_map(|track| match track {
+                Track::Video(video) => Some(video),
+                _ => None
+            })
+            .collect()
+    }
+    /// Returns the first [`VideoTrack`], failing if the media has no video
+    pub fn video_track(&self) -> Result<VideoTrack, AtiumError> {
+        self.video_tracks().into_iter()
+            .next()
+            .ok_or_else(|| AtiumError::probe("Could not find Video track"))
+    }
+    /// Returns all the [`AudioTrack`]s in stream order
+    pub fn audio_tracks(&self) -> Vec<AudioTrack> {
+        self.tracks().into_iter()
+            .filter_map(|track| match track {
+                Track::Audio(audio) => Some(audio),
+                _ => None
+            })
+            .collect()
+    }
+    /// Returns all the [`TextTrack`]s in stream order
+    pub fn text_tracks(&self) -> Vec<TextTrack> {
+        self.tracks().into_iter()
+            .filter_map(|track| match track {
+                Track::Text(text) => Some(text),
+                _ => None
+            })
+            .collect()
+    }
+    /// Returns all the [`MenuTrack`]s in stream order
+    pub fn menu_tracks(&self) -> Vec<MenuTrack> {
+        self.tracks().into_iter()
+            .filter_map(|track| match track {
+                Track::Menu(menu) => Some(menu),
+                _ => None
+            })
+            .collect()
+    }
+    /// Returns the media duration, taken from the General track or,
+    /// if missing, from the first video track
+    pub fn duration(&self) -> Result<Duration, AtiumError> {
+        self.general_track().ok()
+            .and_then(|general| general.duration)
+            .or_else(|| self.video_track().ok().and_then(|video| video.duration))
+            .ok_or_else(|| AtiumError::probe("Could not extract duration"))
+    }
 }
 
 pub struct MediaInfoJsonLoader{}
@@ -48,7 +112,7 @@ impl MediaInfoJsonLoader {
         let analysis_output: AnalysisOutput = serde_json::from_str(data)
             .map_err(|err| {
                 error!("Error when parsing {}", err);
-                AtiumError::IOError("Could not parse json".to_string())
+                AtiumError::probe("Could not parse json")
             })?;
 
         Ok(analysis_output)
@@ -62,19 +126,19 @@ impl MediaInfoJsonLoader {
             }
             Err(err) => {
                 error!("Error when reading JSON file: {}", err);
-                Err(AtiumError::IOError(err.to_string()))
+                Err(AtiumError::Input { message: format!("Could not read [{}]", input_file), source: Some(err) })
             }
         }
     }
     /// Loads JSON structure into [`AnalysisOutput`] struct starting from an input [`String`]
-    pub fn load_json_from_string(&self, input: &String) -> Result<AnalysisOutput, AtiumError> {
-        self.deserialize(input.as_str())
+    pub fn load_json_from_string(&self, input: &str) -> Result<AnalysisOutput, AtiumError> {
+        self.deserialize(input)
     }
 }
 
 fn get_file_name_from_path(path: &Path) -> Result<String, AtiumError> {
     let name_os = path.file_stem()
-        .ok_or(AtiumError::IOError("Could not parse filename".to_string()))?
+        .ok_or(AtiumError::InvalidRequest("Could not parse filename".to_string()))?
         .to_os_string();
 
     let uuid = Uuid::new_v4().to_string();
@@ -86,7 +150,7 @@ fn get_file_name_from_path(path: &Path) -> Result<String, AtiumError> {
 
 fn get_extension_from_path(path: &Path, default_extension: &str) -> Result<String, AtiumError> {
     let name_os = path.extension()
-        .ok_or(AtiumError::IOError("Could not parse extension".to_string()))?
+        .ok_or(AtiumError::InvalidRequest("Could not parse extension".to_string()))?
         .to_os_string();
 
     Ok(name_os.to_str()
@@ -138,30 +202,79 @@ mod tests {
         assert_eq!(result.ok().unwrap(), String::from("1920"))
     }
 
+    #[test]
+    fn test_typed_tracks() {
+        let json_loader = MediaInfoJsonLoader{};
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/info.json");
+
+        let output = json_loader.load_json_from_file(&d.to_str().unwrap().to_string()).unwrap();
+
+        let general = output.general_track().unwrap();
+        assert_eq!(general.duration, Some(Duration::from_secs(20)));
+        assert_eq!(general.video_count, 1);
+
+        let video = output.video_track().unwrap();
+        assert_eq!(video.width, Some(1920));
+        assert_eq!(video.height, Some(1080));
+        assert_eq!(video.frame_rate, Some(25.0));
+
+        let audio = output.audio_tracks();
+        assert_eq!(audio.len(), 1);
+        assert_eq!(audio[0].channels, Some(2));
+        assert_eq!(audio[0].sampling_rate, Some(48000));
+    }
+
+    #[test]
+    fn test_tracks_selected_by_type() {
+        let json_loader = MediaInfoJsonLoader{};
+        let json = r#"{"media": {"track": [
+            {"@type": "Audio", "Channels": "2"},
+            {"@type": "General", "Duration": "3.500"},
+            {"@type": "Video", "Width": "720", "Height": "1280"}
+        ]}}"#;
+
+        let output = json_loader.load_json_from_string(json).unwrap();
+
+        assert_eq!(output.duration().unwrap(), Duration::from_millis(3500));
+        assert_eq!(output.video_track().unwrap().width, Some(720));
+        assert_eq!(output.audio_tracks()[0].channels, Some(2));
+    }
+
+    #[test]
+    fn test_missing_video_track() {
+        let json_loader = MediaInfoJsonLoader{};
+        let json = r#"{"media": {"track": [{"@type": "General"}, {"@type": "Audio"}]}}"#;
+
+        let output = json_loader.load_json_from_string(json).unwrap();
+
+        assert!(output.video_track().is_err());
+    }
+
     #[test]
     fn test_make_output_path() {
         let result = compute_output_file(&String::from("/Users/user.name/path/to/video.mp4"), "mp4");
         println!("{}", result.ok().unwrap());
 
         // [WARNING] needs creating a tmp file
-        fs::write("/tmp/example.mp4", b"");
+        fs::write("/tmp/example.mp4", b"").unwrap();
         let result = compute_output_file(&String::from("/tmp/example.mp4"), "mp4");
         println!("{}", result.ok().unwrap());
     }
 
     #[test]
     fn test_get_name_from_path() {
-        let result = get_file_name_from_path(&Path::new("/tmp/example.mp4"));
+        let result = get_file_name_from_path(Path::new("/tmp/example.mp4"));
 
         assert_eq!(result.ok().unwrap(), String::from("example"));
     }
 
     #[test]
     fn test_get_extension_from_path() {
-        let result = get_extension_from_path(&Path::new("/tmp/example.mp4"), "mp4");
+        let result = get_extension_from_path(Path::new("/tmp/example.mp4"), "mp4");
         assert_eq!(result.ok().unwrap(), String::from("mp4"));
 
-        let point_in_path = get_extension_from_path(&Path::new("/Users/user.name/dir/example.mp4"), "mp4");
+        let point_in_path = get_extension_from_path(Path::new("/Users/user.name/dir/example.mp4"), "mp4");
         assert_eq!(point_in_path.ok().unwrap(), String::from("mp4"));
     }
 }
\ No newline at end of file
@@ -1,10 +1,12 @@
 use std::fs;
 use log::{debug, error};
 use uuid::Uuid;
-use crate::atium::common::command_manager::CommandManager;
+use crate::atium::common::analysis_helper::{AnalysisOutput, MediaInfoJsonLoader};
+use crate::atium::common::analyzer::MediaAnalyzer;
+use crate::atium::common::command_manager::{CommandManager, ExecutionControl};
 use crate::atium::common::error::AtiumError;
 use crate::atium::common::model::{InfoExtractorResponse, InfoExtractorResponseOutput, InfoFormat, InfoOutputType};
-use crate::InfoExtractorRequest;
+use crate::atium::common::model::InfoExtractorRequest;
 
 /// MediaInfo Engine Service for info extraction
 pub struct MediaInfoExtractorService {
@@ -12,7 +14,7 @@ pub struct MediaInfoExtractorService {
 }
 
 impl MediaInfoExtractorService {
-    fn write_info_to_file(&self, output: std::process::Output, out_filepath: String, format: InfoFormat) -> Result<String, &'static str> {
+    fn write_info_to_file(&self, output: std::process::Output, out_filepath: String, format: InfoFormat) -> Result<String, AtiumError> {
         let ext = match format {
             InfoFormat::Json => ".json",
             InfoFormat::Html => ".html",
@@ -32,12 +34,12 @@ impl MediaInfoExtractorService {
             },
             Err(err) => {
                 error!("Could not write to file: {}", err);
-                Err("could not write to file!")
+                Err(AtiumError::Output { message: format!("Could not write info to [{}]", path), source: Some(err) })
             }
         }
     }
     fn write_result(&self, execution_result: std::process::Output, request: InfoExtractorRequest, format: InfoFormat) -> Result<InfoExtractorResponse, AtiumError> {
-        return match request.output_type.unwrap_or(InfoOutputType::Stdout) {
+        match request.output_type.unwrap_or(InfoOutputType::Stdout) {
             InfoOutputType::Stdout => self.command_manager.print_command_output(execution_result.stdout)
                 .map(|_| InfoExtractorResponse {
                     output: InfoExtractorResponseOutput {
@@ -53,7 +55,6 @@ impl MediaInfoExtractorService {
                             content: None
                         }
                     })
-                    .map_err(|err_msg| AtiumError::IOError(err_msg.to_string()))
             }
             InfoOutputType::Plain => self.command_manager.get_command_output_as_string(execution_result.stdout)
                 .map(|output| InfoExtractorResponse {
@@ -71,6 +72,11 @@ impl MediaInfoExtractorService {
         Ok(Self { command_manager })
     }
     pub fn get_info(&self, request: InfoExtractorRequest) -> Result<InfoExtractorResponse, AtiumError> {
+        self.get_info_with_control(request, &ExecutionControl::default())
+    }
+    /// Extracts media info like [`MediaInfoExtractorService::get_info`], `mediainfo` is killed
+    /// when `control` times out or is cancelled
+    pub fn get_info_with_control(&self, request: InfoExtractorRequest, control: &ExecutionControl) -> Result<InfoExtractorResponse, AtiumError> {
 
         let binding = request.clone();
 
@@ -97,17 +103,42 @@ impl MediaInfoExtractorService {
 
         args.push(binding.input.as_str());
 
-        return match self.command_manager.execute_with_args(args) {
-            Ok(execution_result) => {
-                if !execution_result.status.success() {
-                    // WARN: MEDIAINFO WRITES ERRORS TO STDOUT
-                    self.command_manager.print_command_output(execution_result.stdout)?;
-                    return Err(AtiumError::CommandError("Command execution returned ERROR status".to_string()))
-                }
+        // WARN: MEDIAINFO WRITES ERRORS TO STDOUT, they end up in the tail of the failure
+        let execution_result = self.command_manager.execute_with_control(args, control)
+            .map_err(|err| err.into_probe("mediainfo could not analyze the input"))?;
+
+        self.write_result(execution_result, request, format)
+    }
+}
+
+impl MediaAnalyzer for MediaInfoExtractorService {
+    fn analyze_with_control(&self, input: &str, control: &ExecutionControl) -> Result<AnalysisOutput, AtiumError> {
+        let request = InfoExtractorRequest {
+            input: input.to_string(),
+            format: Some(InfoFormat::Json),
+            full: None,
+            output_file: None,
+            output_type: Some(InfoOutputType::Plain)
+        };
 
-                self.write_result(execution_result, request, format)
+        let response = self.get_info_with_control(request, control)?
+            .output
+            .content
+            .unwrap_or_default();
+
+        let media_info_loader = MediaInfoJsonLoader{};
+        match media_info_loader.load_json_from_string(&response) {
+            Ok(output) => {
+                debug!("Analysis done!");
+                Ok(output)
+            }
+            Err(err) => {
+                error!("Could not analyze input file: {}", err);
+                Err(err)
             }
-            Err(_) => Err(AtiumError::CommandError("Could not execute command".to_string()))
         }
     }
-}
\ No newline at end of file
+    fn name(&self) -> &'static str {
+        "mediainfo"
+    }
+}
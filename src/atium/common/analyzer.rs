@@ -0,0 +1,38 @@
+use log::{debug, warn};
+use crate::atium::common::analysis_helper::AnalysisOutput;
+use crate::atium::common::analysis_service::MediaInfoExtractorService;
+use crate::atium::common::command_manager::ExecutionControl;
+use crate::atium::common::error::AtiumError;
+use crate::atium::common::ffprobe_service::FfprobeAnalyzer;
+
+/// A media analysis backend.
+///
+/// Every implementation normalises its own output into the same [`AnalysisOutput`]
+/// model, using `mediainfo` field names and units.
+pub trait MediaAnalyzer: Send + Sync {
+    /// Analyzes the file at `input` and returns its tracks
+    fn analyze(&self, input: &str) -> Result<AnalysisOutput, AtiumError> {
+        self.analyze_with_control(input, &ExecutionControl::default())
+    }
+    /// Analyzes the file at `input`, the analysis is stopped when `control` times out or is cancelled
+    fn analyze_with_control(&self, input: &str, control: &ExecutionControl) -> Result<AnalysisOutput, AtiumError>;
+    /// A short name of the backend, used for logging
+    fn name(&self) -> &'static str;
+}
+
+/// Returns the first analysis backend available on the executing environment.
+///
+/// `mediainfo` is preferred, `ffprobe` is used when `mediainfo` is not installed.
+pub fn load_analyzer() -> Result<Box<dyn MediaAnalyzer>, AtiumError> {
+    let analyzer: Box<dyn MediaAnalyzer> = match MediaInfoExtractorService::new() {
+        Ok(service) => Box::new(service),
+        Err(err) => {
+            warn!("mediainfo not available ({}), falling back to ffprobe", err);
+            Box::new(FfprobeAnalyzer::new()?)
+        }
+    };
+
+    debug!("Using [{}] as analysis backend", analyzer.name());
+
+    Ok(analyzer)
+}
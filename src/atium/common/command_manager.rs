@@ -1,7 +1,195 @@
 use std::ffi::OsStr;
-use std::process::{Command, Output};
-use log::debug;
-use crate::atium::common::error::AtiumError;
+use std::io;
+use std::io::{BufRead, BufReader, Read};
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::process::{Child, Command, ExitStatus, Output, Stdio};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use log::{debug, warn};
+use crate::atium::common::error::{AtiumError, CommandFailure};
+
+/// How often a running command is checked against its timeout and cancellation token
+const WATCHDOG_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A token that stops the commands it is attached to once cancelled.
+///
+/// Clones share the same state, so the token can be cancelled from another thread.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>
+}
+
+impl CancellationToken {
+    /// Creates a new, not cancelled, [`CancellationToken`]
+    pub fn new() -> Self {
+        CancellationToken::default()
+    }
+    /// Cancels the token, running commands are killed shortly after
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst)
+    }
+    /// Returns true when the token has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+}
+
+/// Limits applied to a single command execution
+#[derive(Clone, Debug, Default)]
+pub struct ExecutionControl {
+    /// Maximum running time, the command is killed when exceeded
+    pub timeout: Option<Duration>,
+    /// Token stopping the command when cancelled
+    pub cancellation: Option<CancellationToken>
+}
+
+impl ExecutionControl {
+    /// Returns true when the cancellation token has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        matches!(&self.cancellation, Some(token) if token.is_cancelled())
+    }
+}
+
+/// The reason a command was killed by its [`Watchdog`]
+enum Interruption {
+    Timeout(Duration),
+    Cancelled
+}
+
+impl Interruption {
+    fn into_error(self, command: &str) -> AtiumError {
+        match self {
+            Interruption::Timeout(timeout) => AtiumError::Timeout { command: command.to_string(), timeout },
+            Interruption::Cancelled => AtiumError::Cancelled { command: command.to_string() }
+        }
+    }
+}
+
+/// Watches a running command and kills its process group on timeout or cancellation
+struct Watchdog {
+    finished: Arc<AtomicBool>,
+    handle: thread::JoinHandle<Option<Interruption>>
+}
+
+impl Watchdog {
+    /// Starts watching `child`, returns None when there is nothing to watch for
+    fn start(child: &Child, control: &ExecutionControl) -> Option<Watchdog> {
+        if control.timeout.is_none() && control.cancellation.is_none() {
+            return None
+        }
+
+        let finished = Arc::new(AtomicBool::new(false));
+        let watching = finished.clone();
+        let control = control.clone();
+        let child_id = child.id();
+        let started = Instant::now();
+
+        let handle = thread::spawn(move || {
+            while !watching.load(Ordering::SeqCst) {
+                let interruption = if control.is_cancelled() {
+                    Some(Interruption::Cancelled)
+                } else {
+                    match control.timeout {
+                        Some(timeout) if started.elapsed() >= timeout => Some(Interruption::Timeout(timeout)),
+                        _ => None
+                    }
+                };
+
+                if interruption.is_some() {
+                    // an exited command has nothing left to kill, and its run is not interrupted
+                    #[cfg(unix)]
+                    if has_exited(child_id, false) {
+                        return None
+                    }
+                    kill_process_group(child_id);
+                    return interruption
+                }
+
+                thread::sleep(WATCHDOG_INTERVAL);
+            }
+            None
+        });
+
+        Some(Watchdog { finished, handle })
+    }
+    /// Stops watching and returns the reason the command was killed, if it was
+    fn stop(self) -> Option<Interruption> {
+        self.finished.store(true, Ordering::SeqCst);
+        self.handle.join().unwrap_or(None)
+    }
+}
+
+/// Tells whether the command has exited, waiting for it when `block` is set.
+///
+/// The command is left unreaped, so that its pid, and the id of the group it leads, cannot be reused
+/// by another process before [`Child::wait`] collects it: the [`Watchdog`] never signals a stranger.
+#[cfg(unix)]
+fn has_exited(child_id: u32, block: bool) -> bool {
+    let options = libc::WEXITED | libc::WNOWAIT | if block { 0 } else { libc::WNOHANG };
+    loop {
+        // SAFETY: waitid only fills the zeroed siginfo_t, WNOWAIT keeps the command from being reaped
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        if unsafe { libc::waitid(libc::P_PID, child_id as libc::id_t, &mut info, options) } == 0 {
+            // SAFETY: si_pid is set by waitid, and stays 0 when WNOHANG finds the command running
+            return unsafe { info.si_pid() } != 0
+        }
+        if io::Error::last_os_error().kind() != io::ErrorKind::Interrupted {
+            // the error is reported by `Child::wait`
+            return true
+        }
+    }
+}
+
+/// Waits for the command, the [`Watchdog`] is stopped before the command is reaped
+fn wait_watched(child: &mut Child, watchdog: Option<Watchdog>) -> (io::Result<ExitStatus>, Option<Interruption>) {
+    #[cfg(unix)]
+    {
+        has_exited(child.id(), true);
+        let interruption = watchdog.and_then(Watchdog::stop);
+        (child.wait(), interruption)
+    }
+    // the handle held by `Child` keeps the pid from being reused until it is dropped
+    #[cfg(not(unix))]
+    {
+        let status = child.wait();
+        (status, watchdog.and_then(Watchdog::stop))
+    }
+}
+
+/// Reads a command pipe to the end on its own thread, so that a full pipe cannot block the command
+fn drain<R: Read + Send + 'static>(pipe: Option<R>) -> thread::JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut buffer = Vec::new();
+        if let Some(mut pipe) = pipe {
+            if let Err(err) = pipe.read_to_end(&mut buffer) {
+                warn!("Could not read command output: {}", err);
+            }
+        }
+        buffer
+    })
+}
+
+/// Kills the process group led by the command, so that its own children are stopped as well
+#[cfg(unix)]
+fn kill_process_group(child_id: u32) {
+    // SAFETY: kill only sends a signal, the group id comes from a spawned child leading its own group
+    if unsafe { libc::kill(-(child_id as libc::pid_t), libc::SIGKILL) } != 0 {
+        warn!("Could not kill process group [{}]", child_id);
+    }
+}
+
+#[cfg(not(unix))]
+fn kill_process_group(child_id: u32) {
+    let result = Command::new("taskkill")
+        .args(["/F", "/T", "/PID", &child_id.to_string()])
+        .output();
+    if result.is_err() {
+        warn!("Could not kill process tree [{}]", child_id);
+    }
+}
 
 /// A simple struct that holds the logic needed for managing external commands
 pub struct CommandManager {
@@ -21,13 +209,13 @@ impl CommandManager {
         match command_output {
             Ok(cmd_out) => {
                 if !cmd_out.status.success() {
-                    return Err(AtiumError::CommandError("Command execution returned ERROR status".to_string()))
+                    return Err(AtiumError::Dependency(format!("{} is not working, check returned ERROR status", command)))
                 }
 
                 Ok(CommandManager { command })
             }
-            Err(_) =>
-                Err(AtiumError::CommandError("error when executing command".to_string()))
+            Err(err) =>
+                Err(AtiumError::Dependency(format!("{} is not available: {}", command, err)))
         }
     }
     /// Prints Command Output to stdout
@@ -37,29 +225,231 @@ impl CommandManager {
                 content.lines().for_each(|x| println!("{}", x));
                 Ok(())
             },
-            Err(_) => Err(AtiumError::IOError("error when writing to stdout".to_string())),
+            Err(_) => Err(AtiumError::Output { message: "error when writing to stdout".to_string(), source: None }),
         }
     }
     /// Returns a [`String`] containing the content of the command execution output
     pub fn get_command_output_as_string(&self, output: Vec<u8>) -> Result<String, AtiumError> {
         String::from_utf8(output)
-            .map_err(|_| AtiumError::IOError("error when writing to stdout".to_string()))
+            .map_err(|err| AtiumError::Command(
+                CommandFailure::from_io(self.command.clone(), io::Error::new(io::ErrorKind::InvalidData, err))
+            ))
     }
-    /// Executes the previously built command by suppplying a collection of arguments
-    pub fn execute_with_args(&self, args: Vec<&str>) -> Result<Output, AtiumError> {
-        let mut cmd = Command::new(self.command.clone());
-        let cmd_with_args = cmd.args(args);
-
-
+    fn print_command_args(&self, cmd: &Command) {
         debug!("Commands list begin:");
-        let all_args: Vec<&OsStr> = cmd_with_args.get_args().collect();
+        let all_args: Vec<&OsStr> = cmd.get_args().collect();
         all_args.iter().for_each(|a| debug!(" {}\n", a.to_str().unwrap_or("")));
         debug!("Commands list end");
+    }
+    fn command_line(&self, args: &[&str]) -> String {
+        let mut command_line = vec![self.command.as_str()];
+        command_line.extend(args);
+
+        command_line.join(" ")
+    }
+    /// Returns the [`Output`] of a command that exited successfully, an [`AtiumError::Command`] otherwise
+    fn check_status(&self, command_line: String, output: Output) -> Result<Output, AtiumError> {
+        if !output.status.success() {
+            return Err(AtiumError::Command(CommandFailure::from_output(command_line, &output)))
+        }
+        Ok(output)
+    }
+    fn build_command(&self, args: Vec<&str>) -> Command {
+        let mut cmd = Command::new(self.command.clone());
+        cmd.args(args);
+        // `spawn` inherits stdin: ffmpeg would read the terminal keystrokes, and a background
+        // process group touching the terminal settings is stopped by SIGTTOU
+        cmd.stdin(Stdio::null());
+        // The command leads its own process group, so that it can be killed along with its children
+        #[cfg(unix)]
+        cmd.process_group(0);
+
+        self.print_command_args(&cmd);
+
+        cmd
+    }
+    fn check_cancelled(&self, control: &ExecutionControl) -> Result<(), AtiumError> {
+        if control.is_cancelled() {
+            return Err(Interruption::Cancelled.into_error(&self.command))
+        }
+        Ok(())
+    }
+    /// Executes the previously built command by suppplying a collection of arguments,
+    /// a command exiting with an error status returns an [`AtiumError::Command`]
+    pub fn execute_with_args(&self, args: Vec<&str>) -> Result<Output, AtiumError> {
+        self.execute_with_control(args, &ExecutionControl::default())
+    }
+    /// Executes the previously built command by suppplying a collection of arguments,
+    /// the command is killed when `control` times out or is cancelled
+    pub fn execute_with_control(&self, args: Vec<&str>, control: &ExecutionControl) -> Result<Output, AtiumError> {
+        self.check_cancelled(control)?;
+
+        let command_line = self.command_line(&args);
+        let mut child = self.build_command(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| AtiumError::Command(CommandFailure::from_io(command_line.clone(), err)))?;
+
+        let watchdog = Watchdog::start(&child, control);
+        let stdout_reader = drain(child.stdout.take());
+        let stderr_reader = drain(child.stderr.take());
+
+        let (status, interruption) = wait_watched(&mut child, watchdog);
+
+        if let Some(interruption) = interruption {
+            return Err(interruption.into_error(&self.command))
+        }
+
+        let status = status
+            .map_err(|err| AtiumError::Command(CommandFailure::from_io(command_line.clone(), err)))?;
+        let stdout = stdout_reader.join().unwrap_or_default();
+        let stderr = stderr_reader.join().unwrap_or_default();
 
-        match cmd_with_args.output() {
-            Ok(result) => Ok(result),
-            Err(_) => Err(AtiumError::CommandError("error when executing command".to_string())),
+        self.check_status(command_line, Output { status, stdout, stderr })
+    }
+    /// Executes the previously built command by suppplying a collection of arguments,
+    /// each line written to stdout is handed to `on_line` as soon as it is printed.
+    /// The returned [`Output`] holds the whole stderr, stdout is left empty.
+    /// The command is killed when `control` times out or is cancelled.
+    pub fn execute_streaming(&self, args: Vec<&str>, control: &ExecutionControl, on_line: &mut dyn FnMut(&str)) -> Result<Output, AtiumError> {
+        self.check_cancelled(control)?;
+
+        let command_line = self.command_line(&args);
+        let mut child = self.build_command(args)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|err| AtiumError::Command(CommandFailure::from_io(command_line.clone(), err)))?;
+
+        let watchdog = Watchdog::start(&child, control);
+
+        let stderr_reader = drain(child.stderr.take());
+
+        if let Some(stdout) = child.stdout.take() {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(line) => on_line(&line),
+                    Err(err) => {
+                        warn!("Could not read command stdout: {}", err);
+                        break
+                    }
+                }
+            }
         }
+
+        let (status, interruption) = wait_watched(&mut child, watchdog);
+
+        if let Some(interruption) = interruption {
+            return Err(interruption.into_error(&self.command))
+        }
+
+        let status = status
+            .map_err(|err| AtiumError::Command(CommandFailure::from_io(command_line.clone(), err)))?;
+        let stderr = stderr_reader.join().unwrap_or_default();
+
+        self.check_status(command_line, Output { status, stdout: Vec::new(), stderr })
     }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shell() -> CommandManager {
+        CommandManager::new("sh".to_string(), vec!["-c", "exit 0"]).unwrap()
+    }
+
+    #[test]
+    fn test_execute_without_control() {
+        let output = shell().execute_with_args(vec!["-c", "echo atium"]).unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "atium\n");
+    }
+
+    #[test]
+    fn test_failure_status_and_stderr_tail() {
+        let result = shell().execute_with_args(vec!["-c", "echo first >&2; echo last >&2; exit 3"]);
+
+        match result {
+            Err(AtiumError::Command(failure)) => {
+                assert_eq!(failure.exit_status, Some(3));
+                assert_eq!(failure.stderr_tail, vec!["first".to_string(), "last".to_string()]);
+                assert!(failure.command.starts_with("sh -c"));
+            }
+            _ => panic!("a failed command must return its status")
+        }
+    }
+
+    #[test]
+    fn test_timeout_kills_command() {
+        let control = ExecutionControl { timeout: Some(Duration::from_millis(200)), cancellation: None };
+        let started = Instant::now();
+
+        let result = shell().execute_with_control(vec!["-c", "sleep 5"], &control);
+
+        assert!(matches!(result, Err(AtiumError::Timeout { .. })));
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+
+    #[test]
+    fn test_cancellation_kills_process_group() {
+        let token = CancellationToken::new();
+        let control = ExecutionControl { timeout: None, cancellation: Some(token.clone()) };
+        let canceller = thread::spawn(move || {
+            thread::sleep(Duration::from_millis(200));
+            token.cancel();
+        });
+        let started = Instant::now();
+
+        // the background sleep keeps stdout open, so reading only ends once the whole group is killed
+        let result = shell().execute_streaming(vec!["-c", "sleep 5 & wait"], &control, &mut |_| {});
+        canceller.join().unwrap();
+
+        assert!(matches!(result, Err(AtiumError::Cancelled { .. })));
+        assert!(started.elapsed() < Duration::from_secs(3));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_streaming_stdin_not_inherited() {
+        let mut lines = Vec::new();
+
+        shell().execute_streaming(vec!["-c", "readlink /proc/$$/fd/0"], &ExecutionControl::default(), &mut |line| lines.push(line.to_string())).unwrap();
+
+        assert_eq!(lines, vec!["/dev/null".to_string()]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_stdin_not_inherited() {
+        let output = shell().execute_with_args(vec!["-c", "readlink /proc/$$/fd/0"]).unwrap();
+
+        assert_eq!(String::from_utf8(output.stdout).unwrap(), "/dev/null\n");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_exited_command_left_unreaped() {
+        let mut child = shell().build_command(vec!["-c", "exit 0"]).spawn().unwrap();
+
+        assert!(has_exited(child.id(), true));
+        assert!(has_exited(child.id(), false));
+        // the zombie keeps its pid until it is reaped
+        let stat = std::fs::read_to_string(format!("/proc/{}/stat", child.id())).unwrap();
+        assert!(stat.contains(") Z "));
+        assert!(child.wait().unwrap().success());
+    }
+
+    #[test]
+    fn test_already_cancelled() {
+        let token = CancellationToken::new();
+        token.cancel();
+        let control = ExecutionControl { timeout: None, cancellation: Some(token) };
+
+        let result = shell().execute_with_control(vec!["-c", "exit 0"], &control);
+
+        assert!(matches!(result, Err(AtiumError::Cancelled { .. })));
+    }
+}
@@ -1,20 +1,275 @@
+use std::error::Error;
 use std::fmt;
 use std::fmt::{Formatter};
+use std::io;
+use std::process::Output;
+use std::time::Duration;
+
+/// Number of stderr lines kept in a [`CommandFailure`]
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Details of an external command that failed
+#[derive(Debug)]
+pub struct CommandFailure {
+    /// The executed command line
+    pub command: String,
+    /// Exit code of the command, None when it was killed by a signal or never started
+    pub exit_status: Option<i32>,
+    /// The last lines written by the command on stderr
+    pub stderr_tail: Vec<String>,
+    /// The I/O error raised when starting or reading the command
+    pub source: Option<io::Error>
+}
+
+impl CommandFailure {
+    /// Builds a [`CommandFailure`] out of the [`Output`] of a command that exited with an error.
+    ///
+    /// When stderr is empty the tail is taken from stdout, as `mediainfo` prints its errors there.
+    pub fn from_output(command: String, output: &Output) -> Self {
+        let stream = if output.stderr.iter().all(u8::is_ascii_whitespace) {
+            &output.stdout
+        } else {
+            &output.stderr
+        };
+
+        CommandFailure {
+            command,
+            exit_status: output.status.code(),
+            stderr_tail: tail(&String::from_utf8_lossy(stream), STDERR_TAIL_LINES),
+            source: None
+        }
+    }
+    /// Builds a [`CommandFailure`] for a command that could not be started or read
+    pub fn from_io(command: String, source: io::Error) -> Self {
+        CommandFailure { command, exit_status: None, stderr_tail: Vec::new(), source: Some(source) }
+    }
+}
+
+impl fmt::Display for CommandFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match (&self.source, self.exit_status) {
+            (Some(err), _) => write!(f, "`{}` failed: {}", self.command, err),
+            (None, Some(code)) => write!(f, "`{}` exited with status {}", self.command, code),
+            (None, None) => write!(f, "`{}` was terminated by a signal", self.command)
+        }
+    }
+}
+
+impl Error for CommandFailure {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_ref().map(|err| err as &(dyn Error + 'static))
+    }
+}
+
+/// Returns the last `count` non empty lines of `content`
+fn tail(content: &str, count: usize) -> Vec<String> {
+    let lines: Vec<&str> = content.lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty())
+        .collect();
+
+    lines[lines.len().saturating_sub(count)..].iter()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// The category of an [`AtiumError`], each one has its own CLI exit code
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorCategory {
+    InvalidRequest,
+    Input,
+    Dependency,
+    Probe,
+    Encode,
+    Thumbnail,
+    Output,
+    Command,
+    Timeout,
+    Cancelled
+}
+
+impl ErrorCategory {
+    /// A stable, machine-readable name of the category
+    pub fn name(&self) -> &'static str {
+        match self {
+            ErrorCategory::InvalidRequest => "invalid_request",
+            ErrorCategory::Input => "input",
+            ErrorCategory::Dependency => "dependency",
+            ErrorCategory::Probe => "probe",
+            ErrorCategory::Encode => "encode",
+            ErrorCategory::Thumbnail => "thumbnail",
+            ErrorCategory::Output => "output",
+            ErrorCategory::Command => "command",
+            ErrorCategory::Timeout => "timeout",
+            ErrorCategory::Cancelled => "cancelled"
+        }
+    }
+    /// The exit code used by the CLI
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            ErrorCategory::InvalidRequest => 2,
+            ErrorCategory::Input => 3,
+            ErrorCategory::Dependency => 4,
+            ErrorCategory::Probe => 5,
+            ErrorCategory::Encode => 6,
+            ErrorCategory::Thumbnail => 7,
+            ErrorCategory::Output => 8,
+            ErrorCategory::Command => 9,
+            ErrorCategory::Timeout => 124,
+            ErrorCategory::Cancelled => 130
+        }
+    }
+}
 
 #[derive(Debug)]
 /// This is a specific Error for Atium
 pub enum AtiumError {
-    ConversionError(String),
-    IOError(String),
-    CommandError(String)
+    /// The request holds invalid or incompatible options
+    InvalidRequest(String),
+    /// The input is missing or cannot be read
+    Input { message: String, source: Option<io::Error> },
+    /// A required tool, encoder or analysis backend is not available
+    Dependency(String),
+    /// The input could not be analyzed
+    Probe { message: String, command: Option<CommandFailure> },
+    /// The encoding failed
+    Encode { message: String, command: Option<CommandFailure> },
+    /// The thumbnail extraction failed
+    Thumbnail { message: String, command: Option<CommandFailure> },
+    /// The output cannot be written
+    Output { message: String, source: Option<io::Error> },
+    /// An external command failed outside of a specific stage
+    Command(CommandFailure),
+    /// An external command ran longer than its timeout and was killed
+    Timeout { command: String, timeout: Duration },
+    /// An external command was stopped through its cancellation token
+    Cancelled { command: String }
+}
+
+impl AtiumError {
+    pub(crate) fn probe(message: impl Into<String>) -> Self {
+        AtiumError::Probe { message: message.into(), command: None }
+    }
+    pub(crate) fn encode(message: impl Into<String>) -> Self {
+        AtiumError::Encode { message: message.into(), command: None }
+    }
+    /// Turns a generic [`AtiumError::Command`] into an [`AtiumError::Probe`], other errors are kept
+    pub(crate) fn into_probe(self, message: &str) -> Self {
+        match self {
+            AtiumError::Command(failure) => AtiumError::Probe { message: message.to_string(), command: Some(failure) },
+            other => other
+        }
+    }
+    /// Turns a generic [`AtiumError::Command`] into an [`AtiumError::Encode`], other errors are kept
+    pub(crate) fn into_encode(self, message: &str) -> Self {
+        match self {
+            AtiumError::Command(failure) => AtiumError::Encode { message: message.to_string(), command: Some(failure) },
+            other => other
+        }
+    }
+    /// Turns a generic [`AtiumError::Command`] into an [`AtiumError::Thumbnail`], other errors are kept
+    pub(crate) fn into_thumbnail(self, message: &str) -> Self {
+        match self {
+            AtiumError::Command(failure) => AtiumError::Thumbnail { message: message.to_string(), command: Some(failure) },
+            other => other
+        }
+    }
+    /// Returns the [`ErrorCategory`] of the error
+    pub fn category(&self) -> ErrorCategory {
+        match self {
+            AtiumError::InvalidRequest(_) => ErrorCategory::InvalidRequest,
+            AtiumError::Input { .. } => ErrorCategory::Input,
+            AtiumError::Dependency(_) => ErrorCategory::Dependency,
+            AtiumError::Probe { .. } => ErrorCategory::Probe,
+            AtiumError::Encode { .. } => ErrorCategory::Encode,
+            AtiumError::Thumbnail { .. } => ErrorCategory::Thumbnail,
+            AtiumError::Output { .. } => ErrorCategory::Output,
+            AtiumError::Command(_) => ErrorCategory::Command,
+            AtiumError::Timeout { .. } => ErrorCategory::Timeout,
+            AtiumError::Cancelled { .. } => ErrorCategory::Cancelled
+        }
+    }
+    /// Returns the exit code of the error category
+    pub fn exit_code(&self) -> i32 {
+        self.category().exit_code()
+    }
+    /// Returns the failed external command, if any
+    pub fn command_failure(&self) -> Option<&CommandFailure> {
+        match self {
+            AtiumError::Probe { command, .. }
+            | AtiumError::Encode { command, .. }
+            | AtiumError::Thumbnail { command, .. } => command.as_ref(),
+            AtiumError::Command(failure) => Some(failure),
+            _ => None
+        }
+    }
 }
 
 impl fmt::Display for AtiumError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        match *self {
-            AtiumError::ConversionError(ref msg) => write!(f, "Conversion Error: {}", msg),
-            AtiumError::IOError(ref msg) => write!(f, "I/O Error: {}", msg),
-            AtiumError::CommandError(ref msg) => write!(f, "Command Error: {}", msg)
+        match self {
+            AtiumError::InvalidRequest(msg) => write!(f, "Invalid Request: {}", msg),
+            AtiumError::Input { message, .. } => write!(f, "Input Error: {}", message),
+            AtiumError::Dependency(msg) => write!(f, "Dependency Error: {}", msg),
+            AtiumError::Probe { message, .. } => write!(f, "Probe Error: {}", message),
+            AtiumError::Encode { message, .. } => write!(f, "Encode Error: {}", message),
+            AtiumError::Thumbnail { message, .. } => write!(f, "Thumbnail Error: {}", message),
+            AtiumError::Output { message, .. } => write!(f, "Output Error: {}", message),
+            AtiumError::Command(failure) => write!(f, "Command Error: {}", failure),
+            AtiumError::Timeout { command, timeout } =>
+                write!(f, "Timeout: {} killed after {:.1}s", command, timeout.as_secs_f64()),
+            AtiumError::Cancelled { command } => write!(f, "Cancelled: {} killed on cancellation", command)
+        }
+    }
+}
+
+impl Error for AtiumError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            AtiumError::Input { source, .. } | AtiumError::Output { source, .. } =>
+                source.as_ref().map(|err| err as &(dyn Error + 'static)),
+            AtiumError::Command(failure) => Some(failure),
+            _ => self.command_failure().map(|failure| failure as &(dyn Error + 'static))
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tail() {
+        let content = (1..=30).map(|line| format!("line {}\n", line)).collect::<String>();
+
+        let lines = tail(&content, STDERR_TAIL_LINES);
+
+        assert_eq!(lines.len(), 20);
+        assert_eq!(lines[0], "line 11");
+        assert_eq!(lines[19], "line 30");
+        assert_eq!(tail("only\n\n", 20), vec!["only".to_string()]);
+    }
+
+    #[test]
+    fn test_source_chain() {
+        let failure = CommandFailure::from_io(
+            "ffmpeg -i missing.mp4".to_string(),
+            io::Error::new(io::ErrorKind::NotFound, "not found")
+        );
+        let err = AtiumError::Command(failure).into_encode("Conversion failed");
+
+        assert_eq!(err.category(), ErrorCategory::Encode);
+        assert_eq!(err.exit_code(), 6);
+        let command = err.source().unwrap();
+        assert_eq!(command.to_string(), "`ffmpeg -i missing.mp4` failed: not found");
+        assert!(command.source().unwrap().downcast_ref::<io::Error>().is_some());
+    }
+
+    #[test]
+    fn test_stage_mapping_keeps_other_errors() {
+        let err = AtiumError::Cancelled { command: "ffmpeg".to_string() }.into_probe("Analysis failed");
+
+        assert_eq!(err.category(), ErrorCategory::Cancelled);
+        assert!(err.source().is_none());
+    }
+}
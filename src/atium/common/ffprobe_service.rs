@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use log::{debug, error};
+use serde::Deserialize;
+use serde_json::Value;
+use crate::atium::common::analysis_helper::{AnalysisOutput, Media};
+use crate::atium::common::analyzer::MediaAnalyzer;
+use crate::atium::common::command_manager::{CommandManager, ExecutionControl};
+use crate::atium::common::error::AtiumError;
+use crate::atium::common::media::RawTrack;
+
+#[derive(Deserialize)]
+struct FfprobeOutput {
+    #[serde(default)]
+    streams: Vec<FfprobeStream>,
+    format: Option<FfprobeFormat>
+}
+
+#[derive(Deserialize)]
+struct FfprobeSideData {
+    rotation: Option<f64>
+}
+
+#[derive(Deserialize)]
+struct FfprobeStream {
+    codec_name: Option<String>,
+    codec_type: Option<String>,
+    codec_tag_string: Option<String>,
+    width: Option<u32>,
+    height: Option<u32>,
+    sample_aspect_ratio: Option<String>,
+    display_aspect_ratio: Option<String>,
+    field_order: Option<String>,
+    avg_frame_rate: Option<String>,
+    r_frame_rate: Option<String>,
+    duration: Option<String>,
+    bit_rate: Option<String>,
+    bits_per_raw_sample: Option<String>,
+    nb_frames: Option<String>,
+    sample_rate: Option<String>,
+    channels: Option<u32>,
+    channel_layout: Option<String>,
+    #[serde(default)]
+    tags: HashMap<String, String>,
+    #[serde(default)]
+    side_data_list: Vec<FfprobeSideData>
+}
+
+#[derive(Deserialize)]
+struct FfprobeFormat {
+    format_long_name: Option<String>,
+    duration: Option<String>,
+    size: Option<String>,
+    bit_rate: Option<String>
+}
+
+/// FFprobe Engine Service for media analysis
+pub struct FfprobeAnalyzer {
+    command_manager: CommandManager
+}
+
+impl FfprobeAnalyzer {
+    /// Constructs a new instance of [`FfprobeAnalyzer`] by loading and checking `ffprobe` availability
+    pub fn new() -> Result<Self, AtiumError> {
+        let command_manager =
+            CommandManager::new("ffprobe".to_string(), vec!["-version"])?;
+
+        Ok(Self { command_manager })
+    }
+}
+
+impl MediaAnalyzer for FfprobeAnalyzer {
+    fn analyze_with_control(&self, input: &str, control: &ExecutionControl) -> Result<AnalysisOutput, AtiumError> {
+        let args = vec!["-v", "error", "-print_format", "json", "-show_streams", "-show_format", input];
+
+        let content = self.command_manager.execute_with_control(args, control)
+            .and_then(|result| self.command_manager.get_command_output_as_string(result.stdout))
+            .map_err(|err| err.into_probe("ffprobe could not analyze the input"))?;
+        match load_ffprobe_json(&content) {
+            Ok(output) => {
+                debug!("Analysis done!");
+                Ok(output)
+            }
+            Err(err) => {
+                error!("Could not analyze input file: {}", err);
+                Err(err)
+            }
+        }
+    }
+    fn name(&self) -> &'static str {
+        "ffprobe"
+    }
+}
+
+/// Converts the JSON printed by `ffprobe -print_format json -show_streams -show_format`
+/// into an [`AnalysisOutput`] using `mediainfo` field names and units
+pub fn load_ffprobe_json(data: &str) -> Result<AnalysisOutput, AtiumError> {
+    let output: FfprobeOutput = serde_json::from_str(data)
+        .map_err(|err| {
+            error!("Error when parsing {}", err);
+            AtiumError::probe("Could not parse json")
+        })?;
+
+    let count_of = |codec_type: &str| output.streams.iter()
+        .filter(|stream| stream.codec_type.as_deref() == Some(codec_type))
+        .count();
+
+    let mut general = RawTrack::new();
+    insert(&mut general, "@type", Some("General".to_string()));
+    insert(&mut general, "VideoCount", Some(count_of("video").to_string()));
+    insert(&mut general, "AudioCount", Some(count_of("audio").to_string()));
+    insert(&mut general, "TextCount", Some(count_of("subtitle").to_string()));
+    if let Some(format) = &output.format {
+        insert(&mut general, "Format", format.format_long_name.clone());
+        insert(&mut general, "Duration", format.duration.as_deref().and_then(seconds));
+        insert(&mut general, "FileSize", format.size.clone());
+        insert(&mut general, "OverallBitRate", format.bit_rate.clone());
+    }
+    if let Some(video) = output.streams.iter().find(|s| s.codec_type.as_deref() == Some("video")) {
+        insert(&mut general, "FrameRate", frame_rate(video));
+    }
+
+    let mut tracks = vec![general];
+    tracks.extend(output.streams.iter().filter_map(stream_to_track));
+
+    Ok(AnalysisOutput { media: Media { track: tracks } })
+}
+
+fn stream_to_track(stream: &FfprobeStream) -> Option<RawTrack> {
+    let mut track = RawTrack::new();
+    let kind = match stream.codec_type.as_deref()? {
+        "video" => "Video",
+        "audio" => "Audio",
+        "subtitle" => "Text",
+        _ => return None
+    };
+
+    insert(&mut track, "@type", Some(kind.to_string()));
+    insert(&mut track, "Format", stream.codec_name.as_deref().map(format_name));
+    insert(&mut track, "CodecID", stream.codec_tag_string.clone());
+    insert(&mut track, "Duration", stream.duration.as_deref().and_then(seconds));
+    insert(&mut track, "BitRate", stream.bit_rate.clone());
+    insert(&mut track, "Language", stream.tags.get("language").cloned());
+
+    match kind {
+        "Video" => {
+            insert(&mut track, "Width", stream.width.map(|w| w.to_string()));
+            insert(&mut track, "Height", stream.height.map(|h| h.to_string()));
+            insert(&mut track, "PixelAspectRatio", stream.sample_aspect_ratio.as_deref().and_then(ratio));
+            insert(&mut track, "DisplayAspectRatio", display_aspect_ratio(stream));
+            insert(&mut track, "Rotation", rotation(stream));
+            insert(&mut track, "FrameRate", frame_rate(stream));
+            insert(&mut track, "FrameCount", stream.nb_frames.clone());
+            insert(&mut track, "BitDepth", stream.bits_per_raw_sample.clone());
+            insert(&mut track, "ScanType", stream.field_order.as_deref().map(|order| match order {
+                "progressive" => "Progressive".to_string(),
+                _ => "Interlaced".to_string()
+            }));
+        }
+        "Audio" => {
+            insert(&mut track, "Channels", stream.channels.map(|c| c.to_string()));
+            insert(&mut track, "ChannelLayout", stream.channel_layout.clone());
+            insert(&mut track, "SamplingRate", stream.sample_rate.clone());
+        }
+        _ => {
+            insert(&mut track, "Title", stream.tags.get("title").cloned());
+        }
+    }
+
+    Some(track)
+}
+
+fn insert(track: &mut RawTrack, name: &str, value: Option<String>) {
+    if let Some(value) = value {
+        track.insert(name.to_string(), Value::String(value));
+    }
+}
+
+/// Maps `ffprobe` codec names onto `mediainfo` format names
+fn format_name(codec_name: &str) -> String {
+    match codec_name {
+        "h264" => "AVC".to_string(),
+        "hevc" => "HEVC".to_string(),
+        "vp8" => "VP8".to_string(),
+        "vp9" => "VP9".to_string(),
+        "av1" => "AV1".to_string(),
+        "prores" => "ProRes".to_string(),
+        "mpeg2video" => "MPEG Video".to_string(),
+        "aac" => "AAC".to_string(),
+        "mp3" => "MPEG Audio".to_string(),
+        "ac3" => "AC-3".to_string(),
+        "eac3" => "E-AC-3".to_string(),
+        "opus" => "Opus".to_string(),
+        "flac" => "FLAC".to_string(),
+        "mov_text" => "Timed Text".to_string(),
+        "subrip" => "UTF-8".to_string(),
+        "webvtt" => "WebVTT".to_string(),
+        other => other.to_uppercase()
+    }
+}
+
+fn seconds(value: &str) -> Option<String> {
+    value.parse::<f64>().ok().map(|secs| format!("{:.3}", secs))
+}
+
+/// Parses ratios expressed as `num/den` or `num:den`
+fn parse_ratio(value: &str) -> Option<f64> {
+    let (num, den) = value.split_once(['/', ':'])?;
+    let num = num.parse::<f64>().ok()?;
+    let den = den.parse::<f64>().ok()?;
+
+    if den == 0.0 || num == 0.0 {
+        return None
+    }
+
+    Some(num / den)
+}
+
+fn ratio(value: &str) -> Option<String> {
+    parse_ratio(value).map(|r| format!("{:.3}", r))
+}
+
+fn frame_rate(stream: &FfprobeStream) -> Option<String> {
+    stream.avg_frame_rate.as_deref().and_then(ratio)
+        .or_else(|| stream.r_frame_rate.as_deref().and_then(ratio))
+}
+
+fn display_aspect_ratio(stream: &FfprobeStream) -> Option<String> {
+    stream.display_aspect_ratio.as_deref().and_then(ratio)
+        .or_else(|| {
+            let sar = stream.sample_aspect_ratio.as_deref().and_then(parse_ratio).unwrap_or(1.0);
+            let (width, height) = (stream.width?, stream.height?);
+            if height == 0 {
+                return None
+            }
+            Some(format!("{:.3}", width as f64 * sar / height as f64))
+        })
+}
+
+/// `mediainfo` reports the clockwise rotation to apply, the display matrix
+/// exposed by `ffprobe` holds the counter-clockwise one
+fn rotation(stream: &FfprobeStream) -> Option<String> {
+    let degrees = stream.side_data_list.iter()
+        .find_map(|side_data| side_data.rotation.map(|r| -r))
+        .or_else(|| stream.tags.get("rotate").and_then(|r| r.parse::<f64>().ok()))?;
+
+    Some(format!("{:.3}", degrees.rem_euclid(360.0)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::PathBuf;
+    use std::time::Duration;
+    use super::*;
+
+    fn load_fixture() -> AnalysisOutput {
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/ffprobe.json");
+
+        load_ffprobe_json(&fs::read_to_string(d).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_general_track() {
+        let output = load_fixture();
+
+        let general = output.general_track().unwrap();
+        assert_eq!(general.duration, Some(Duration::from_secs(20)));
+        assert_eq!(general.file_size, Some(8154880));
+        assert_eq!(general.video_count, 1);
+        assert_eq!(general.audio_count, 1);
+        assert_eq!(general.text_count, 1);
+    }
+
+    #[test]
+    fn test_video_track() {
+        let output = load_fixture();
+
+        let video = output.video_track().unwrap();
+        assert_eq!(video.format, Some("AVC".to_string()));
+        assert_eq!(video.width, Some(1080));
+        assert_eq!(video.height, Some(1920));
+        assert_eq!(video.display_aspect_ratio, Some(0.562));
+        assert_eq!(video.rotation, Some(90.0));
+        assert_eq!(video.frame_rate, Some(29.97));
+        assert_eq!(video.frame_count, Some(599));
+    }
+
+    #[test]
+    fn test_audio_and_text_tracks() {
+        let output = load_fixture();
+
+        let audio = output.audio_tracks();
+        assert_eq!(audio.len(), 1);
+        assert_eq!(audio[0].channels, Some(2));
+        assert_eq!(audio[0].sampling_rate, Some(48000));
+        assert_eq!(audio[0].bit_rate, Some(192000));
+
+        let text = output.text_tracks();
+        assert_eq!(text[0].title, Some("Italiano".to_string()));
+    }
+}
@@ -0,0 +1,178 @@
+use std::time::Duration;
+
+/// Candidate frames sampled over the whole video
+const CANDIDATES: f64 = 20.0;
+/// Frames per second decoded by the `thumbnail` filter, after the scene detection
+const SAMPLE_RATE: f64 = 2.0;
+/// Luma below which a pixel is considered black by `blackframe`
+const BLACK_THRESHOLD: u32 = 32;
+/// Share of black pixels above which a frame is discarded
+const MAX_BLACK_RATIO: f64 = 0.9;
+/// Average luma of a well exposed frame, out of 255
+const TARGET_BRIGHTNESS: f64 = 115.0;
+/// Spread between the 10th and 90th luma percentiles of a well contrasted frame, out of 255
+const TARGET_CONTRAST: f64 = 150.0;
+/// Scene score from which a frame is considered part of a transition
+const TRANSITION_SCENE_SCORE: f64 = 0.4;
+
+/// Statistics of a candidate frame, as printed by the filters of [`analysis_filter`]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FrameStats {
+    pub timestamp: Duration,
+    /// Average luma, from `signalstats`
+    pub brightness: Option<f64>,
+    /// Spread between the 10th and 90th luma percentiles, from `signalstats`
+    pub contrast: Option<f64>,
+    /// Percentage of black pixels, from `blackframe`
+    pub black: Option<f64>,
+    /// Difference with the previous source frame within `[0, 1]`, from `select`
+    pub scene: Option<f64>
+}
+
+/// The filter chain printing the [`FrameStats`] of the candidate frames of a `duration` long video:
+/// the scene score is computed on every frame, then the `thumbnail` filter keeps the most representative
+/// frame of every batch, which is measured by `signalstats` and `blackframe`
+pub fn analysis_filter(duration: Duration) -> String {
+    let batch = (duration.as_secs_f64() * SAMPLE_RATE / CANDIDATES).ceil().max(1.0) as u64;
+
+    format!(
+        "scale=320:-2,select='gte(scene,0)',fps={},format=yuv420p,thumbnail=n={},signalstats,blackframe=amount=0:threshold={},metadata=mode=print",
+        SAMPLE_RATE, batch, BLACK_THRESHOLD
+    )
+}
+
+/// Parses the frames printed by the `metadata` filter, each one starting with a `pts_time` line
+/// followed by its `lavfi.*` keys. Log prefixes are ignored.
+pub fn parse_frame_stats(output: &str) -> Vec<FrameStats> {
+    // frames along with their 10th and 90th luma percentiles
+    let mut frames: Vec<(FrameStats, Option<f64>, Option<f64>)> = Vec::new();
+
+    for line in output.lines() {
+        if let Some((_, pts_time)) = line.split_once("pts_time:") {
+            if let Some(seconds) = pts_time.split_whitespace().next().and_then(|value| value.parse::<f64>().ok()) {
+                if seconds.is_finite() && seconds >= 0.0 {
+                    frames.push((FrameStats { timestamp: Duration::from_secs_f64(seconds), ..FrameStats::default() }, None, None));
+                }
+            }
+            continue
+        }
+
+        let (key, value) = match line.split_once("lavfi.").and_then(|(_, entry)| entry.split_once('=')) {
+            Some((key, value)) => (key.trim(), value.trim().parse::<f64>().ok()),
+            None => continue
+        };
+        if let Some((frame, low, high)) = frames.last_mut() {
+            match key {
+                "signalstats.YAVG" => frame.brightness = value,
+                "signalstats.YLOW" => *low = value,
+                "signalstats.YHIGH" => *high = value,
+                "blackframe.pblack" => frame.black = value,
+                "scene_score" => frame.scene = value,
+                _ => {}
+            }
+        }
+    }
+
+    frames.into_iter()
+        .map(|(frame, low, high)| FrameStats {
+            contrast: low.zip(high).map(|(low, high)| high - low),
+            ..frame
+        })
+        .collect()
+}
+
+impl FrameStats {
+    /// Scores the frame within `[0, 1]`, favouring well exposed and contrasted frames
+    /// away from scene transitions. Mostly black frames score 0.
+    pub fn score(&self) -> f64 {
+        let black = self.black.unwrap_or(0.0) / 100.0;
+        if black >= MAX_BLACK_RATIO {
+            return 0.0
+        }
+
+        let brightness = self.brightness
+            .map(|brightness| 1.0 - (brightness - TARGET_BRIGHTNESS).abs() / TARGET_BRIGHTNESS)
+            .unwrap_or(0.0)
+            .clamp(0.0, 1.0);
+        let contrast = self.contrast
+            .map(|contrast| contrast / TARGET_CONTRAST)
+            .unwrap_or(0.0)
+            .clamp(0.0, 1.0);
+        let steadiness = 1.0 - (self.scene.unwrap_or(0.0) / TRANSITION_SCENE_SCORE).clamp(0.0, 1.0);
+
+        (0.4 * contrast + 0.3 * brightness + 0.3 * steadiness) * (1.0 - black)
+    }
+}
+
+/// Returns the best scoring frame along with its score, the earliest one on ties
+pub fn best_frame(frames: &[FrameStats]) -> Option<(&FrameStats, f64)> {
+    frames.iter()
+        .map(|frame| (frame, frame.score()))
+        .fold(None, |best: Option<(&FrameStats, f64)>, (frame, score)| match best {
+            Some((_, best_score)) if best_score >= score => best,
+            _ => Some((frame, score))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METADATA_OUTPUT: &str = "[Parsed_metadata_7 @ 0x5581] frame:0    pts:0       pts_time:0
+[Parsed_metadata_7 @ 0x5581] lavfi.scene_score=0.000000
+[Parsed_metadata_7 @ 0x5581] lavfi.signalstats.YMIN=16
+[Parsed_metadata_7 @ 0x5581] lavfi.signalstats.YLOW=16
+[Parsed_metadata_7 @ 0x5581] lavfi.signalstats.YAVG=16.2
+[Parsed_metadata_7 @ 0x5581] lavfi.signalstats.YHIGH=17
+[Parsed_metadata_7 @ 0x5581] lavfi.blackframe.pblack=100
+[Parsed_metadata_7 @ 0x5581] frame:1    pts:11      pts_time:5.5
+[Parsed_metadata_7 @ 0x5581] lavfi.scene_score=0.020000
+[Parsed_metadata_7 @ 0x5581] lavfi.signalstats.YLOW=40
+[Parsed_metadata_7 @ 0x5581] lavfi.signalstats.YAVG=110.5
+[Parsed_metadata_7 @ 0x5581] lavfi.signalstats.YHIGH=190
+[Parsed_metadata_7 @ 0x5581] lavfi.blackframe.pblack=3
+[Parsed_metadata_7 @ 0x5581] frame:2    pts:22      pts_time:11
+[Parsed_metadata_7 @ 0x5581] lavfi.scene_score=0.650000
+[Parsed_metadata_7 @ 0x5581] lavfi.signalstats.YLOW=35
+[Parsed_metadata_7 @ 0x5581] lavfi.signalstats.YAVG=120
+[Parsed_metadata_7 @ 0x5581] lavfi.signalstats.YHIGH=200
+[Parsed_metadata_7 @ 0x5581] lavfi.blackframe.pblack=0
+frame=   60 fps=0.0 q=-0.0 Lsize=N/A time=00:00:30.00 bitrate=N/A speed= 120x
+";
+
+    #[test]
+    fn test_parse_frame_stats() {
+        let frames = parse_frame_stats(METADATA_OUTPUT);
+
+        assert_eq!(frames.len(), 3);
+        assert_eq!(frames[1], FrameStats {
+            timestamp: Duration::from_millis(5500),
+            brightness: Some(110.5),
+            contrast: Some(150.0),
+            black: Some(3.0),
+            scene: Some(0.02)
+        });
+        assert_eq!(frames[2].contrast, Some(165.0));
+    }
+
+    #[test]
+    fn test_best_frame() {
+        let frames = parse_frame_stats(METADATA_OUTPUT);
+
+        assert_eq!(frames[0].score(), 0.0);
+        // the last frame is a scene cut
+        let (best, score) = best_frame(&frames).unwrap();
+        assert_eq!(best.timestamp, Duration::from_millis(5500));
+        assert!(score > 0.9 && score <= 1.0);
+        assert!(best_frame(&[]).is_none());
+    }
+
+    #[test]
+    fn test_analysis_filter() {
+        assert_eq!(
+            analysis_filter(Duration::from_secs(600)),
+            "scale=320:-2,select='gte(scene,0)',fps=2,format=yuv420p,thumbnail=n=60,signalstats,blackframe=amount=0:threshold=32,metadata=mode=print"
+        );
+        assert!(analysis_filter(Duration::from_secs(3)).contains("thumbnail=n=1,"));
+    }
+}
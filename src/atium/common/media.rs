@@ -0,0 +1,303 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use serde_json::Value;
+
+/// A raw track as emitted by the analysis engine: a map of field names to values
+pub type RawTrack = HashMap<String, Value>;
+
+/// Kind of a track, read from its `@type` field
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TrackType {
+    General, Video, Audio, Text, Menu, Other(String)
+}
+
+impl TrackType {
+    fn from_raw(track: &RawTrack) -> TrackType {
+        match string_field(track, "@type").as_deref() {
+            Some("General") => TrackType::General,
+            Some("Video") => TrackType::Video,
+            Some("Audio") => TrackType::Audio,
+            Some("Text") => TrackType::Text,
+            Some("Menu") => TrackType::Menu,
+            Some(other) => TrackType::Other(other.to_string()),
+            None => TrackType::Other(String::from(""))
+        }
+    }
+}
+
+/// Container level information
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct GeneralTrack {
+    pub format: Option<String>,
+    pub duration: Option<Duration>,
+    pub overall_bit_rate: Option<u32>,
+    pub frame_rate: Option<f64>,
+    pub frame_count: Option<u32>,
+    pub file_size: Option<u64>,
+    pub video_count: u32,
+    pub audio_count: u32,
+    pub text_count: u32
+}
+
+/// A video stream
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct VideoTrack {
+    pub format: Option<String>,
+    pub codec_id: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub display_aspect_ratio: Option<f64>,
+    pub pixel_aspect_ratio: Option<f64>,
+    /// Rotation in degrees, as stored in the container
+    pub rotation: Option<f64>,
+    pub frame_rate: Option<f64>,
+    pub frame_count: Option<u32>,
+    pub bit_rate: Option<u32>,
+    pub bit_depth: Option<u32>,
+    pub duration: Option<Duration>,
+    pub scan_type: Option<String>,
+    pub language: Option<String>
+}
+
+/// An audio stream
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct AudioTrack {
+    pub format: Option<String>,
+    pub codec_id: Option<String>,
+    pub channels: Option<u32>,
+    pub channel_layout: Option<String>,
+    pub sampling_rate: Option<u32>,
+    pub bit_rate: Option<u32>,
+    pub duration: Option<Duration>,
+    pub language: Option<String>
+}
+
+/// A subtitle or caption stream
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TextTrack {
+    pub format: Option<String>,
+    pub codec_id: Option<String>,
+    pub language: Option<String>,
+    pub title: Option<String>
+}
+
+/// A chapter entry of a [`MenuTrack`]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chapter {
+    pub start: Duration,
+    pub title: String
+}
+
+/// Chapters list
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct MenuTrack {
+    pub chapters: Vec<Chapter>
+}
+
+/// A strongly typed track, selected by its `@type` field
+#[derive(Clone, Debug, PartialEq)]
+pub enum Track {
+    General(GeneralTrack),
+    Video(VideoTrack),
+    Audio(AudioTrack),
+    Text(TextTrack),
+    Menu(MenuTrack),
+    Other(String)
+}
+
+impl From<&RawTrack> for Track {
+    fn from(track: &RawTrack) -> Self {
+        match TrackType::from_raw(track) {
+            TrackType::General => Track::General(GeneralTrack::from(track)),
+            TrackType::Video => Track::Video(VideoTrack::from(track)),
+            TrackType::Audio => Track::Audio(AudioTrack::from(track)),
+            TrackType::Text => Track::Text(TextTrack::from(track)),
+            TrackType::Menu => Track::Menu(MenuTrack::from(track)),
+            TrackType::Other(kind) => Track::Other(kind)
+        }
+    }
+}
+
+impl From<&RawTrack> for GeneralTrack {
+    fn from(track: &RawTrack) -> Self {
+        GeneralTrack {
+            format: string_field(track, "Format"),
+            duration: duration_field(track, "Duration"),
+            overall_bit_rate: u32_field(track, "OverallBitRate"),
+            frame_rate: f64_field(track, "FrameRate"),
+            frame_count: u32_field(track, "FrameCount"),
+            file_size: string_field(track, "FileSize").and_then(|s| s.trim().parse::<u64>().ok()),
+            video_count: u32_field(track, "VideoCount").unwrap_or(0),
+            audio_count: u32_field(track, "AudioCount").unwrap_or(0),
+            text_count: u32_field(track, "TextCount").unwrap_or(0)
+        }
+    }
+}
+
+impl From<&RawTrack> for VideoTrack {
+    fn from(track: &RawTrack) -> Self {
+        VideoTrack {
+            format: string_field(track, "Format"),
+            codec_id: string_field(track, "CodecID"),
+            width: u32_field(track, "Width"),
+            height: u32_field(track, "Height"),
+            display_aspect_ratio: f64_field(track, "DisplayAspectRatio"),
+            pixel_aspect_ratio: f64_field(track, "PixelAspectRatio"),
+            rotation: f64_field(track, "Rotation"),
+            frame_rate: f64_field(track, "FrameRate"),
+            frame_count: u32_field(track, "FrameCount"),
+            bit_rate: u32_field(track, "BitRate"),
+            bit_depth: u32_field(track, "BitDepth"),
+            duration: duration_field(track, "Duration"),
+            scan_type: string_field(track, "ScanType"),
+            language: string_field(track, "Language")
+        }
+    }
+}
+
+impl From<&RawTrack> for AudioTrack {
+    fn from(track: &RawTrack) -> Self {
+        AudioTrack {
+            format: string_field(track, "Format"),
+            codec_id: string_field(track, "CodecID"),
+            channels: u32_field(track, "Channels"),
+            channel_layout: string_field(track, "ChannelLayout"),
+            sampling_rate: u32_field(track, "SamplingRate"),
+            bit_rate: u32_field(track, "BitRate"),
+            duration: duration_field(track, "Duration"),
+            language: string_field(track, "Language")
+        }
+    }
+}
+
+impl From<&RawTrack> for TextTrack {
+    fn from(track: &RawTrack) -> Self {
+        TextTrack {
+            format: string_field(track, "Format"),
+            codec_id: string_field(track, "CodecID"),
+            language: string_field(track, "Language"),
+            title: string_field(track, "Title")
+        }
+    }
+}
+
+impl From<&RawTrack> for MenuTrack {
+    fn from(track: &RawTrack) -> Self {
+        // Chapters live in the `extra` object, keyed by start time: `_hh_mm_ss_mmm`
+        let mut chapters: Vec<Chapter> = match track.get("extra") {
+            Some(Value::Object(extra)) => extra.iter()
+                .filter_map(|(key, value)| {
+                    let start = parse_chapter_key(key)?;
+                    let title = value.as_str().unwrap_or("").to_string();
+                    Some(Chapter { start, title })
+                })
+                .collect(),
+            _ => Vec::new()
+        };
+        chapters.sort_by_key(|chapter| chapter.start);
+
+        MenuTrack { chapters }
+    }
+}
+
+fn parse_chapter_key(key: &str) -> Option<Duration> {
+    let parts: Vec<u64> = key.trim_start_matches('_')
+        .split('_')
+        .map(|p| p.parse::<u64>().ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    match parts.as_slice() {
+        [h, m, s, ms] => Some(Duration::from_millis(((h * 60 + m) * 60 + s) * 1000 + ms)),
+        _ => None
+    }
+}
+
+fn string_field(track: &RawTrack, name: &str) -> Option<String> {
+    match track.get(name) {
+        Some(Value::String(s)) => Some(s.to_string()),
+        Some(Value::Number(n)) => Some(n.to_string()),
+        _ => None
+    }
+}
+
+fn f64_field(track: &RawTrack, name: &str) -> Option<f64> {
+    string_field(track, name).and_then(|s| s.trim().parse::<f64>().ok())
+}
+
+fn u32_field(track: &RawTrack, name: &str) -> Option<u32> {
+    let value = string_field(track, name)?;
+    // Some fields are reported as decimals (e.g. `FrameCount` on VFR sources)
+    value.trim().parse::<u32>().ok()
+        .or_else(|| value.trim().parse::<f64>().ok()
+            .filter(|v| *v >= 0.0 && *v <= u32::MAX as f64)
+            .map(|v| v.round() as u32))
+}
+
+fn duration_field(track: &RawTrack, name: &str) -> Option<Duration> {
+    // Durations are expressed in seconds, with millisecond precision
+    f64_field(track, name)
+        .filter(|secs| secs.is_finite() && *secs >= 0.0)
+        .map(Duration::from_secs_f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use super::*;
+
+    fn raw(value: Value) -> RawTrack {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_video_track_from_raw() {
+        let track = raw(json!({
+            "@type": "Video",
+            "Width": "1920",
+            "Height": "800",
+            "DisplayAspectRatio": "2.400",
+            "Rotation": "90.000",
+            "FrameRate": "23.976",
+            "Duration": "12.345"
+        }));
+
+        match Track::from(&track) {
+            Track::Video(video) => {
+                assert_eq!(video.width, Some(1920));
+                assert_eq!(video.height, Some(800));
+                assert_eq!(video.display_aspect_ratio, Some(2.4));
+                assert_eq!(video.rotation, Some(90.0));
+                assert_eq!(video.frame_rate, Some(23.976));
+                assert_eq!(video.duration, Some(Duration::from_millis(12345)));
+            }
+            other => panic!("unexpected track {:?}", other)
+        }
+    }
+
+    #[test]
+    fn test_unknown_track_type() {
+        let track = raw(json!({ "@type": "Image" }));
+
+        assert_eq!(Track::from(&track), Track::Other(String::from("Image")));
+    }
+
+    #[test]
+    fn test_menu_chapters() {
+        let track = raw(json!({
+            "@type": "Menu",
+            "extra": {
+                "_00_05_00_000": "Chapter 2",
+                "_00_00_00_000": "Chapter 1"
+            }
+        }));
+
+        match Track::from(&track) {
+            Track::Menu(menu) => {
+                assert_eq!(menu.chapters.len(), 2);
+                assert_eq!(menu.chapters[0].title, "Chapter 1");
+                assert_eq!(menu.chapters[1].start, Duration::from_secs(300));
+            }
+            other => panic!("unexpected track {:?}", other)
+        }
+    }
+}
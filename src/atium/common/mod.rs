@@ -2,5 +2,13 @@ pub mod command_manager;
 pub mod error;
 pub mod model;
 pub mod thumbnail_service;
+pub mod sprite;
+pub mod frame_score;
+pub mod preview;
+pub mod timestamp;
 pub mod analysis_helper;
 pub mod analysis_service;
+pub mod media;
+pub mod analyzer;
+pub mod ffprobe_service;
+pub mod progress;
@@ -1,19 +1,180 @@
+use std::path::Path;
+use std::time::Duration;
+use crate::atium::common::error::AtiumError;
+
+/// Timestamp of the thumbnail extracted when none is requested
+pub const DEFAULT_THUMBNAIL_TIMESTAMP: &str = "00:00:01";
+
+/// The frames of a thumbnail request, resolved against the source duration:
+/// * Timestamps  -> a frame at every timestamp, parsed as a [`Timestamp`](crate::common::timestamp::Timestamp)
+/// * Interval    -> a frame every interval, starting from the beginning
+/// * Percentages -> a frame at every percentage of the duration, within `[0, 100)`
+/// * Auto        -> the best scoring frame among candidates sampled over the whole video,
+///   avoiding black, flat and scene transition frames
+#[derive(Clone, Debug, PartialEq)]
+pub enum ThumbnailSelection {
+    Timestamps(Vec<String>),
+    Interval(Duration),
+    Percentages(Vec<f64>),
+    Auto
+}
+
+impl Default for ThumbnailSelection {
+    fn default() -> Self {
+        ThumbnailSelection::Timestamps(vec![DEFAULT_THUMBNAIL_TIMESTAMP.to_string()])
+    }
+}
+
+/// Returns the [`ThumbnailSelection::Timestamps`] of a comma separated list of timestamps,
+/// the default one when the list is empty. `auto` returns [`ThumbnailSelection::Auto`].
+pub fn parse_timestamps(timestamps_string: &str) -> ThumbnailSelection {
+    if timestamps_string.trim().eq_ignore_ascii_case("auto") {
+        return ThumbnailSelection::Auto
+    }
+
+    let timestamps: Vec<String> = timestamps_string.split(',')
+        .map(str::trim)
+        .filter(|timestamp| !timestamp.is_empty())
+        .map(String::from)
+        .collect();
+
+    if timestamps.is_empty() {
+        ThumbnailSelection::default()
+    } else {
+        ThumbnailSelection::Timestamps(timestamps)
+    }
+}
+
+/// Returns the [`ThumbnailSelection::Percentages`] of a comma separated list of percentages,
+/// e.g. `10,30,50` or `10%,30%,50%`.
+///
+/// Fails on the first entry that is not a number within `[0, 100)`
+pub fn parse_percentages(percentages_string: &str) -> Result<ThumbnailSelection, AtiumError> {
+    percentages_string.split(',')
+        .map(str::trim)
+        .filter(|percentage| !percentage.is_empty())
+        .map(|percentage| percentage.trim_end_matches('%').trim().parse::<f64>().ok()
+            .filter(|value| (0.0..100.0).contains(value))
+            .ok_or_else(|| AtiumError::InvalidRequest(format!("Thumbnail percentage [{}] must be a number within [0, 100)", percentage))))
+        .collect::<Result<Vec<f64>, AtiumError>>()
+        .map(ThumbnailSelection::Percentages)
+}
+
+/// Image formats of the thumbnails:
+/// * Jpeg -> JPEG through the native `mjpeg` encoder
+/// * Png  -> lossless PNG through the native `png` encoder
+/// * WebP -> WebP through `libwebp`
+/// * Avif -> AVIF through `libaom-av1`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    Jpeg, Png, WebP, Avif
+}
+
+impl ImageFormat {
+    /// The file extension of the image
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "jpeg",
+            ImageFormat::Png => "png",
+            ImageFormat::WebP => "webp",
+            ImageFormat::Avif => "avif"
+        }
+    }
+    /// The `ffmpeg` encoder of the image
+    pub fn encoder(&self) -> &'static str {
+        match self {
+            ImageFormat::Jpeg => "mjpeg",
+            ImageFormat::Png => "png",
+            ImageFormat::WebP => "libwebp",
+            ImageFormat::Avif => "libaom-av1"
+        }
+    }
+}
+
+/// Returns a value of [`ImageFormat`] based on input:
+/// Valid inputs are:
+/// * jpg, jpeg -> JPEG
+/// * png       -> PNG
+/// * webp      -> WEBP
+/// * avif      -> AVIF
+///
+/// Fails on any other input
+pub fn parse_image_format(format_string: &str) -> Result<ImageFormat, AtiumError> {
+    match format_string.to_lowercase().as_str() {
+        "jpg" | "jpeg" => Ok(ImageFormat::Jpeg),
+        "png" => Ok(ImageFormat::Png),
+        "webp" => Ok(ImageFormat::WebP),
+        "avif" => Ok(ImageFormat::Avif),
+        _ => Err(AtiumError::InvalidRequest(format!("Image format [{}] is not supported, expected jpeg/png/webp/avif", format_string)))
+    }
+}
+
+/// Returns the requested [`ImageFormat`] or, when missing, the one matching the extension of `output_file`.
+/// JPEG is used when there is no output file or it has no extension.
+///
+/// Fails when the requested format, or the extension, is not supported
+pub fn image_format(format_string: Option<&str>, output_file: Option<&str>) -> Result<ImageFormat, AtiumError> {
+    format_string
+        .or_else(|| output_file.and_then(|output| Path::new(output).extension()).and_then(|e| e.to_str()))
+        .map(parse_image_format)
+        .unwrap_or(Ok(ImageFormat::Jpeg))
+}
+
+/// Encoding options of the extracted images
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ImageOutput {
+    pub format: ImageFormat,
+    /// Maximum width in pixels, the source aspect ratio is always kept
+    pub width: Option<u32>,
+    /// Maximum height in pixels, the source aspect ratio is always kept
+    pub height: Option<u32>,
+    /// Quality within `[1, 100]`, the higher the better. Ignored by the lossless PNG.
+    /// If None, the encoder default is used
+    pub quality: Option<u8>
+}
+
+impl Default for ImageOutput {
+    fn default() -> Self {
+        ImageOutput { format: ImageFormat::Jpeg, width: None, height: None, quality: None }
+    }
+}
+
+/// Returns the [`ImageOutput`] of the given options, the format follows [`image_format`]
+///
+/// Fails when the format is not supported
+pub fn parse_image_output(
+    format_string: Option<&str>,
+    output_file: Option<&str>,
+    width: Option<u32>,
+    height: Option<u32>,
+    quality: Option<u8>
+) -> Result<ImageOutput, AtiumError> {
+    Ok(ImageOutput { format: image_format(format_string, output_file)?, width, height, quality })
+}
+
 /// A Thumbnail extraction request
 #[derive(Clone)]
 pub struct ThumbnailRequest {
-    /// A timestamp with format `hh:mm:ss`
-    pub timestamp: Option<String>,
+    /// The frames to extract
+    pub selection: ThumbnailSelection,
     /// The filepath from where the thumbnail will be extracted
     pub input_file: Option<String>,
-    /// A filepath where the thumbnail will be saved
+    /// A filepath where the thumbnail will be saved, defaults to the input path with the extension of the image format.
+    /// When several frames are extracted, they are numbered from 1 before the extension, e.g. `thumb-001.jpeg`
     pub output_file: Option<String>,
-    /// Input file duration. If None, it will be computed afterwards
-    pub input_duration: Option<String>
+    /// Format, size and quality of the thumbnails
+    pub image: ImageOutput,
+    /// Input file duration. If None, it will be computed afterwards along with the frame rate
+    pub input_duration: Option<Duration>,
+    /// Input file frame rate, resolving SMPTE timecodes
+    pub input_frame_rate: Option<f64>
 }
 
 impl ThumbnailRequest {
-    /// Create a new [`ThumbnailRequest`] which is None if timestamp and output are None
-    /// otherwise is Some
+    /// Create a new [`ThumbnailRequest`] which is None if input and output are None
+    /// otherwise is Some. The timestamp is a comma separated list, parsed as in [`parse_timestamps`],
+    /// and the image format follows the output extension as in [`image_format`], JPEG being used when
+    /// the extension is not an image format. Use [`parse_image_output`] to reject such outputs
     pub fn new(
         timestamp: &Option<String>,
         input_file: &Option<String>,
@@ -25,18 +186,163 @@ impl ThumbnailRequest {
 
         Some(
             ThumbnailRequest {
-                timestamp: timestamp.clone(),
+                selection: timestamp.as_deref().map(parse_timestamps).unwrap_or_default(),
                 input_file: input_file.clone(),
                 output_file: output_file.clone(),
+                image: ImageOutput { format: image_format(None, output_file.as_deref()).unwrap_or(ImageFormat::Jpeg), ..ImageOutput::default() },
                 // Will be computed later
-                input_duration: None
+                input_duration: None,
+                input_frame_rate: None
             }
         )
     }
 }
 
+/// The frame picked by [`ThumbnailSelection::Auto`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ScoredFrame {
+    pub timestamp: Duration,
+    /// Score of the frame within `[0, 1]`, the higher the better
+    pub score: f64
+}
+
 pub struct ThumbnailResponse {
-    pub output: String
+    /// The extracted thumbnails, in the order of the requested frames
+    pub outputs: Vec<String>,
+    /// The selected frame, when [`ThumbnailSelection::Auto`] was requested
+    pub scored_frame: Option<ScoredFrame>
+}
+
+/// Thumbnail extraction modes:
+/// * Single  -> one frame at the requested timestamp
+/// * Sprite  -> a grid of frames taken at a regular interval, along with a WebVTT thumbnail track
+/// * Preview -> a short animated clip starting at the requested timestamp
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ThumbnailMode {
+    Single, Sprite, Preview
+}
+
+/// Returns a value of [`ThumbnailMode`] based on input:
+/// Valid inputs are:
+/// * single  -> SINGLE
+/// * sprite  -> SPRITE
+/// * preview -> PREVIEW
+///
+/// Fails on any other input
+pub fn parse_thumbnail_mode(mode_string: &str) -> Result<ThumbnailMode, AtiumError> {
+    match mode_string.to_lowercase().as_str() {
+        "single" => Ok(ThumbnailMode::Single),
+        "sprite" => Ok(ThumbnailMode::Sprite),
+        "preview" => Ok(ThumbnailMode::Preview),
+        _ => Err(AtiumError::InvalidRequest(format!("Thumbnail mode [{}] is not supported, expected single/sprite/preview", mode_string)))
+    }
+}
+
+/// Animated preview formats:
+/// * Gif  -> an animated GIF, encoded with a palette generated from the clip
+/// * WebP -> an animated WebP
+/// * Mp4  -> a silent H.264 MP4
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PreviewFormat {
+    Gif, WebP, Mp4
+}
+
+impl PreviewFormat {
+    /// The file extension of the preview
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PreviewFormat::Gif => "gif",
+            PreviewFormat::WebP => "webp",
+            PreviewFormat::Mp4 => "mp4"
+        }
+    }
+    /// The `ffmpeg` encoder of the preview
+    pub fn encoder(&self) -> &'static str {
+        match self {
+            PreviewFormat::Gif => "gif",
+            PreviewFormat::WebP => "libwebp",
+            PreviewFormat::Mp4 => "libx264"
+        }
+    }
+}
+
+/// Returns a value of [`PreviewFormat`] based on input:
+/// Valid inputs are:
+/// * gif  -> GIF
+/// * webp -> WEBP
+/// * mp4  -> MP4
+///
+/// Fails on any other input
+pub fn parse_preview_format(format_string: &str) -> Result<PreviewFormat, AtiumError> {
+    match format_string.to_lowercase().as_str() {
+        "gif" => Ok(PreviewFormat::Gif),
+        "webp" => Ok(PreviewFormat::WebP),
+        "mp4" => Ok(PreviewFormat::Mp4),
+        _ => Err(AtiumError::InvalidRequest(format!("Preview format [{}] is not supported, expected gif/webp/mp4", format_string)))
+    }
+}
+
+/// An animated preview request, a clip is produced for every frame of the selection
+#[derive(Clone)]
+pub struct PreviewRequest {
+    /// Start of the clips, with the same semantics as [`ThumbnailRequest::selection`]
+    pub selection: ThumbnailSelection,
+    /// The filepath from where the preview will be extracted
+    pub input_file: String,
+    /// A filepath where the preview will be saved, defaults to the input path with a `-preview` suffix.
+    /// Several clips are numbered as in [`ThumbnailRequest::output_file`]
+    pub output_file: Option<String>,
+    pub format: PreviewFormat,
+    /// Length of a clip, clamped to the input duration
+    pub duration: Duration,
+    /// Frames per second of the preview
+    pub fps: u32,
+    /// Width in pixels of the preview, the height follows the source aspect ratio. Must be even for MP4
+    pub width: u32
+}
+
+/// The extracted previews
+pub struct PreviewResponse {
+    /// The extracted clips, in the order of the requested frames
+    pub outputs: Vec<String>,
+    /// Start of every clip
+    pub starts: Vec<Duration>,
+    /// Length of the clips
+    pub duration: Duration,
+    /// The start frame, when [`ThumbnailSelection::Auto`] was requested
+    pub scored_frame: Option<ScoredFrame>
+}
+
+/// A sprite sheet request: frames are taken every `interval` and tiled in a grid,
+/// a WebVTT track maps every time range to its tile
+#[derive(Clone)]
+pub struct SpriteRequest {
+    /// The filepath from where the frames will be extracted
+    pub input_file: String,
+    /// A filepath where the sprite image will be saved, defaults to the input path with a `-sprite.jpg` suffix
+    pub output_file: Option<String>,
+    /// A filepath where the WebVTT track will be saved, defaults to the sprite path with the `vtt` extension
+    pub vtt_file: Option<String>,
+    /// Time between two frames
+    pub interval: Duration,
+    /// Width of a tile in pixels
+    pub tile_width: u32,
+    /// Height of a tile in pixels, if None it follows the source display aspect ratio
+    pub tile_height: Option<u32>,
+    /// Number of tiles per row
+    pub columns: u32
+}
+
+/// The extracted sprite sheet and its WebVTT track
+pub struct SpriteResponse {
+    pub sprite: String,
+    pub vtt: String,
+    /// Number of tiles holding a frame
+    pub tiles: u32,
+    pub columns: u32,
+    pub rows: u32,
+    /// Size of a tile in pixels
+    pub tile_size: (u32, u32)
 }
 
 
@@ -107,4 +413,47 @@ pub struct InfoExtractorResponseOutput {
 pub struct InfoExtractorResponse {
     /// The [`InfoExtractorResponseOutput`] containing pointers to the actual output
     pub output: InfoExtractorResponseOutput
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_percentages() {
+        assert_eq!(parse_percentages("10, 50%,90,").unwrap(), ThumbnailSelection::Percentages(vec![10.0, 50.0, 90.0]));
+        assert_eq!(parse_percentages("0").unwrap(), ThumbnailSelection::Percentages(vec![0.0]));
+        assert!(parse_percentages("10,fifty").is_err());
+        assert!(parse_percentages("100").is_err());
+        assert!(parse_percentages("-5").is_err());
+        assert!(parse_percentages("NaN").is_err());
+    }
+
+    #[test]
+    fn test_parse_thumbnail_mode() {
+        assert_eq!(parse_thumbnail_mode("Single").unwrap(), ThumbnailMode::Single);
+        assert_eq!(parse_thumbnail_mode("sprite").unwrap(), ThumbnailMode::Sprite);
+        assert_eq!(parse_thumbnail_mode("PREVIEW").unwrap(), ThumbnailMode::Preview);
+        assert!(parse_thumbnail_mode("grid").is_err());
+    }
+
+    #[test]
+    fn test_parse_preview_format() {
+        assert_eq!(parse_preview_format("GIF").unwrap(), PreviewFormat::Gif);
+        assert_eq!(parse_preview_format("webp").unwrap(), PreviewFormat::WebP);
+        assert_eq!(parse_preview_format("mp4").unwrap(), PreviewFormat::Mp4);
+        assert!(parse_preview_format("apng").is_err());
+    }
+
+    #[test]
+    fn test_image_format() {
+        assert_eq!(parse_image_format("JPG").unwrap(), ImageFormat::Jpeg);
+        assert_eq!(parse_image_format("avif").unwrap(), ImageFormat::Avif);
+        assert!(parse_image_format("bmp").is_err());
+        assert_eq!(image_format(Some("png"), Some("thumb.jpg")).unwrap(), ImageFormat::Png);
+        assert_eq!(image_format(None, Some("thumb.webp")).unwrap(), ImageFormat::WebP);
+        assert_eq!(image_format(None, Some("thumb")).unwrap(), ImageFormat::Jpeg);
+        assert_eq!(image_format(None, None).unwrap(), ImageFormat::Jpeg);
+        assert!(image_format(None, Some("thumb.bmp")).is_err());
+    }
+}
@@ -0,0 +1,98 @@
+use std::time::Duration;
+use crate::atium::common::error::AtiumError;
+use crate::atium::common::model::PreviewFormat;
+use crate::atium::common::timestamp::seek_position;
+
+/// Fits a `clip` long window starting at `start` within a `source` long video:
+/// the clip is shortened to the source duration, then moved back so that it ends with the source at the latest
+pub fn clip_window(start: Duration, clip: Duration, source: Duration) -> (Duration, Duration) {
+    let clip = clip.min(source);
+
+    (start.min(source - clip), clip)
+}
+
+/// Checks the preview `width` against the `format`: MP4 previews are encoded in yuv420p,
+/// whose chroma subsampling requires an even width
+pub fn check_preview_width(format: PreviewFormat, width: u32) -> Result<(), AtiumError> {
+    if format == PreviewFormat::Mp4 && width / 2 * 2 != width {
+        return Err(AtiumError::InvalidRequest(format!("MP4 preview width must be even, got {}", width)))
+    }
+
+    Ok(())
+}
+
+/// The filter chain scaling the preview to `width` at `fps`, the height keeps the source aspect ratio.
+/// GIFs are encoded in two steps: a palette is generated from the whole clip, then used to map its frames.
+fn preview_filter(format: PreviewFormat, fps: u32, width: u32) -> String {
+    let scale = format!("fps={},scale={}:-2:flags=lanczos", fps, width);
+
+    match format {
+        PreviewFormat::Gif => format!("{},split[frames][palette_source];[palette_source]palettegen=stats_mode=diff[palette];[frames][palette]paletteuse=dither=bayer:bayer_scale=5", scale),
+        PreviewFormat::WebP => scale,
+        PreviewFormat::Mp4 => format!("{},format=yuv420p", scale)
+    }
+}
+
+/// The `ffmpeg` arguments encoding the `clip` long preview starting at `start`, without audio
+pub fn preview_args(input_file: &str, output_file: &str, start: Duration, clip: Duration, format: PreviewFormat, fps: u32, width: u32) -> Vec<String> {
+    let mut args = vec![
+        String::from("-ss"),
+        seek_position(start),
+        String::from("-t"),
+        format!("{:.3}", clip.as_secs_f64()),
+        String::from("-i"),
+        input_file.to_string(),
+        String::from("-an"),
+        String::from("-sn"),
+        String::from("-filter_complex"),
+        preview_filter(format, fps, width),
+        String::from("-c:v"),
+        format.encoder().to_string()
+    ];
+
+    let format_args: &[&str] = match format {
+        PreviewFormat::Gif => &["-loop", "0"],
+        PreviewFormat::WebP => &["-loop", "0", "-q:v", "70"],
+        PreviewFormat::Mp4 => &["-crf", "28", "-preset", "veryfast", "-movflags", "+faststart"]
+    };
+    args.extend(format_args.iter().map(|arg| arg.to_string()));
+    args.push(output_file.to_string());
+
+    args
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clip_window() {
+        let source = Duration::from_secs(60);
+
+        assert_eq!(clip_window(Duration::from_secs(10), Duration::from_secs(3), source), (Duration::from_secs(10), Duration::from_secs(3)));
+        assert_eq!(clip_window(Duration::from_secs(59), Duration::from_secs(3), source), (Duration::from_secs(57), Duration::from_secs(3)));
+        assert_eq!(clip_window(Duration::from_secs(5), Duration::from_secs(90), source), (Duration::ZERO, source));
+    }
+
+    #[test]
+    fn test_check_preview_width() {
+        assert!(check_preview_width(PreviewFormat::Mp4, 320).is_ok());
+        assert!(check_preview_width(PreviewFormat::Mp4, 321).is_err());
+        assert!(check_preview_width(PreviewFormat::Gif, 321).is_ok());
+        assert!(check_preview_width(PreviewFormat::WebP, 321).is_ok());
+    }
+
+    #[test]
+    fn test_preview_args() {
+        let gif = preview_args("in.mp4", "out.gif", Duration::from_millis(12_500), Duration::from_secs(3), PreviewFormat::Gif, 10, 320);
+
+        assert_eq!(gif[..6], ["-ss", "12.500000", "-t", "3.000", "-i", "in.mp4"]);
+        assert_eq!(gif[9], "fps=10,scale=320:-2:flags=lanczos,split[frames][palette_source];[palette_source]palettegen=stats_mode=diff[palette];[frames][palette]paletteuse=dither=bayer:bayer_scale=5");
+        assert_eq!(gif[gif.len() - 3..], ["-loop", "0", "out.gif"]);
+
+        let mp4 = preview_args("in.mp4", "out.mp4", Duration::ZERO, Duration::from_secs(3), PreviewFormat::Mp4, 15, 480);
+        assert_eq!(mp4[9], "fps=15,scale=480:-2:flags=lanczos,format=yuv420p");
+        assert_eq!(mp4[11], "libx264");
+        assert!(mp4.contains(&"+faststart".to_string()));
+    }
+}
@@ -0,0 +1,145 @@
+use std::time::Duration;
+
+/// The step of a job a [`Progress`] refers to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ProgressStage {
+    /// Loudness measurement pass
+    LoudnessAnalysis,
+    /// First pass of a two-pass encoding
+    FirstPass,
+    /// The actual encoding
+    Encoding
+}
+
+/// A progress update of a running `ffmpeg` command
+#[derive(Clone, Debug, PartialEq)]
+pub struct Progress {
+    pub stage: ProgressStage,
+    /// Position of the output, in media time
+    pub out_time: Duration,
+    /// Completion percentage of the stage, None when the source duration is unknown
+    pub percentage: Option<f64>,
+    /// Encoding speed, as a multiple of real time
+    pub speed: Option<f64>,
+    /// Encoded frames per second
+    pub fps: Option<f64>,
+    /// Whether the stage is completed
+    pub finished: bool
+}
+
+/// Parses the `key=value` blocks printed by `ffmpeg -progress`
+pub struct ProgressParser {
+    stage: ProgressStage,
+    duration: Option<Duration>,
+    out_time: Duration,
+    speed: Option<f64>,
+    fps: Option<f64>
+}
+
+impl ProgressParser {
+    /// Creates a new [`ProgressParser`], `duration` is the duration of the source
+    pub fn new(stage: ProgressStage, duration: Option<Duration>) -> Self {
+        ProgressParser { stage, duration, out_time: Duration::ZERO, speed: None, fps: None }
+    }
+    /// Feeds a line of output, returns Some when a progress block is completed
+    pub fn parse_line(&mut self, line: &str) -> Option<Progress> {
+        let (key, value) = line.trim().split_once('=')?;
+        let value = value.trim();
+
+        match key {
+            // Despite the name, `out_time_ms` is expressed in microseconds
+            "out_time_us" | "out_time_ms" => {
+                if let Ok(micros) = value.parse::<u64>() {
+                    self.out_time = Duration::from_micros(micros);
+                }
+                None
+            }
+            "speed" => {
+                self.speed = value.trim_end_matches('x').trim().parse::<f64>().ok();
+                None
+            }
+            "fps" => {
+                self.fps = value.parse::<f64>().ok();
+                None
+            }
+            "progress" => {
+                let finished = value == "end";
+                let percentage = self.duration
+                    .filter(|duration| !duration.is_zero())
+                    .map(|duration| {
+                        if finished {
+                            100.0
+                        } else {
+                            (self.out_time.as_secs_f64() / duration.as_secs_f64() * 100.0).min(100.0)
+                        }
+                    });
+
+                Some(Progress {
+                    stage: self.stage,
+                    out_time: self.out_time,
+                    percentage,
+                    speed: self.speed,
+                    fps: self.fps,
+                    finished
+                })
+            }
+            _ => None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: &str = "frame=250
+fps=49.87
+stream_0_0_q=28.0
+bitrate=1805.2kbits/s
+total_size=2257968
+out_time_us=10005333
+out_time_ms=10005333
+out_time=00:00:10.005333
+dup_frames=0
+drop_frames=0
+speed=1.99x
+progress=continue";
+
+    #[test]
+    fn test_parse_progress_block() {
+        let mut parser = ProgressParser::new(ProgressStage::Encoding, Some(Duration::from_secs(20)));
+
+        let updates: Vec<Progress> = BLOCK.lines().filter_map(|line| parser.parse_line(line)).collect();
+
+        assert_eq!(updates.len(), 1);
+        let progress = &updates[0];
+        assert_eq!(progress.out_time, Duration::from_micros(10005333));
+        assert_eq!(progress.fps, Some(49.87));
+        assert_eq!(progress.speed, Some(1.99));
+        assert!((progress.percentage.unwrap() - 50.026665).abs() < 0.0001);
+        assert!(!progress.finished);
+    }
+
+    #[test]
+    fn test_progress_end() {
+        let mut parser = ProgressParser::new(ProgressStage::Encoding, Some(Duration::from_secs(20)));
+
+        let progress = parser.parse_line("progress=end").unwrap();
+
+        assert!(progress.finished);
+        assert_eq!(progress.percentage, Some(100.0));
+    }
+
+    #[test]
+    fn test_unknown_duration() {
+        let mut parser = ProgressParser::new(ProgressStage::FirstPass, None);
+
+        parser.parse_line("out_time_us=1000000");
+        parser.parse_line("speed=N/A");
+        let progress = parser.parse_line("progress=continue").unwrap();
+
+        assert_eq!(progress.percentage, None);
+        assert_eq!(progress.speed, None);
+        assert_eq!(progress.out_time, Duration::from_secs(1));
+    }
+}
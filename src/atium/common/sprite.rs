@@ -0,0 +1,143 @@
+use std::time::Duration;
+use crate::atium::common::error::AtiumError;
+
+/// Largest width or height of a JPEG image
+const MAX_JPEG_SIDE: u32 = 65535;
+
+/// The grid of a sprite sheet, tiles are filled row by row starting from the top left corner
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SpriteLayout {
+    /// Number of tiles holding a frame
+    pub count: u32,
+    pub columns: u32,
+    pub rows: u32,
+    /// Size of a tile in pixels
+    pub tile: (u32, u32)
+}
+
+impl SpriteLayout {
+    /// Computes the grid holding a frame every `interval` of a `duration` long video,
+    /// on at most `columns` tiles per row.
+    ///
+    /// Fails when the interval is below a millisecond, when the columns or the tile size are 0 or when the sprite would exceed the JPEG size limit
+    pub fn new(duration: Duration, interval: Duration, columns: u32, tile: (u32, u32)) -> Result<Self, AtiumError> {
+        if interval.as_millis() == 0 {
+            return Err(AtiumError::InvalidRequest("Sprite interval must be at least 1ms".to_string()))
+        }
+        if columns == 0 || tile.0 == 0 || tile.1 == 0 {
+            return Err(AtiumError::InvalidRequest("Sprite columns and tile size must be greater than 0".to_string()))
+        }
+
+        let frames = (duration.as_secs_f64() / interval.as_secs_f64()).ceil();
+        let count = if frames.is_finite() && frames >= 1.0 { frames.min(u32::MAX as f64) as u32 } else { 1 };
+        let columns = columns.min(count);
+        let rows = count / columns + u32::from(count % columns > 0);
+        let layout = SpriteLayout { count, columns, rows, tile };
+
+        let (width, height) = layout.size();
+        if width > MAX_JPEG_SIDE as u64 || height > MAX_JPEG_SIDE as u64 {
+            return Err(AtiumError::InvalidRequest(format!(
+                "A sprite of {} tiles of {}x{} would be {}x{}, above the JPEG limit of {} pixels: increase the interval or reduce the tile size",
+                count, tile.0, tile.1, width, height, MAX_JPEG_SIDE
+            )))
+        }
+
+        Ok(layout)
+    }
+    /// Size of the whole sprite in pixels
+    pub fn size(&self) -> (u64, u64) {
+        (self.columns as u64 * self.tile.0 as u64, self.rows as u64 * self.tile.1 as u64)
+    }
+    /// Top left corner of the `index`-th tile in pixels
+    pub fn tile_position(&self, index: u32) -> (u32, u32) {
+        ((index % self.columns) * self.tile.0, (index / self.columns) * self.tile.1)
+    }
+}
+
+/// Height of a `tile_width` wide tile following the aspect ratio of `display_size`, rounded to an even number
+pub fn tile_height(tile_width: u32, display_size: (u32, u32)) -> u32 {
+    if display_size.0 == 0 {
+        return tile_width
+    }
+    let height = tile_width as f64 * display_size.1 as f64 / display_size.0 as f64;
+
+    ((height / 2.0).round() as u32 * 2).max(2)
+}
+
+/// Formats a duration as a WebVTT timestamp, e.g. `01:02:03.456`
+fn vtt_timestamp(duration: Duration) -> String {
+    let millis = duration.as_millis();
+    format!(
+        "{:02}:{:02}:{:02}.{:03}",
+        millis / 3_600_000, millis / 60_000 % 60, millis / 1000 % 60, millis % 1000
+    )
+}
+
+/// Renders the WebVTT thumbnail track of a sprite: every cue covers an `interval`
+/// and points to its tile through a `#xywh=` media fragment of `sprite_url`.
+/// The last cue ends with the video.
+pub fn webvtt(layout: &SpriteLayout, interval: Duration, duration: Duration, sprite_url: &str) -> String {
+    let mut track = String::from("WEBVTT\n");
+
+    for index in 0..layout.count {
+        let start = interval * index;
+        let end = if index + 1 == layout.count { duration.max(start + Duration::from_millis(1)) } else { interval * (index + 1) };
+        let (x, y) = layout.tile_position(index);
+
+        track.push_str(&format!(
+            "\n{} --> {}\n{}#xywh={},{},{},{}\n",
+            vtt_timestamp(start), vtt_timestamp(end), sprite_url, x, y, layout.tile.0, layout.tile.1
+        ));
+    }
+
+    track
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sprite_layout() {
+        let layout = SpriteLayout::new(Duration::from_secs_f64(95.5), Duration::from_secs(10), 4, (160, 90)).unwrap();
+
+        assert_eq!(layout, SpriteLayout { count: 10, columns: 4, rows: 3, tile: (160, 90) });
+        assert_eq!(layout.size(), (640, 270));
+        assert_eq!(layout.tile_position(5), (160, 90));
+
+        let short = SpriteLayout::new(Duration::from_secs(3), Duration::from_secs(10), 10, (160, 90)).unwrap();
+        assert_eq!((short.count, short.columns, short.rows), (1, 1, 1));
+    }
+
+    #[test]
+    fn test_sprite_layout_failures() {
+        assert!(SpriteLayout::new(Duration::from_secs(60), Duration::ZERO, 10, (160, 90)).is_err());
+        assert!(SpriteLayout::new(Duration::from_secs(60), Duration::from_secs(1), 0, (160, 90)).is_err());
+        assert!(SpriteLayout::new(Duration::from_secs(36_000), Duration::from_secs(1), 10, (160, 90)).is_err());
+    }
+
+    #[test]
+    fn test_tile_height() {
+        assert_eq!(tile_height(160, (1920, 1080)), 90);
+        assert_eq!(tile_height(160, (1080, 1920)), 284);
+        assert_eq!(tile_height(160, (720, 576)), 128);
+    }
+
+    #[test]
+    fn test_webvtt() {
+        let layout = SpriteLayout::new(Duration::from_millis(25_500), Duration::from_secs(10), 2, (160, 90)).unwrap();
+
+        assert_eq!(webvtt(&layout, Duration::from_secs(10), Duration::from_millis(25_500), "sprite.jpg"), "WEBVTT
+
+00:00:00.000 --> 00:00:10.000
+sprite.jpg#xywh=0,0,160,90
+
+00:00:10.000 --> 00:00:20.000
+sprite.jpg#xywh=160,0,160,90
+
+00:00:20.000 --> 00:00:25.500
+sprite.jpg#xywh=0,90,160,90
+");
+        assert_eq!(vtt_timestamp(Duration::from_millis(3_723_456)), "01:02:03.456");
+    }
+}
@@ -1,126 +1,431 @@
-use chrono::{NaiveTime};
-use log::{debug, error, info, warn};
-use crate::atium::common::analysis_helper::{compute_output_file, MediaInfoJsonLoader};
+use std::fs;
+use std::path::Path;
+use std::process::Output;
+use std::time::Duration;
+use log::{debug, info};
+use crate::atium::common::analysis_helper::compute_output_file;
+use crate::atium::common::analyzer::{load_analyzer, MediaAnalyzer};
 use crate::atium::common::command_manager::CommandManager;
 use crate::atium::common::error::AtiumError;
-use crate::atium::common::model::{InfoFormat, InfoOutputType, ThumbnailRequest, ThumbnailResponse};
-use crate::{InfoExtractorRequest, MediaInfoExtractorService};
+use crate::atium::common::frame_score::{analysis_filter, best_frame, parse_frame_stats};
+use crate::atium::common::model::{ImageFormat, ImageOutput, PreviewRequest, PreviewResponse, ScoredFrame, SpriteRequest, SpriteResponse, ThumbnailRequest, ThumbnailResponse, ThumbnailSelection};
+use crate::atium::common::preview::{check_preview_width, clip_window, preview_args};
+use crate::atium::common::sprite::{SpriteLayout, tile_height, webvtt};
+use crate::atium::common::timestamp::{seek_position, Timestamp};
+use crate::converter::service::{compute_display_size, is_encoder_listed};
 
+/// Most thumbnails extracted by a single request, every one of them opens the input
+const MAX_THUMBNAILS: usize = 100;
 
 pub struct FFMPEGThumbnailService {
-    command_manager: CommandManager
+    command_manager: CommandManager,
+    analyzer: Box<dyn MediaAnalyzer>
 }
 
 impl FFMPEGThumbnailService{
-    fn build_args(&self, input_file: String, output_file: String, timestamp: String) -> Vec<String> {
+    /// Runs `ffmpeg` with `args`, without reading stdin
+    fn execute(&self, args: Vec<String>, message: &str) -> Result<Output, AtiumError> {
+        let mut ffmpeg_args = vec![String::from("-nostdin")];
+        ffmpeg_args.extend(args);
+
+        self.command_manager.execute_with_args(ffmpeg_args.iter().map(AsRef::as_ref).collect())
+            .map_err(|err| err.into_thumbnail(message))
+    }
+    /// Seeks every frame on its own input, so that all the thumbnails come from a single `ffmpeg` run
+    fn build_args(&self, input_file: &str, thumbnails: &[(Duration, String)], image: &ImageOutput) -> Vec<String> {
+        let mut args = Vec::new();
+        for (timestamp, _) in thumbnails {
+            args.extend([
+                String::from("-ss"),
+                seek_position(*timestamp),
+                String::from("-i"),
+                input_file.to_string()
+            ]);
+        }
+        for (index, (_, output_file)) in thumbnails.iter().enumerate() {
+            args.extend([
+                String::from("-map"),
+                format!("{}:v:0", index),
+                String::from("-frames:v"),
+                String::from("1")
+            ]);
+            args.extend(image_args(image));
+            args.push(output_file.clone());
+        }
+
+        args
+    }
+    fn build_output_from_input_path(&self, input_file: String, format: ImageFormat) -> String {
+        format!("{}.{}", input_file, format.extension())
+    }
+    fn check_encoder(&self, encoder: &str) -> Result<(), AtiumError> {
+        let result = self.command_manager.execute_with_args(vec!["-hide_banner", "-encoders"])?;
+        let listing = self.command_manager.get_command_output_as_string(result.stdout)?;
+        if !is_encoder_listed(&listing, encoder) {
+            return Err(AtiumError::Dependency(format!("Encoder [{}] is not available in the local ffmpeg build", encoder)))
+        }
+
+        Ok(())
+    }
+    /// Scores candidate frames sampled over the whole input and returns the best one
+    fn select_frame(&self, input_file: &str, duration: Duration) -> Result<ScoredFrame, AtiumError> {
+        debug!("Scoring candidate frames of [{}]", input_file);
+        let filter = analysis_filter(duration);
+        let args = ["-hide_banner", "-i", input_file, "-an", "-sn", "-dn", "-vf", &filter, "-f", "null", "-"];
+        let result = self.execute(args.iter().map(|arg| arg.to_string()).collect(), "ffmpeg could not analyze the candidate frames")?;
+        let frames = parse_frame_stats(&String::from_utf8_lossy(&result.stderr));
+
+        let (frame, score) = best_frame(&frames)
+            .ok_or_else(|| AtiumError::Thumbnail { message: "No candidate frame could be scored".to_string(), command: None })?;
+        debug!("Selected frame at [{:.3}s] with score [{:.3}] among {} candidates", frame.timestamp.as_secs_f64(), score, frames.len());
+
+        Ok(ScoredFrame { timestamp: frame.timestamp, score })
+    }
+    /// Extracts the requested thumbnails and returns a [`ThumbnailResponse`]
+    pub fn extract_thumbnail(&self, thumbnail_request: ThumbnailRequest) -> Result<ThumbnailResponse, AtiumError> {
+        let input_file = thumbnail_request.clone().input_file.expect("INPUT_FILE Cannot be empty");
+        let image = thumbnail_request.image;
+        check_image_output(&image)?;
+        self.check_encoder(image.format.encoder())?;
+
+        let output_file = thumbnail_request.clone().output_file
+            .unwrap_or_else(|| self.build_output_from_input_path(input_file.clone(), image.format));
+
+        debug!("Computing timestamps for thumbnail extraction");
+        let (source_duration, frame_rate) = match thumbnail_request.input_duration {
+            Some(duration) => (duration, thumbnail_request.input_frame_rate),
+            None => {
+                let analysis = self.analyzer.analyze(&input_file)?;
+                (analysis.duration()?, analysis.video_track().ok().and_then(|video| video.frame_rate))
+            }
+        };
+        let (timestamps, scored_frame) = match thumbnail_request.selection {
+            ThumbnailSelection::Auto => {
+                let scored_frame = self.select_frame(&input_file, source_duration)?;
+                (vec![scored_frame.timestamp], Some(scored_frame))
+            }
+            selection => (resolve_timestamps(&selection, source_duration, frame_rate)?, None)
+        };
+
+        let count = timestamps.len();
+        let thumbnails = timestamps.into_iter()
+            .enumerate()
+            .map(|(index, timestamp)| Ok((timestamp, compute_output_file(&numbered_output(&output_file, index, count), image.format.extension())?)))
+            .collect::<Result<Vec<(Duration, String)>, AtiumError>>()?;
+
+        let args = self.build_args(&input_file, &thumbnails, &image);
+
+        self.execute(args, "ffmpeg could not extract the thumbnail")?;
+
+        let outputs: Vec<String> = thumbnails.into_iter().map(|(_, output)| output).collect();
+        info!("{} thumbnail(s) extracted at paths {:?}", outputs.len(), outputs);
+        Ok(ThumbnailResponse{ outputs, scored_frame })
+    }
+    fn build_sprite_args(&self, input_file: String, output_file: String, interval: Duration, layout: &SpriteLayout) -> Vec<String> {
+        let (width, height) = layout.tile;
         vec![
             String::from("-i"),
             input_file,
-            String::from("-ss"),
-            timestamp,
-            String::from("-vframes"),
+            String::from("-vf"),
+            format!(
+                "fps=1000/{},scale={}:{}:force_original_aspect_ratio=decrease,pad={}:{}:(ow-iw)/2:(oh-ih)/2,setsar=1,tile={}x{}",
+                interval.as_millis(), width, height, width, height, layout.columns, layout.rows
+            ),
+            String::from("-frames:v"),
             String::from("1"),
+            String::from("-q:v"),
+            String::from("3"),
             output_file,
         ]
     }
-    fn build_output_from_input_path(&self, input_file: String) -> String {
-        let mut out = input_file;
-        out.push_str(".jpeg");
+    /// Extracts a sprite sheet of frames taken every [`SpriteRequest::interval`] along with
+    /// its WebVTT thumbnail track, and returns a [`SpriteResponse`]
+    pub fn extract_sprite(&self, sprite_request: SpriteRequest) -> Result<SpriteResponse, AtiumError> {
+        let input_file = sprite_request.input_file;
+        if sprite_request.tile_width == 0 {
+            return Err(AtiumError::InvalidRequest("Sprite tile width must be greater than 0".to_string()))
+        }
 
-        out
-    }
-    fn get_source_duration(&self, input_file: String) -> Result<String, AtiumError> {
-        let service = MediaInfoExtractorService::new()?;
-        let request = InfoExtractorRequest {
-            input: input_file,
-            format: Some(InfoFormat::Json),
-            full: None,
-            output_file: None,
-            output_type: Some(InfoOutputType::Plain)
+        let analysis = self.analyzer.analyze(&input_file)?;
+        let duration = analysis.duration()?;
+        let tile_height = match sprite_request.tile_height {
+            Some(height) => height,
+            None => tile_height(sprite_request.tile_width, compute_display_size(&analysis.video_track()?)?)
         };
+        let layout = SpriteLayout::new(duration, sprite_request.interval, sprite_request.columns, (sprite_request.tile_width, tile_height))?;
 
-        let response = service.get_info(request)?
-            .output
-            .content
-            .unwrap_or_else(|| String::from(""));
-
-        let media_info_loader = MediaInfoJsonLoader{};
-        match media_info_loader.load_json_from_string(&response) {
-            Ok(output) => {
-                debug!("Analysis done!");
+        let output_file = sprite_request.output_file
+            .unwrap_or_else(|| format!("{}-sprite.jpg", input_file));
+        let output_file = compute_output_file(&output_file, "jpg")?;
+        let vtt_file = sprite_request.vtt_file
+            .unwrap_or_else(|| Path::new(&output_file).with_extension("vtt").to_string_lossy().to_string());
+        let vtt_file = compute_output_file(&vtt_file, "vtt")?;
 
-                output.extract_field_from_track(0, &"Duration_String3".to_string())
-                    .map(|duration_field| {
-                        let split = duration_field.split('.').collect::<Vec<_>>();
-                        let duration_in_secs = split.get(0)
-                            .map(|s| s.to_string())
-                            .unwrap_or("00:00:01".to_string());
+        let args = self.build_sprite_args(input_file, output_file.clone(), sprite_request.interval, &layout);
+        self.execute(args, "ffmpeg could not extract the sprite")?;
 
-                        debug!("Duration in hh:mm:ss format: [{}]", duration_in_secs);
+        // Players resolve the sprite relatively to the track, a full path is only needed across directories
+        let sprite_path = Path::new(&output_file);
+        let sprite_url = match (sprite_path.parent(), Path::new(&vtt_file).parent(), sprite_path.file_name()) {
+            (Some(sprite_dir), Some(vtt_dir), Some(name)) if sprite_dir == vtt_dir => name.to_string_lossy().to_string(),
+            _ => output_file.clone()
+        };
+        fs::write(&vtt_file, webvtt(&layout, sprite_request.interval, duration, &sprite_url))
+            .map_err(|err| AtiumError::Output { message: format!("Could not write the WebVTT track [{}]", vtt_file), source: Some(err) })?;
 
-                        duration_in_secs
-                    })
-            }
-            Err(err) => {
-                error!("Could not analyze input file: {}", err);
-                Err(err)
-            }
-        }
+        info!("Sprite of {} tiles extracted at path [{}] with track [{}]", layout.count, output_file, vtt_file);
+        Ok(SpriteResponse {
+            sprite: output_file,
+            vtt: vtt_file,
+            tiles: layout.count,
+            columns: layout.columns,
+            rows: layout.rows,
+            tile_size: layout.tile
+        })
     }
-    fn compute_timestamp(&self, input_file: String, thumbnail_request: ThumbnailRequest) -> Result<String, AtiumError> {
-        debug!("Computing timestamp for thumbnail extraction");
-        let binding_req_ts = thumbnail_request.timestamp.unwrap_or_else(|| String::from("00:00:01"));
-        let source_duration = self.get_source_duration(input_file)?;
-        let req_time = NaiveTime::parse_from_str(binding_req_ts.as_str(), "%H:%M:%S")
-            .map_err(|err| {
-                warn!("Error parsing requested timestamp: {}", err);
-                AtiumError::IOError("An error occurred when parsing requested timestamp".to_string())
-            })?;
-        let source_duration_time = NaiveTime::parse_from_str(source_duration.as_str(), "%H:%M:%S")
-            .map_err(|err| {
-                warn!("Error parsing duration timestamp: {}", err);
-                AtiumError::IOError("An error occurred when parsing duration timestamp".to_string())
-            })?;
-
-        if req_time.lt(&source_duration_time) {
-            Ok(binding_req_ts)
-        } else {
-            debug!("Requested timestamp is greater than the input duration");
-            debug!("Thumbnail extraction timestamp will be set to 00:00:00.000");
-
-            Ok("00:00:00.000".to_string())
+    /// Extracts an animated clip of [`PreviewRequest::duration`] for every frame of the selection,
+    /// and returns a [`PreviewResponse`]. Clips are moved back to end with the input at the latest.
+    pub fn extract_preview(&self, preview_request: PreviewRequest) -> Result<PreviewResponse, AtiumError> {
+        let input_file = preview_request.input_file;
+        if preview_request.duration.as_millis() == 0 || preview_request.fps == 0 || preview_request.width == 0 {
+            return Err(AtiumError::InvalidRequest("Preview duration, fps and width must be greater than 0".to_string()))
         }
-    }
-    /// Extracts a thumbnail and returns a [`ThumbnailResponse`]
-    pub fn extract_thumbnail(&self, thumbnail_request: ThumbnailRequest) -> Result<ThumbnailResponse, AtiumError> {
-        let input_file = thumbnail_request.clone().input_file.expect("INPUT_FILE Cannot be empty");
+        check_preview_width(preview_request.format, preview_request.width)?;
 
-        let mut output_file = thumbnail_request.clone().output_file
-            .unwrap_or_else(|| self.build_output_from_input_path(input_file.clone()));
+        self.check_encoder(preview_request.format.encoder())?;
 
-        output_file = compute_output_file(&output_file, "jpeg")?;
+        let analysis = self.analyzer.analyze(&input_file)?;
+        let source_duration = analysis.duration()?;
+        let frame_rate = analysis.video_track().ok().and_then(|video| video.frame_rate);
+        let (timestamps, scored_frame) = match preview_request.selection {
+            ThumbnailSelection::Auto => {
+                let scored_frame = self.select_frame(&input_file, source_duration)?;
+                (vec![scored_frame.timestamp], Some(scored_frame))
+            }
+            selection => (resolve_timestamps(&selection, source_duration, frame_rate)?, None)
+        };
 
-        let timestamp = self.compute_timestamp(input_file.clone(), thumbnail_request)?;
+        let output_file = preview_request.output_file
+            .unwrap_or_else(|| format!("{}-preview.{}", input_file, preview_request.format.extension()));
+        let count = timestamps.len();
+        let mut outputs = Vec::with_capacity(count);
+        let mut starts = Vec::with_capacity(count);
+        let clip = preview_request.duration.min(source_duration);
 
-        let args = self.build_args(input_file, output_file.clone(), timestamp);
+        for (index, timestamp) in timestamps.into_iter().enumerate() {
+            let (start, _) = clip_window(timestamp, clip, source_duration);
+            let output = compute_output_file(&numbered_output(&output_file, index, count), preview_request.format.extension())?;
+            let args = preview_args(&input_file, &output, start, clip, preview_request.format, preview_request.fps, preview_request.width);
 
-        match self.command_manager.execute_with_args(args.iter().map(AsRef::as_ref).collect()) {
-            Ok(result) => {
-                if !result.status.success() {
-                    self.command_manager.print_command_output(result.stderr)?;
-                    return Err(AtiumError::ConversionError("Execution of command returned ERROR".to_string()))
-                }
-                info!("Thumbnail extracted at path [{}]", output_file);
-                Ok(ThumbnailResponse{ output: output_file })
-            }
-            Err(err) => Err(err)
+            self.execute(args, "ffmpeg could not extract the preview")?;
+
+            outputs.push(output);
+            starts.push(start);
         }
+
+        info!("{} preview(s) extracted at paths {:?}", outputs.len(), outputs);
+        Ok(PreviewResponse { outputs, starts, duration: clip, scored_frame })
     }
     /// Constructs a new instance of [`FFMPEGThumbnailService`] by loading and checking `ffmpeg` availability
     pub fn new() -> Result<Self, AtiumError> {
         let command_manager =
             CommandManager::new("ffmpeg".to_string(), vec!["-version"])?;
+        let analyzer = load_analyzer()?;
 
-        Ok(Self { command_manager })
+        Ok(Self { command_manager, analyzer })
     }
-}
\ No newline at end of file
+}
+
+/// Resolves the timestamps of a [`ThumbnailSelection`] against the source duration.
+///
+/// Timestamps are parsed as [`Timestamp`], timecodes being resolved with the source `frame_rate`,
+/// and those past the duration fall back to the beginning of the video.
+/// Fails on unparsable timestamps, a zero interval, percentages outside `[0, 100)`,
+/// or when more than [`MAX_THUMBNAILS`] frames are requested.
+/// [`ThumbnailSelection::Auto`] is resolved beforehand by scoring the candidate frames.
+fn resolve_timestamps(selection: &ThumbnailSelection, duration: Duration, frame_rate: Option<f64>) -> Result<Vec<Duration>, AtiumError> {
+    let timestamps = match selection {
+        ThumbnailSelection::Timestamps(timestamps) => timestamps.iter()
+            .map(|timestamp| {
+                let position = timestamp.parse::<Timestamp>()?.resolve(duration, frame_rate)?;
+
+                if position < duration {
+                    Ok(position)
+                } else {
+                    debug!("Requested timestamp [{}] is greater than the input duration, it will be set to 00:00:00.000", timestamp);
+                    Ok(Duration::ZERO)
+                }
+            })
+            .collect::<Result<Vec<Duration>, AtiumError>>()?,
+        ThumbnailSelection::Interval(interval) => {
+            if interval.as_millis() == 0 {
+                return Err(AtiumError::InvalidRequest("Thumbnail interval must be at least 1ms".to_string()))
+            }
+            let count = (duration.as_secs_f64() / interval.as_secs_f64()).ceil().max(1.0);
+            if count > MAX_THUMBNAILS as f64 {
+                return Err(too_many_thumbnails(count as usize))
+            }
+
+            (0..count as u32).map(|index| *interval * index).collect()
+        }
+        ThumbnailSelection::Auto => return Err(AtiumError::InvalidRequest("Automatic thumbnail selection needs the candidate frames to be scored".to_string())),
+        ThumbnailSelection::Percentages(percentages) => percentages.iter()
+            .map(|percentage| {
+                if (0.0..100.0).contains(percentage) {
+                    Ok(duration.mul_f64(percentage / 100.0))
+                } else {
+                    Err(AtiumError::InvalidRequest(format!("Thumbnail percentage [{}] must be within [0, 100)", percentage)))
+                }
+            })
+            .collect::<Result<Vec<Duration>, AtiumError>>()?
+    };
+
+    if timestamps.is_empty() {
+        return Err(AtiumError::InvalidRequest("At least one thumbnail must be requested".to_string()))
+    }
+    if timestamps.len() > MAX_THUMBNAILS {
+        return Err(too_many_thumbnails(timestamps.len()))
+    }
+
+    Ok(timestamps)
+}
+
+fn too_many_thumbnails(count: usize) -> AtiumError {
+    AtiumError::InvalidRequest(format!(
+        "{} thumbnails requested, at most {} can be extracted at once: use a sprite for dense previews", count, MAX_THUMBNAILS
+    ))
+}
+
+/// Fails when the size or the quality of the images are out of range
+fn check_image_output(image: &ImageOutput) -> Result<(), AtiumError> {
+    if image.width == Some(0) || image.height == Some(0) {
+        return Err(AtiumError::InvalidRequest("Thumbnail width and height must be greater than 0".to_string()))
+    }
+    match image.quality {
+        Some(quality) if !(1..=100).contains(&quality) =>
+            Err(AtiumError::InvalidRequest(format!("Thumbnail quality [{}] must be within [1, 100]", quality))),
+        _ => Ok(())
+    }
+}
+
+/// The `ffmpeg` output arguments encoding an image: the frame is fitted within the requested size,
+/// and the quality is mapped on the scale of the encoder
+fn image_args(image: &ImageOutput) -> Vec<String> {
+    let mut args = Vec::new();
+
+    let scale = match (image.width, image.height) {
+        (Some(width), Some(height)) => Some(format!("scale={}:{}:force_original_aspect_ratio=decrease", width, height)),
+        (Some(width), None) => Some(format!("scale={}:-2", width)),
+        (None, Some(height)) => Some(format!("scale=-2:{}", height)),
+        (None, None) => None
+    };
+    if let Some(scale) = scale {
+        args.extend([String::from("-vf"), scale]);
+    }
+
+    args.extend([String::from("-c:v"), image.format.encoder().to_string()]);
+
+    let quality = image.quality.map(|quality| quality.clamp(1, 100) as u32);
+    match (image.format, quality) {
+        // qscale from 2 (best) to 31 (worst)
+        (ImageFormat::Jpeg, Some(quality)) => args.extend([String::from("-q:v"), (2 + (100 - quality) * 29 / 99).to_string()]),
+        (ImageFormat::WebP, Some(quality)) => args.extend([String::from("-quality"), quality.to_string()]),
+        // crf from 0 (best) to 63 (worst)
+        (ImageFormat::Avif, Some(quality)) => args.extend([String::from("-crf"), ((100 - quality) * 63 / 99).to_string()]),
+        _ => {}
+    }
+    if image.format == ImageFormat::Avif {
+        args.extend([String::from("-still-picture"), String::from("1")]);
+    }
+
+    // the muxer follows the format whatever the output extension
+    let muxer = match image.format {
+        ImageFormat::Avif => "avif",
+        _ => "image2"
+    };
+    args.extend([String::from("-f"), muxer.to_string()]);
+
+    args
+}
+
+/// The path of the `index`-th of `count` thumbnails: a single thumbnail keeps `output_file`,
+/// several ones are numbered from 1 before the extension
+fn numbered_output(output_file: &str, index: usize, count: usize) -> String {
+    if count <= 1 {
+        return output_file.to_string()
+    }
+
+    let path = Path::new(output_file);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => path
+            .with_file_name(format!("{}-{:03}.{}", stem.to_string_lossy(), index + 1, extension.to_string_lossy()))
+            .to_string_lossy()
+            .to_string(),
+        _ => format!("{}-{:03}", output_file, index + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve_timestamps() {
+        let duration = Duration::from_secs(100);
+
+        let timestamps = ThumbnailSelection::Timestamps(vec!["00:00:10".to_string(), "00:02:00".to_string()]);
+        assert_eq!(resolve_timestamps(&timestamps, duration, None).unwrap(), vec![Duration::from_secs(10), Duration::ZERO]);
+        assert!(resolve_timestamps(&ThumbnailSelection::Timestamps(vec!["ten".to_string()]), duration, None).is_err());
+
+        let precise = ThumbnailSelection::Timestamps(vec!["00:01:39.500".to_string(), "99.999".to_string(), "00:01:40.000".to_string(), "25%".to_string()]);
+        assert_eq!(resolve_timestamps(&precise, duration, None).unwrap(), vec![
+            Duration::from_millis(99_500), Duration::from_millis(99_999), Duration::ZERO, Duration::from_secs(25)
+        ]);
+        let timecode = ThumbnailSelection::Timestamps(vec!["00:00:10:12".to_string()]);
+        assert_eq!(resolve_timestamps(&timecode, duration, Some(25.0)).unwrap(), vec![Duration::from_millis(10_480)]);
+        assert!(resolve_timestamps(&timecode, duration, None).is_err());
+
+        let interval = ThumbnailSelection::Interval(Duration::from_secs(30));
+        assert_eq!(resolve_timestamps(&interval, duration, None).unwrap(), vec![
+            Duration::ZERO, Duration::from_secs(30), Duration::from_secs(60), Duration::from_secs(90)
+        ]);
+        assert!(resolve_timestamps(&ThumbnailSelection::Interval(Duration::ZERO), duration, None).is_err());
+        assert!(resolve_timestamps(&ThumbnailSelection::Interval(Duration::from_millis(10)), duration, None).is_err());
+
+        let percentages = ThumbnailSelection::Percentages(vec![10.0, 50.0, 90.0]);
+        assert_eq!(resolve_timestamps(&percentages, duration, None).unwrap(), vec![
+            Duration::from_secs(10), Duration::from_secs(50), Duration::from_secs(90)
+        ]);
+        assert!(resolve_timestamps(&ThumbnailSelection::Percentages(vec![100.0]), duration, None).is_err());
+        assert!(resolve_timestamps(&ThumbnailSelection::Percentages(vec![]), duration, None).is_err());
+    }
+
+    #[test]
+    fn test_image_args() {
+        assert_eq!(image_args(&ImageOutput::default()), vec!["-c:v", "mjpeg", "-f", "image2"]);
+
+        let jpeg = ImageOutput { width: Some(320), height: Some(180), quality: Some(100), ..ImageOutput::default() };
+        assert_eq!(image_args(&jpeg), vec![
+            "-vf", "scale=320:180:force_original_aspect_ratio=decrease", "-c:v", "mjpeg", "-q:v", "2", "-f", "image2"
+        ]);
+
+        let avif = ImageOutput { format: ImageFormat::Avif, width: None, height: Some(360), quality: Some(1) };
+        assert_eq!(image_args(&avif), vec![
+            "-vf", "scale=-2:360", "-c:v", "libaom-av1", "-crf", "63", "-still-picture", "1", "-f", "avif"
+        ]);
+
+        let png = ImageOutput { format: ImageFormat::Png, width: Some(640), height: None, quality: Some(50) };
+        assert_eq!(image_args(&png), vec!["-vf", "scale=640:-2", "-c:v", "png", "-f", "image2"]);
+
+        assert!(check_image_output(&ImageOutput { quality: Some(0), ..ImageOutput::default() }).is_err());
+        assert!(check_image_output(&ImageOutput { width: Some(0), ..ImageOutput::default() }).is_err());
+    }
+
+    #[test]
+    fn test_numbered_output() {
+        assert_eq!(numbered_output("/tmp/thumb.jpeg", 0, 1), "/tmp/thumb.jpeg");
+        assert_eq!(numbered_output("/tmp/thumb.jpeg", 1, 3), "/tmp/thumb-002.jpeg");
+        assert_eq!(numbered_output("video.mp4.jpeg", 0, 2), "video.mp4-001.jpeg");
+        assert_eq!(numbered_output("thumb", 9, 10), "thumb-010");
+    }
+}
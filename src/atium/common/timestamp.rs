@@ -0,0 +1,247 @@
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+use crate::atium::common::error::AtiumError;
+
+/// A position in a video:
+/// * Time       -> `hh:mm:ss.mmm`, `mm:ss.mmm` or plain seconds like `90.25`, the fraction being optional
+/// * Timecode   -> SMPTE timecode `hh:mm:ss:ff`, or `hh:mm:ss;ff` for drop-frame, resolved with the source frame rate
+/// * Percentage -> percentage of the duration like `50%`, within `[0, 100)`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Timestamp {
+    Time(Duration),
+    Timecode { hours: u64, minutes: u64, seconds: u64, frames: u64, drop_frame: bool },
+    Percentage(f64)
+}
+
+fn invalid(timestamp: &str) -> AtiumError {
+    AtiumError::InvalidRequest(format!(
+        "Could not parse requested timestamp [{}], expected hh:mm:ss.mmm, seconds, hh:mm:ss:ff or a percentage", timestamp
+    ))
+}
+
+impl FromStr for Timestamp {
+    type Err = AtiumError;
+
+    fn from_str(timestamp_string: &str) -> Result<Self, Self::Err> {
+        let timestamp = timestamp_string.trim();
+
+        if let Some(percentage) = timestamp.strip_suffix('%') {
+            return percentage.trim().parse::<f64>().ok()
+                .filter(|percentage| (0.0..100.0).contains(percentage))
+                .map(Timestamp::Percentage)
+                .ok_or_else(|| invalid(timestamp))
+        }
+
+        if let Some((time, frames)) = timestamp.rsplit_once(';') {
+            return parse_timecode(time, frames, true).ok_or_else(|| invalid(timestamp))
+        }
+
+        let parts: Vec<&str> = timestamp.split(':').collect();
+        let parsed = match parts.as_slice() {
+            [hours, minutes, seconds, frames] => return parse_timecode(&format!("{}:{}:{}", hours, minutes, seconds), frames, false)
+                .ok_or_else(|| invalid(timestamp)),
+            [hours, minutes, seconds] => sexagesimal(Some(hours), minutes, seconds),
+            [minutes, seconds] => sexagesimal(None, minutes, seconds),
+            [seconds] => parse_seconds(seconds),
+            _ => None
+        };
+
+        parsed.map(Timestamp::Time).ok_or_else(|| invalid(timestamp))
+    }
+}
+
+/// Parses a non negative number of seconds with an optional fraction, keeping the millisecond precision
+fn parse_seconds(seconds: &str) -> Option<Duration> {
+    if seconds.is_empty() || !seconds.chars().all(|c| c.is_ascii_digit() || c == '.') {
+        return None
+    }
+    seconds.parse::<f64>().ok()
+        .map(|seconds| (seconds * 1000.0).round())
+        // the cast would saturate on larger values
+        .filter(|millis| *millis < u64::MAX as f64)
+        .map(|millis| Duration::from_millis(millis as u64))
+}
+
+fn parse_field(field: &str, max: u64) -> Option<u64> {
+    if field.is_empty() || !field.chars().all(|c| c.is_ascii_digit()) {
+        return None
+    }
+    field.parse::<u64>().ok().filter(|value| *value < max)
+}
+
+fn sexagesimal(hours: Option<&&str>, minutes: &str, seconds: &str) -> Option<Duration> {
+    let hours = match hours {
+        Some(hours) => parse_field(hours, u64::MAX)?,
+        None => 0
+    };
+    let minutes = parse_field(minutes, 60)?;
+    let seconds = parse_seconds(seconds).filter(|seconds| seconds.as_secs() < 60)?;
+
+    let whole_seconds = hours.checked_mul(3600)?.checked_add(minutes * 60)?;
+
+    Duration::from_secs(whole_seconds).checked_add(seconds)
+}
+
+fn parse_timecode(time: &str, frames: &str, drop_frame: bool) -> Option<Timestamp> {
+    let fields: Vec<&str> = time.split(':').collect();
+    match fields.as_slice() {
+        [hours, minutes, seconds] => Some(Timestamp::Timecode {
+            hours: parse_field(hours, u64::MAX)?,
+            minutes: parse_field(minutes, 60)?,
+            seconds: parse_field(seconds, 60)?,
+            frames: parse_field(frames, u64::MAX)?,
+            drop_frame
+        }),
+        _ => None
+    }
+}
+
+impl Timestamp {
+    /// Resolves the position within a `duration` long video, timecodes need the source `frame_rate`.
+    ///
+    /// Fails when the frames of a timecode exceed the frame rate, when the position is out of range,
+    /// or when drop-frame is used at a rate other than 29.97 or 59.94 or on a label it skips
+    pub fn resolve(&self, duration: Duration, frame_rate: Option<f64>) -> Result<Duration, AtiumError> {
+        match *self {
+            Timestamp::Time(time) => Ok(time),
+            Timestamp::Percentage(percentage) => Ok(duration.mul_f64(percentage / 100.0)),
+            Timestamp::Timecode { hours, minutes, seconds, frames, drop_frame } => {
+                let frame_rate = frame_rate.filter(|rate| rate.is_finite() && *rate > 0.0)
+                    .ok_or_else(|| AtiumError::InvalidRequest(format!("Timecode [{}] needs the source frame rate", self)))?;
+                let nominal = frame_rate.round() as u64;
+                if frames >= nominal {
+                    return Err(AtiumError::InvalidRequest(format!("Timecode [{}] exceeds the {} frames of a second", self, nominal)))
+                }
+
+                let out_of_range = || AtiumError::InvalidRequest(format!("Timecode [{}] is out of range", self));
+                let mut frame_number = hours.checked_mul(3600)
+                    .and_then(|whole_seconds| whole_seconds.checked_add(minutes * 60 + seconds))
+                    .and_then(|whole_seconds| whole_seconds.checked_mul(nominal))
+                    .and_then(|frame_number| frame_number.checked_add(frames))
+                    .ok_or_else(out_of_range)?;
+                if drop_frame {
+                    // frame numbers 0 and 1 (0 to 3 at 59.94) are skipped every minute but every tenth one
+                    let dropped = match nominal {
+                        30 => 2,
+                        60 => 4,
+                        _ => return Err(AtiumError::InvalidRequest(format!("Drop-frame timecode [{}] needs a 29.97 or 59.94 frame rate", self)))
+                    };
+                    if seconds == 0 && minutes % 10 != 0 && frames < dropped {
+                        return Err(AtiumError::InvalidRequest(format!(
+                            "Drop-frame timecode [{}] does not exist, the first {} frames of the minute are skipped", self, dropped
+                        )))
+                    }
+                    let total_minutes = hours * 60 + minutes;
+                    frame_number -= dropped * (total_minutes - total_minutes / 10);
+                }
+
+                // NTSC rates are often reported rounded, e.g. 29.97, which drifts by a frame within the hour
+                let ntsc = (frame_rate * 1.001).round();
+                let frame_rate = if (ntsc / 1.001 - frame_rate).abs() < 0.01 { ntsc / 1.001 } else { frame_rate };
+                // the small offset absorbs the float error on exact positions, before truncating to the nanosecond
+                let nanos = (frame_number as f64 * 1_000_000_000.0 / frame_rate + 0.001).floor();
+                if nanos >= u64::MAX as f64 {
+                    return Err(out_of_range())
+                }
+
+                Ok(Duration::from_nanos(nanos as u64))
+            }
+        }
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Timestamp::Time(time) => {
+                let millis = time.as_millis();
+                write!(f, "{:02}:{:02}:{:02}.{:03}", millis / 3_600_000, millis / 60_000 % 60, millis / 1000 % 60, millis % 1000)
+            }
+            Timestamp::Timecode { hours, minutes, seconds, frames, drop_frame } => {
+                let separator = if *drop_frame { ';' } else { ':' };
+                write!(f, "{:02}:{:02}:{:02}{}{:02}", hours, minutes, seconds, separator, frames)
+            }
+            Timestamp::Percentage(percentage) => write!(f, "{}%", percentage)
+        }
+    }
+}
+
+/// Formats a position as an `ffmpeg` seek argument in seconds, truncated to the microsecond
+/// so that the seek never lands after the frame it points to
+pub fn seek_position(position: Duration) -> String {
+    format!("{}.{:06}", position.as_secs(), position.subsec_micros())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(timestamp: &str) -> Timestamp {
+        timestamp.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse("00:00:01"), Timestamp::Time(Duration::from_secs(1)));
+        assert_eq!(parse("00:00:01.500"), Timestamp::Time(Duration::from_millis(1500)));
+        assert_eq!(parse("01:02:03.4"), Timestamp::Time(Duration::from_millis(3_723_400)));
+        assert_eq!(parse("02:30"), Timestamp::Time(Duration::from_secs(150)));
+        assert_eq!(parse("90.25"), Timestamp::Time(Duration::from_millis(90_250)));
+        assert_eq!(parse(" 12 "), Timestamp::Time(Duration::from_secs(12)));
+
+        assert!("00:61:00".parse::<Timestamp>().is_err());
+        assert!("00:00:60.5".parse::<Timestamp>().is_err());
+        assert!("-5".parse::<Timestamp>().is_err());
+        assert!("99999999999999999:00:00".parse::<Timestamp>().is_err());
+        assert!("99999999999999999999999".parse::<Timestamp>().is_err());
+        assert!("ten".parse::<Timestamp>().is_err());
+        assert!("".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn test_parse_timecode_and_percentage() {
+        assert_eq!(parse("00:01:00:12"), Timestamp::Timecode { hours: 0, minutes: 1, seconds: 0, frames: 12, drop_frame: false });
+        assert_eq!(parse("00:10:00;02"), Timestamp::Timecode { hours: 0, minutes: 10, seconds: 0, frames: 2, drop_frame: true });
+        assert_eq!(parse("50%"), Timestamp::Percentage(50.0));
+
+        assert!("100%".parse::<Timestamp>().is_err());
+        assert!("00:00:00:aa".parse::<Timestamp>().is_err());
+    }
+
+    #[test]
+    fn test_resolve() {
+        let duration = Duration::from_secs(600);
+
+        assert_eq!(parse("12.5%").resolve(duration, None).unwrap(), Duration::from_secs(75));
+        assert_eq!(parse("00:00:01:12").resolve(duration, Some(25.0)).unwrap(), Duration::from_millis(1480));
+        assert!(parse("00:00:01:25").resolve(duration, Some(25.0)).is_err());
+        assert!(parse("00:00:01:12").resolve(duration, None).is_err());
+
+        // 00:01:00;02 is the first frame of the second minute, frame 1800
+        let drop_frame = parse("00:01:00;02").resolve(duration, Some(30000.0 / 1001.0)).unwrap();
+        assert_eq!(drop_frame.as_millis(), 60_060);
+        // no frame is dropped on the tenth minute, frame 17982
+        let tenth_minute = parse("00:10:00;00").resolve(duration, Some(30000.0 / 1001.0)).unwrap();
+        assert_eq!(tenth_minute.as_millis(), 599_999);
+        assert!(parse("00:01:00;02").resolve(duration, Some(25.0)).is_err());
+        // the labels skipped by drop-frame do not exist
+        assert!(parse("00:01:00;00").resolve(duration, Some(30000.0 / 1001.0)).is_err());
+        assert!(parse("00:01:00;01").resolve(duration, Some(30000.0 / 1001.0)).is_err());
+        assert!(parse("00:01:00;03").resolve(duration, Some(60000.0 / 1001.0)).is_err());
+        assert!(parse("00:01:00;04").resolve(duration, Some(60000.0 / 1001.0)).is_ok());
+        // overflowing positions are rejected
+        assert!(parse("99999999999999999:00:00:00").resolve(duration, Some(25.0)).is_err());
+        assert!(parse("00:00:02:00").resolve(duration, Some(1e30)).is_err());
+        // a rounded NTSC rate resolves to the same frame
+        assert_eq!(parse("01:00:00;00").resolve(duration, Some(29.97)).unwrap(), parse("01:00:00;00").resolve(duration, Some(30000.0 / 1001.0)).unwrap());
+    }
+
+    #[test]
+    fn test_display_and_seek_position() {
+        assert_eq!(parse("90.25").to_string(), "00:01:30.250");
+        assert_eq!(parse("00:10:00;02").to_string(), "00:10:00;02");
+        assert_eq!(seek_position(Duration::from_secs_f64(2002.0 / 30000.0)), "0.066733");
+        assert_eq!(seek_position(Duration::from_millis(90_250)), "90.250000");
+    }
+}
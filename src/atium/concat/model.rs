@@ -0,0 +1,34 @@
+use std::time::Duration;
+use crate::converter::model::{OutputCodec, OutputContainer, RateControl};
+
+/// Methods joining the inputs:
+/// * Copy      -> `concat` demuxer with stream copy, the inputs share their codecs, resolution and frame rate
+/// * Normalize -> every input is scaled, converted to a common frame rate and resampled, then joined by the `concat` filter
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConcatMethod {
+    Copy, Normalize
+}
+
+/// Concatenation request, the inputs are joined in the given order
+pub struct ConcatRequest {
+    /// At least two input files
+    pub inputs: Vec<String>,
+    pub output_file: String,
+    pub container: OutputContainer,
+    /// Video codec of normalized inputs, streams are copied when the inputs match
+    pub codec: OutputCodec,
+    pub rate_control: Option<RateControl>,
+    pub preset: Option<String>,
+    /// Normalizes the inputs even when they could be stream copied
+    pub normalize: bool
+}
+
+/// Concatenation response
+pub struct ConcatResponse {
+    pub output_file: String,
+    pub method: ConcatMethod,
+    /// Why the inputs could not be stream copied, empty with [`ConcatMethod::Copy`]
+    pub mismatches: Vec<String>,
+    /// Sum of the input durations
+    pub duration: Duration
+}
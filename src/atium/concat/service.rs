@@ -0,0 +1,515 @@
+use std::{env, fs, io};
+use std::path::{Path, PathBuf};
+use std::process::Output;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::atium::common::analysis_helper::{AnalysisOutput, compute_output_file};
+use crate::atium::common::analyzer::{load_analyzer, MediaAnalyzer};
+use crate::atium::common::command_manager::{CommandManager, ExecutionControl};
+use crate::atium::common::error::AtiumError;
+use crate::atium::common::progress::{Progress, ProgressParser, ProgressStage};
+use crate::atium::concat::model::{ConcatMethod, ConcatRequest, ConcatResponse};
+use crate::converter::model::{audio_codec_from_format, AudioCodec, AudioOutput, codec_from_format, OutputCodec, OutputContainer};
+use crate::converter::service::{compute_display_size, is_encoder_listed};
+
+/// Sample rate of normalized inputs, when the reference input has no audio
+const DEFAULT_SAMPLE_RATE: u32 = 48000;
+/// Audio bitrate of normalized inputs in kbit/s
+const AUDIO_BITRATE: u32 = 192;
+
+/// The audio properties of an input that must match for a stream copy
+#[derive(Clone, Debug, Default, PartialEq)]
+struct AudioProfile {
+    format: Option<String>,
+    sampling_rate: Option<u32>,
+    channels: Option<u32>
+}
+
+/// The stream properties of an input, as analysed
+#[derive(Clone, Debug, Default, PartialEq)]
+struct InputProfile {
+    video_format: Option<String>,
+    /// Display size in pixels, after the rotation
+    size: (u32, u32),
+    frame_rate: Option<f64>,
+    /// The first audio track, None without audio
+    audio: Option<AudioProfile>,
+    duration: Duration
+}
+
+impl InputProfile {
+    fn from_analysis(analysis_output: &AnalysisOutput) -> Result<Self, AtiumError> {
+        let video_track = analysis_output.video_track()?;
+
+        Ok(InputProfile {
+            size: compute_display_size(&video_track)?,
+            video_format: video_track.format,
+            frame_rate: video_track.frame_rate,
+            audio: analysis_output.audio_tracks().into_iter().next().map(|audio_track| AudioProfile {
+                format: audio_track.format,
+                sampling_rate: audio_track.sampling_rate,
+                channels: audio_track.channels
+            }),
+            duration: analysis_output.duration()?
+        })
+    }
+}
+
+/// The concat list of a stream copy, removed when dropped so that no early return leaks it
+struct ListFile(PathBuf);
+
+impl ListFile {
+    fn new() -> Self {
+        ListFile(env::temp_dir().join(format!("{}.ffconcat", Uuid::new_v4())))
+    }
+}
+
+impl Drop for ListFile {
+    fn drop(&mut self) {
+        match fs::remove_file(&self.0) {
+            Ok(_) => debug!("Concat list [{}] removed", self.0.display()),
+            // the list was never written
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {},
+            Err(err) => warn!("Concat list [{}] not removed: {}", self.0.display(), err)
+        }
+    }
+}
+
+/// Joins several inputs into a single output, stream copying them when they match
+pub struct FFMPEGConcatService {
+    command_manager: CommandManager,
+    analyzer: Box<dyn MediaAnalyzer>
+}
+
+impl FFMPEGConcatService {
+    fn check_request(&self, request: &ConcatRequest) -> Result<(), AtiumError> {
+        if request.inputs.len() < 2 {
+            return Err(AtiumError::InvalidRequest("Concatenation needs at least two inputs".to_string()))
+        }
+        for input in &request.inputs {
+            if !Path::new(input).is_file() {
+                return Err(AtiumError::Input { message: format!("Input file [{}] does not exist", input), source: None })
+            }
+        }
+
+        Ok(())
+    }
+    fn check_encoders(&self, request: &ConcatRequest) -> Result<(), AtiumError> {
+        let result = self.command_manager.execute_with_args(vec!["-hide_banner", "-encoders"])?;
+        let listing = self.command_manager.get_command_output_as_string(result.stdout)?;
+
+        for encoder in [request.codec.encoder(), audio_codec(request.container).encoder()].into_iter().flatten() {
+            if !is_encoder_listed(&listing, encoder) {
+                return Err(AtiumError::Dependency(format!("Encoder [{}] is not available in the local ffmpeg build", encoder)))
+            }
+        }
+
+        Ok(())
+    }
+    fn build_copy_args(&self, request: &ConcatRequest, list_file: &str, output_file: String) -> Vec<String> {
+        vec![
+            String::from("-f"), String::from("concat"),
+            String::from("-safe"), String::from("0"),
+            String::from("-i"), list_file.to_string(),
+            String::from("-c"), String::from("copy"),
+            String::from("-f"), request.container.muxer().to_string(),
+            output_file
+        ]
+    }
+    fn build_normalize_args(&self, request: &ConcatRequest, profiles: &[InputProfile], output_file: String) -> Result<Vec<String>, AtiumError> {
+        let mut args = Vec::new();
+        for input in &request.inputs {
+            args.push(String::from("-i"));
+            args.push(input.clone());
+        }
+
+        let has_audio = profiles.iter().any(|profile| profile.audio.is_some());
+        args.push(String::from("-filter_complex"));
+        args.push(normalize_filter(profiles));
+        args.push(String::from("-map"));
+        args.push(String::from("[v]"));
+        if has_audio {
+            args.push(String::from("-map"));
+            args.push(String::from("[a]"));
+        }
+
+        args.extend(request.codec.encoding_args(request.rate_control.as_ref(), request.preset.as_deref(), None)?);
+        if has_audio {
+            let audio = AudioOutput { codec: audio_codec(request.container), bitrate: Some(AUDIO_BITRATE), channels: None, sample_rate: None };
+            args.extend(audio.audio_args(None)?);
+        }
+        args.extend(vec![
+            String::from("-f"),
+            request.container.muxer().to_string(),
+            output_file
+        ]);
+
+        Ok(args)
+    }
+    fn execute(&self, args: Vec<String>, duration: Duration, control: &ExecutionControl, on_progress: &mut dyn FnMut(Progress)) -> Result<Output, AtiumError> {
+        let mut progress_args = vec![String::from("-nostdin"), String::from("-progress"), String::from("pipe:1"), String::from("-nostats")];
+        progress_args.extend(args);
+
+        let mut parser = ProgressParser::new(ProgressStage::Encoding, Some(duration));
+        let mut on_line = |line: &str| {
+            if let Some(progress) = parser.parse_line(line) {
+                on_progress(progress);
+            }
+        };
+
+        self.command_manager.execute_streaming(progress_args.iter().map(AsRef::as_ref).collect(), control, &mut on_line)
+            .map_err(|err| err.into_encode("ffmpeg concatenation failed"))
+    }
+    /// Writes the `concat` demuxer list of the inputs into a temporary file and stream copies them
+    fn concat_copy(&self, request: &ConcatRequest, output_file: String, duration: Duration, control: &ExecutionControl, on_progress: &mut dyn FnMut(Progress)) -> Result<Output, AtiumError> {
+        let inputs = request.inputs.iter()
+            .map(|input| fs::canonicalize(input)
+                .map(|path| path.to_string_lossy().to_string())
+                .map_err(|err| AtiumError::Input { message: format!("Could not resolve input file [{}]", input), source: Some(err) }))
+            .collect::<Result<Vec<String>, AtiumError>>()?;
+
+        let list_file = ListFile::new();
+        fs::write(&list_file.0, concat_list(&inputs))
+            .map_err(|err| AtiumError::Output { message: "Could not write the concat list".to_string(), source: Some(err) })?;
+
+        self.execute(self.build_copy_args(request, &list_file.0.to_string_lossy(), output_file), duration, control, on_progress)
+    }
+    /// Concatenates the inputs and returns a [`ConcatResponse`]
+    pub fn concat(&self, request: ConcatRequest) -> Result<ConcatResponse, AtiumError> {
+        self.concat_with_control(request, &ExecutionControl::default(), |_| {})
+    }
+    /// Concatenates the inputs like [`FFMPEGConcatService::concat`], reporting the progress of `ffmpeg`
+    /// to `on_progress`. Every external command is bound to `control`.
+    ///
+    /// The inputs are analysed first: when their codecs, resolution, frame rate and audio match they are
+    /// stream copied, otherwise they are normalized to the longest input and encoded with the requested codec.
+    pub fn concat_with_control<F: FnMut(Progress)>(&self, request: ConcatRequest, control: &ExecutionControl, mut on_progress: F) -> Result<ConcatResponse, AtiumError> {
+        self.check_request(&request)?;
+
+        let profiles = request.inputs.iter()
+            .map(|input| {
+                debug!("Analysing input [{}]", input);
+                InputProfile::from_analysis(&self.analyzer.analyze_with_control(input, control)?)
+            })
+            .collect::<Result<Vec<InputProfile>, AtiumError>>()?;
+        let duration = profiles.iter().map(|profile| profile.duration).sum();
+
+        let mismatches = if request.normalize {
+            vec![String::from("normalization was requested")]
+        } else {
+            mismatches(&request.inputs, &profiles, request.container)
+        };
+        let method = if mismatches.is_empty() { ConcatMethod::Copy } else { ConcatMethod::Normalize };
+        mismatches.iter().for_each(|mismatch| info!("Inputs are normalized: {}", mismatch));
+
+        if method == ConcatMethod::Normalize {
+            check_normalize_codec(&request)?;
+            self.check_encoders(&request)?;
+        }
+
+        let output_file = compute_output_file(&request.output_file, request.container.extension())?;
+        debug!("Concatenating {} inputs into [{}] with method [{:?}]", request.inputs.len(), output_file, method);
+
+        let result = match method {
+            ConcatMethod::Copy => self.concat_copy(&request, output_file.clone(), duration, control, &mut on_progress),
+            ConcatMethod::Normalize => self.build_normalize_args(&request, &profiles, output_file.clone())
+                .and_then(|args| self.execute(args, duration, control, &mut on_progress))
+        };
+        match result {
+            Ok(_) => {}
+            Err(err @ (AtiumError::Timeout { .. } | AtiumError::Cancelled { .. })) => {
+                // the output left behind by a killed ffmpeg is incomplete
+                if let Err(remove_err) = fs::remove_file(&output_file) {
+                    warn!("Partial output [{}] not removed: {}", output_file, remove_err);
+                }
+                return Err(err)
+            }
+            Err(err) => return Err(err)
+        }
+
+        debug!("Concatenation done!");
+
+        Ok(ConcatResponse { output_file, method, mismatches, duration })
+    }
+    /// Constructs a new instance of [`FFMPEGConcatService`]
+    pub fn new() -> Result<Self, AtiumError> {
+        let command_manager =
+            CommandManager::new("ffmpeg".to_string(), vec!["-version"])?;
+        let analyzer = load_analyzer()?;
+
+        Ok(Self { command_manager, analyzer })
+    }
+}
+
+/// Checks the codec normalized inputs are encoded with, it is not used when the inputs are stream copied
+fn check_normalize_codec(request: &ConcatRequest) -> Result<(), AtiumError> {
+    if request.codec == OutputCodec::Copy {
+        return Err(AtiumError::InvalidRequest("The inputs cannot be stream copied, a codec is needed to normalize them".to_string()))
+    }
+    if !request.container.supports(request.codec) {
+        return Err(AtiumError::InvalidRequest(format!("Codec [{:?}] cannot be muxed into a [{}] container", request.codec, request.container.extension())))
+    }
+    request.codec.encoding_args(request.rate_control.as_ref(), request.preset.as_deref(), None)?;
+
+    Ok(())
+}
+
+/// The audio codec of normalized inputs, the only one accepted by WebM or the most widely supported one
+fn audio_codec(container: OutputContainer) -> AudioCodec {
+    match container {
+        OutputContainer::WebM => AudioCodec::Opus,
+        _ => AudioCodec::Aac
+    }
+}
+
+fn same_frame_rate(first: Option<f64>, second: Option<f64>) -> bool {
+    match (first, second) {
+        (Some(first), Some(second)) => (first - second).abs() < 0.01,
+        (first, second) => first.is_none() && second.is_none()
+    }
+}
+
+fn describe_frame_rate(frame_rate: Option<f64>) -> String {
+    frame_rate.map(|frame_rate| format!("{}", frame_rate)).unwrap_or_else(|| "unknown".to_string())
+}
+
+fn describe_audio(audio: &Option<AudioProfile>) -> String {
+    match audio {
+        Some(audio) => format!(
+            "{} {}Hz {} channels",
+            audio.format.as_deref().unwrap_or("unknown"),
+            audio.sampling_rate.map(|rate| rate.to_string()).unwrap_or_else(|| "?".to_string()),
+            audio.channels.map(|channels| channels.to_string()).unwrap_or_else(|| "?".to_string())
+        ),
+        None => "no audio".to_string()
+    }
+}
+
+/// Lists why the inputs cannot be stream copied into `container`: every input is compared to the first one
+fn mismatches(inputs: &[String], profiles: &[InputProfile], container: OutputContainer) -> Vec<String> {
+    let mut mismatches = Vec::new();
+    let first = match profiles.first() {
+        Some(first) => first,
+        None => return mismatches
+    };
+
+    let source_codec = first.video_format.as_deref().and_then(codec_from_format);
+    if let Some(codec) = source_codec.filter(|codec| !container.supports(*codec)) {
+        mismatches.push(format!("codec [{:?}] cannot be muxed into a [{}] container", codec, container.extension()));
+    }
+    // only Matroska is trusted with the audio formats no codec matches, e.g. PCM
+    let audio_format = first.audio.as_ref().and_then(|audio| audio.format.as_deref());
+    let unsupported_audio = audio_format.filter(|format| match audio_codec_from_format(format) {
+        Some(codec) => !container.supports_audio(codec),
+        None => container != OutputContainer::Mkv
+    });
+    if let Some(format) = unsupported_audio {
+        mismatches.push(format!("audio format [{}] cannot be muxed into a [{}] container", format, container.extension()));
+    }
+
+    for (input, profile) in inputs.iter().zip(profiles).skip(1) {
+        if profile.video_format != first.video_format {
+            mismatches.push(format!(
+                "[{}] video codec {} differs from {}",
+                input, profile.video_format.as_deref().unwrap_or("unknown"), first.video_format.as_deref().unwrap_or("unknown")
+            ));
+        }
+        if profile.size != first.size {
+            mismatches.push(format!("[{}] resolution {}x{} differs from {}x{}", input, profile.size.0, profile.size.1, first.size.0, first.size.1));
+        }
+        if !same_frame_rate(profile.frame_rate, first.frame_rate) {
+            mismatches.push(format!(
+                "[{}] frame rate {} differs from {}", input, describe_frame_rate(profile.frame_rate), describe_frame_rate(first.frame_rate)
+            ));
+        }
+        if profile.audio != first.audio {
+            mismatches.push(format!("[{}] audio {} differs from {}", input, describe_audio(&profile.audio), describe_audio(&first.audio)));
+        }
+    }
+
+    mismatches
+}
+
+/// The input the others are normalized to, the longest one and the earliest one on ties
+fn reference_profile(profiles: &[InputProfile]) -> Option<&InputProfile> {
+    profiles.iter().fold(None, |reference: Option<&InputProfile>, profile| match reference {
+        Some(reference) if reference.duration >= profile.duration => Some(reference),
+        _ => Some(profile)
+    })
+}
+
+/// The filter graph normalizing every input to the size, frame rate and sample rate of the [`reference_profile`],
+/// then joining them with the `concat` filter into `[v]` and, when any input has audio, `[a]`.
+/// Inputs are fitted into the reference frame and padded, audio is stereo and inputs without audio get silence.
+fn normalize_filter(profiles: &[InputProfile]) -> String {
+    let reference = reference_profile(profiles).cloned().unwrap_or_default();
+    let (width, height) = ((reference.size.0 / 2 * 2).max(2), (reference.size.1 / 2 * 2).max(2));
+    let fps = reference.frame_rate.map(|frame_rate| format!(",fps={}", frame_rate)).unwrap_or_default();
+    let sample_rate = reference.audio.as_ref().and_then(|audio| audio.sampling_rate).unwrap_or(DEFAULT_SAMPLE_RATE);
+    let has_audio = profiles.iter().any(|profile| profile.audio.is_some());
+
+    let mut chains = Vec::new();
+    let mut joined = String::new();
+    for (index, profile) in profiles.iter().enumerate() {
+        chains.push(format!(
+            "[{index}:v:0]scale={width}:{height}:force_original_aspect_ratio=decrease,pad={width}:{height}:(ow-iw)/2:(oh-ih)/2,setsar=1{fps},format=yuv420p[v{index}]",
+            index = index, width = width, height = height, fps = fps
+        ));
+        joined.push_str(&format!("[v{}]", index));
+
+        if has_audio {
+            chains.push(match profile.audio {
+                Some(_) => format!("[{}:a:0]aresample={},aformat=channel_layouts=stereo[a{}]", index, sample_rate, index),
+                None => format!("anullsrc=r={}:cl=stereo,atrim=duration={:.6}[a{}]", sample_rate, profile.duration.as_secs_f64(), index)
+            });
+            joined.push_str(&format!("[a{}]", index));
+        }
+    }
+
+    let outputs = if has_audio { "[v][a]" } else { "[v]" };
+    chains.push(format!("{}concat=n={}:v=1:a={}{}", joined, profiles.len(), u8::from(has_audio), outputs));
+
+    chains.join(";")
+}
+
+/// The `concat` demuxer list of `inputs`, single quotes being escaped
+fn concat_list(inputs: &[String]) -> String {
+    let mut list = String::from("ffconcat version 1.0\n");
+    for input in inputs {
+        list.push_str(&format!("file '{}'\n", input.replace('\'', "'\\''")));
+    }
+
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn profile(format: &str, size: (u32, u32), frame_rate: f64, sampling_rate: Option<u32>, seconds: u64) -> InputProfile {
+        InputProfile {
+            video_format: Some(format.to_string()),
+            size,
+            frame_rate: Some(frame_rate),
+            audio: sampling_rate.map(|sampling_rate| AudioProfile {
+                format: Some("AAC".to_string()),
+                sampling_rate: Some(sampling_rate),
+                channels: Some(2)
+            }),
+            duration: Duration::from_secs(seconds)
+        }
+    }
+
+    fn inputs(count: usize) -> Vec<String> {
+        (0..count).map(|index| format!("in{}.mp4", index)).collect()
+    }
+
+    #[test]
+    fn test_matching_inputs() {
+        let profiles = vec![
+            profile("AVC", (1920, 1080), 25.0, Some(48000), 5),
+            profile("AVC", (1920, 1080), 25.000001, Some(48000), 600)
+        ];
+
+        assert!(mismatches(&inputs(2), &profiles, OutputContainer::Mp4).is_empty());
+        assert_eq!(mismatches(&inputs(2), &profiles, OutputContainer::WebM), vec![
+            "codec [H264] cannot be muxed into a [webm] container",
+            "audio format [AAC] cannot be muxed into a [webm] container"
+        ]);
+    }
+
+    #[test]
+    fn test_unsupported_audio() {
+        let mut profiles = vec![
+            profile("AVC", (1920, 1080), 25.0, Some(48000), 5),
+            profile("AVC", (1920, 1080), 25.0, Some(48000), 5)
+        ];
+        profiles.iter_mut().for_each(|profile| profile.audio.iter_mut().for_each(|audio| audio.format = Some("PCM".to_string())));
+
+        assert_eq!(mismatches(&inputs(2), &profiles, OutputContainer::Mp4), vec!["audio format [PCM] cannot be muxed into a [mp4] container"]);
+        assert!(mismatches(&inputs(2), &profiles, OutputContainer::Mkv).is_empty());
+    }
+
+    #[test]
+    fn test_matching_webm_inputs() {
+        let mut profiles = vec![
+            profile("VP9", (1280, 720), 30.0, Some(48000), 5),
+            profile("VP9", (1280, 720), 30.0, Some(48000), 8)
+        ];
+        profiles.iter_mut().for_each(|profile| profile.audio.iter_mut().for_each(|audio| audio.format = Some("Opus".to_string())));
+        assert!(mismatches(&inputs(2), &profiles, OutputContainer::WebM).is_empty());
+
+        // the default codec only matters when the inputs are normalized
+        let request = ConcatRequest {
+            inputs: inputs(2),
+            output_file: "out.webm".to_string(),
+            container: OutputContainer::WebM,
+            codec: OutputCodec::H264,
+            rate_control: None,
+            preset: None,
+            normalize: false
+        };
+        assert!(check_normalize_codec(&request).is_err());
+        assert!(check_normalize_codec(&ConcatRequest { codec: OutputCodec::Vp9, ..request }).is_ok());
+    }
+
+    #[test]
+    fn test_mismatching_inputs() {
+        let profiles = vec![
+            profile("AVC", (1920, 1080), 25.0, Some(48000), 5),
+            profile("HEVC", (1280, 720), 29.97, Some(44100), 600),
+            profile("AVC", (1920, 1080), 25.0, None, 5)
+        ];
+
+        assert_eq!(mismatches(&inputs(3), &profiles, OutputContainer::Mp4), vec![
+            "[in1.mp4] video codec HEVC differs from AVC",
+            "[in1.mp4] resolution 1280x720 differs from 1920x1080",
+            "[in1.mp4] frame rate 29.97 differs from 25",
+            "[in1.mp4] audio AAC 44100Hz 2 channels differs from AAC 48000Hz 2 channels",
+            "[in2.mp4] audio no audio differs from AAC 48000Hz 2 channels"
+        ]);
+    }
+
+    #[test]
+    fn test_normalize_filter() {
+        let profiles = vec![
+            profile("AVC", (1280, 720), 25.0, None, 5),
+            profile("HEVC", (1920, 1080), 29.97, Some(44100), 600)
+        ];
+
+        assert_eq!(normalize_filter(&profiles), [
+            "[0:v:0]scale=1920:1080:force_original_aspect_ratio=decrease,pad=1920:1080:(ow-iw)/2:(oh-ih)/2,setsar=1,fps=29.97,format=yuv420p[v0]",
+            "anullsrc=r=44100:cl=stereo,atrim=duration=5.000000[a0]",
+            "[1:v:0]scale=1920:1080:force_original_aspect_ratio=decrease,pad=1920:1080:(ow-iw)/2:(oh-ih)/2,setsar=1,fps=29.97,format=yuv420p[v1]",
+            "[1:a:0]aresample=44100,aformat=channel_layouts=stereo[a1]",
+            "[v0][a0][v1][a1]concat=n=2:v=1:a=1[v][a]"
+        ].join(";"));
+
+        let silent = vec![profile("AVC", (1280, 720), 25.0, None, 5), profile("AVC", (1281, 721), 25.0, None, 5)];
+        assert!(normalize_filter(&silent).ends_with("[v0][v1]concat=n=2:v=1:a=0[v]"));
+        assert!(normalize_filter(&silent).contains("pad=1280:720:"));
+    }
+
+    #[test]
+    fn test_concat_list() {
+        let list = concat_list(&["/videos/intro.mp4".to_string(), "/videos/it's.mp4".to_string()]);
+
+        assert_eq!(list, "ffconcat version 1.0\nfile '/videos/intro.mp4'\nfile '/videos/it'\\''s.mp4'\n");
+    }
+
+    #[test]
+    fn test_list_file_removed_on_drop() {
+        let list_file = ListFile::new();
+        let path = list_file.0.clone();
+        assert!(path.starts_with(env::temp_dir()));
+
+        fs::write(&path, "ffconcat version 1.0\n").unwrap();
+        drop(list_file);
+        assert!(!path.exists());
+        // a list never written is no error
+        drop(ListFile::new());
+    }
+}
@@ -0,0 +1,57 @@
+use std::path::Path;
+
+/// Path of the directory receiving the segments of representation `id`, next to the `manifest`
+/// and named after it, e.g. `movie_0` for `movie.mpd`
+pub fn representation_dir(manifest: &str, id: usize) -> String {
+    let path = Path::new(manifest);
+    let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+
+    path.with_file_name(format!("{}_{}", stem, id)).to_string_lossy().to_string()
+}
+
+/// Output options of the `dash` muxer writing `manifest`, video streams go to a first
+/// adaptation set and the audio, if any, to a second one.
+///
+/// The muxer writes the segment timeline itself, out of the timestamps of the fragments.
+/// Segments are written to the [`representation_dir`] of their stream, which must exist.
+pub fn dash_args(manifest: &str, segment_duration: u32, with_audio: bool) -> Vec<String> {
+    let stem = Path::new(manifest).file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+    let adaptation_sets = match with_audio {
+        true => "id=0,streams=v id=1,streams=a",
+        false => "id=0,streams=v"
+    };
+
+    vec![
+        String::from("-f"), String::from("dash"),
+        String::from("-seg_duration"), segment_duration.to_string(),
+        String::from("-use_template"), String::from("1"),
+        String::from("-use_timeline"), String::from("1"),
+        String::from("-adaptation_sets"), adaptation_sets.to_string(),
+        String::from("-init_seg_name"), format!("{}_$RepresentationID$/init.mp4", stem),
+        String::from("-media_seg_name"), format!("{}_$RepresentationID$/segment_$Number%05d$.m4s", stem),
+        manifest.to_string()
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_representation_dir() {
+        assert_eq!(representation_dir("/videos/movie.mpd", 0), "/videos/movie_0");
+        assert_eq!(representation_dir("movie.mpd", 2), "movie_2");
+    }
+
+    #[test]
+    fn test_dash_args() {
+        assert_eq!(dash_args("/videos/movie.mpd", 6, true), vec![
+            "-f", "dash", "-seg_duration", "6", "-use_template", "1", "-use_timeline", "1",
+            "-adaptation_sets", "id=0,streams=v id=1,streams=a",
+            "-init_seg_name", "movie_$RepresentationID$/init.mp4",
+            "-media_seg_name", "movie_$RepresentationID$/segment_$Number%05d$.m4s",
+            "/videos/movie.mpd"
+        ]);
+        assert_eq!(dash_args("movie.mpd", 4, false)[9], "id=0,streams=v");
+    }
+}
@@ -0,0 +1,138 @@
+use log::{debug, error};
+use serde::Deserialize;
+use crate::atium::common::error::AtiumError;
+use crate::converter::model::{LoudnessMeasurement, LoudnessTarget};
+
+/// Statistics printed by the `loudnorm` filter with `print_format=json`
+#[derive(Deserialize)]
+struct LoudnormStats {
+    input_i: String,
+    input_tp: String,
+    input_lra: String,
+    input_thresh: String,
+    output_i: String,
+    output_tp: String,
+    output_lra: String,
+    output_thresh: String,
+    normalization_type: String,
+    target_offset: String
+}
+
+/// Measured (input) and achieved (output) loudness reported by a `loudnorm` run
+pub struct LoudnormReport {
+    pub input: LoudnessMeasurement,
+    pub output: LoudnessMeasurement,
+    pub linear: bool
+}
+
+fn parse_value(value: &str) -> Result<f64, AtiumError> {
+    // Silence is reported as `-inf`
+    value.trim().parse::<f64>()
+        .map_err(|_| AtiumError::encode(format!("Could not parse loudness value [{}]", value)))
+}
+
+/// Parses the JSON block that `loudnorm` prints on stderr at the end of the run
+pub fn parse_loudnorm_output(stderr: &str) -> Result<LoudnormReport, AtiumError> {
+    let start = stderr.rfind('{')
+        .ok_or_else(|| AtiumError::encode("Could not find loudness statistics"))?;
+    let end = stderr.rfind('}')
+        .filter(|end| *end > start)
+        .ok_or_else(|| AtiumError::encode("Could not find loudness statistics"))?;
+
+    let stats: LoudnormStats = serde_json::from_str(&stderr[start..=end])
+        .map_err(|err| {
+            error!("Error when parsing {}", err);
+            AtiumError::encode("Could not parse loudness statistics")
+        })?;
+
+    debug!("Loudness normalization type is [{}]", stats.normalization_type);
+
+    let target_offset = parse_value(&stats.target_offset)?;
+
+    Ok(LoudnormReport {
+        input: LoudnessMeasurement {
+            integrated: parse_value(&stats.input_i)?,
+            true_peak: parse_value(&stats.input_tp)?,
+            loudness_range: parse_value(&stats.input_lra)?,
+            threshold: parse_value(&stats.input_thresh)?,
+            target_offset
+        },
+        output: LoudnessMeasurement {
+            integrated: parse_value(&stats.output_i)?,
+            true_peak: parse_value(&stats.output_tp)?,
+            loudness_range: parse_value(&stats.output_lra)?,
+            threshold: parse_value(&stats.output_thresh)?,
+            target_offset
+        },
+        linear: stats.normalization_type.eq_ignore_ascii_case("linear")
+    })
+}
+
+/// The `loudnorm` filter of the analysis pass
+pub fn measure_filter(target: &LoudnessTarget) -> String {
+    format!(
+        "loudnorm=I={}:TP={}:LRA={}:print_format=json",
+        target.integrated, target.true_peak, target.loudness_range
+    )
+}
+
+/// The `loudnorm` filter applying a linear normalization based on the analysis pass
+pub fn normalize_filter(target: &LoudnessTarget, measured: &LoudnessMeasurement) -> String {
+    format!(
+        "loudnorm=I={}:TP={}:LRA={}:measured_I={}:measured_TP={}:measured_LRA={}:measured_thresh={}:offset={}:linear=true:print_format=json",
+        target.integrated, target.true_peak, target.loudness_range,
+        measured.integrated, measured.true_peak, measured.loudness_range,
+        measured.threshold, measured.target_offset
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STDERR: &str = r#"size=N/A time=00:00:20.00 bitrate=N/A speed= 105x
+video:0kB audio:7500kB subtitle:0kB other streams:0kB global headers:0kB muxing overhead: unknown
+[Parsed_loudnorm_0 @ 0x600000bc4000]
+{
+	"input_i" : "-27.61",
+	"input_tp" : "-4.47",
+	"input_lra" : "18.06",
+	"input_thresh" : "-39.20",
+	"output_i" : "-23.04",
+	"output_tp" : "-1.00",
+	"output_lra" : "7.00",
+	"output_thresh" : "-34.17",
+	"normalization_type" : "dynamic",
+	"target_offset" : "0.04"
+}
+"#;
+
+    #[test]
+    fn test_parse_loudnorm_output() {
+        let report = parse_loudnorm_output(STDERR).unwrap();
+
+        assert_eq!(report.input.integrated, -27.61);
+        assert_eq!(report.input.true_peak, -4.47);
+        assert_eq!(report.input.threshold, -39.2);
+        assert_eq!(report.output.integrated, -23.04);
+        assert_eq!(report.output.loudness_range, 7.0);
+        assert_eq!(report.input.target_offset, 0.04);
+        assert!(!report.linear);
+    }
+
+    #[test]
+    fn test_parse_missing_statistics() {
+        assert!(parse_loudnorm_output("Conversion failed!").is_err());
+    }
+
+    #[test]
+    fn test_normalize_filter() {
+        let target = LoudnessTarget { integrated: -23.0, true_peak: -1.0, loudness_range: 7.0 };
+        let measured = parse_loudnorm_output(STDERR).unwrap().input;
+
+        assert_eq!(
+            normalize_filter(&target, &measured),
+            "loudnorm=I=-23:TP=-1:LRA=7:measured_I=-27.61:measured_TP=-4.47:measured_LRA=18.06:measured_thresh=-39.2:offset=0.04:linear=true:print_format=json"
+        );
+    }
+}
@@ -1,2 +1,4 @@
 pub mod service;
-pub mod model;
\ No newline at end of file
+pub mod model;
+pub mod loudness;
+pub mod dash;
@@ -1,4 +1,8 @@
+use std::path::Path;
+use std::time::Duration;
+use crate::atium::common::error::AtiumError;
 use crate::atium::common::model::{ThumbnailRequest, ThumbnailResponse};
+use crate::atium::common::timestamp::Timestamp;
 
 /// The input file source type
 pub enum InputSourceType {
@@ -18,6 +22,7 @@ pub struct ConversionInput {
 /// * FullHd2k    -> 1080p - 2048x1080
 /// * UltraHd     -> 4k    - 3840x2160
 /// * FullUltraHd -> 8k    - 7680x4320
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum OutputResolution {
     Sd, Hd, FullHd, FullHd2k, UltraHd, FullUltraHd
 }
@@ -30,7 +35,7 @@ pub enum OutputResolution {
 /// * 2k   -> FULL-HD-2K
 /// * uhd  -> ULTRA-HD
 /// * 8k   -> 8K
-pub fn parse_resolution(resolution_string: &String) -> OutputResolution {
+pub fn parse_resolution(resolution_string: &str) -> OutputResolution {
     match resolution_string.to_lowercase().as_str() {
         "sd" => OutputResolution::Sd,
         "hd" => OutputResolution::Hd,
@@ -49,7 +54,7 @@ pub fn parse_resolution(resolution_string: &String) -> OutputResolution {
 /// * FullHd2k    -> 1080p - 2048x1080
 /// * UltraHd     -> 4k    - 3840x2160
 /// * FullUltraHd -> 8k    - 7680x4320
-pub fn get_width_height(resolution: OutputResolution) -> (i32,i32) {
+pub fn get_width_height(resolution: OutputResolution) -> (u32,u32) {
     match resolution {
         OutputResolution::Sd => (640, 480),
         OutputResolution::Hd => (1280,720),
@@ -60,16 +65,824 @@ pub fn get_width_height(resolution: OutputResolution) -> (i32,i32) {
     }
 }
 
-/// Output codec options
+/// Scaling modes, all of them keep the source display aspect ratio:
+/// * Fit  -> the output fits inside the requested resolution
+/// * Fill -> the output covers the requested resolution, exceeding it on one side
+/// * Pad  -> as Fit, then padded (letterbox/pillarbox) up to the requested resolution
+/// * Crop -> as Fill, then cropped down to the requested resolution
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScalingMode {
+    Fit, Fill, Pad, Crop
+}
+
+/// Returns a value of [`ScalingMode`] based on input:
+/// Valid inputs are:
+/// * fit   -> FIT
+/// * fill  -> FILL
+/// * pad   -> PAD
+/// * crop  -> CROP
+///
+/// Fails on any other input
+pub fn parse_scaling_mode(mode_string: &str) -> Result<ScalingMode, AtiumError> {
+    match mode_string.to_lowercase().as_str() {
+        "fit" => Ok(ScalingMode::Fit),
+        "fill" => Ok(ScalingMode::Fill),
+        "pad" => Ok(ScalingMode::Pad),
+        "crop" => Ok(ScalingMode::Crop),
+        _ => Err(AtiumError::InvalidRequest(format!("Scaling mode [{}] is not supported, expected fit/fill/pad/crop", mode_string)))
+    }
+}
+
+/// How the source rotation is handled:
+/// * Bake     -> frames are rotated, the output has no rotation metadata
+/// * Metadata -> frames are kept as stored and the rotation is written as a display matrix, needs ffmpeg 6 or later
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RotationMode {
+    Bake, Metadata
+}
+
+/// Returns a value of [`RotationMode`] based on input:
+/// Valid inputs are:
+/// * bake      -> BAKE
+/// * metadata  -> METADATA
+///
+/// Fails on any other input
+pub fn parse_rotation_mode(mode_string: &str) -> Result<RotationMode, AtiumError> {
+    match mode_string.to_lowercase().as_str() {
+        "bake" => Ok(RotationMode::Bake),
+        "metadata" => Ok(RotationMode::Metadata),
+        _ => Err(AtiumError::InvalidRequest(format!("Rotation mode [{}] is not supported, expected bake/metadata", mode_string)))
+    }
+}
+
+/// Output codec options:
+/// * H264    -> H.264/AVC through `libx264`
+/// * H265    -> H.265/HEVC through `libx265`
+/// * Vp9     -> VP9 through `libvpx-vp9`
+/// * Av1Aom  -> AV1 through `libaom-av1`
+/// * Av1Svt  -> AV1 through `libsvtav1`
+/// * ProRes  -> Apple ProRes 422 HQ through `prores_ks`
+/// * Copy    -> video stream is copied without re-encoding
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum OutputCodec {
-    H264
+    H264, H265, Vp9, Av1Aom, Av1Svt, ProRes, Copy
+}
+
+impl OutputCodec {
+    /// The `ffmpeg` encoder backing this codec, None when the stream is copied
+    pub fn encoder(&self) -> Option<&'static str> {
+        match self {
+            OutputCodec::H264 => Some("libx264"),
+            OutputCodec::H265 => Some("libx265"),
+            OutputCodec::Vp9 => Some("libvpx-vp9"),
+            OutputCodec::Av1Aom => Some("libaom-av1"),
+            OutputCodec::Av1Svt => Some("libsvtav1"),
+            OutputCodec::ProRes => Some("prores_ks"),
+            OutputCodec::Copy => None
+        }
+    }
+    /// The `ffmpeg` arguments selecting this codec for the video stream
+    pub fn codec_args(&self) -> Vec<String> {
+        let mut args = vec![
+            String::from("-c:v"),
+            self.encoder().unwrap_or("copy").to_string()
+        ];
+
+        // ProRes 422 HQ
+        if *self == OutputCodec::ProRes {
+            args.push(String::from("-profile:v"));
+            args.push(String::from("3"));
+        }
+
+        args
+    }
+    /// The `ffmpeg` arguments selecting this codec along with its rate control, preset and tune.
+    /// Fails when one of the options is not supported by the codec encoder.
+    pub fn encoding_args(&self, rate_control: Option<&RateControl>, preset: Option<&str>, tune: Option<&str>) -> Result<Vec<String>, AtiumError> {
+        let mut args = self.codec_args();
+
+        args.extend(self.rate_control_args(rate_control)?);
+        if let Some(preset) = preset {
+            args.extend(self.preset_args(preset)?);
+        }
+        if let Some(tune) = tune {
+            args.extend(self.tune_args(tune)?);
+        }
+
+        Ok(args)
+    }
+    /// The `ffmpeg` arguments running the given pass of a two-pass encoding,
+    /// `log_file` is the prefix of the statistics file shared by both passes.
+    /// Fails when the codec encoder does not support two-pass encoding.
+    pub fn pass_args(&self, pass: u8, log_file: &str) -> Result<Vec<String>, AtiumError> {
+        match self {
+            OutputCodec::H264 | OutputCodec::Vp9 | OutputCodec::Av1Aom => Ok(vec![
+                String::from("-pass"), pass.to_string(),
+                String::from("-passlogfile"), log_file.to_string()
+            ]),
+            // libx265 ignores `-pass`, its own parameters must be used
+            OutputCodec::H265 => Ok(vec![
+                String::from("-x265-params"), format!("pass={}:stats={}.log", pass, log_file)
+            ]),
+            _ => Err(AtiumError::InvalidRequest(format!("Codec [{:?}] does not support two-pass encoding", self)))
+        }
+    }
+    fn max_quality_value(&self) -> Option<u32> {
+        match self {
+            OutputCodec::H264 | OutputCodec::H265 => Some(51),
+            OutputCodec::Vp9 | OutputCodec::Av1Aom | OutputCodec::Av1Svt => Some(63),
+            OutputCodec::ProRes | OutputCodec::Copy => None
+        }
+    }
+    fn rate_control_args(&self, rate_control: Option<&RateControl>) -> Result<Vec<String>, AtiumError> {
+        // libvpx-vp9 and libaom-av1 need a zero bitrate to run in constant quality mode,
+        // otherwise they default to a very low bitrate target
+        let constant_quality_bitrate = match self {
+            OutputCodec::Vp9 | OutputCodec::Av1Aom => vec![String::from("-b:v"), String::from("0")],
+            _ => vec![]
+        };
+
+        let rate_control = match rate_control {
+            Some(rate_control) => rate_control,
+            None => return Ok(constant_quality_bitrate)
+        };
+
+        let max_quality_value = self.max_quality_value()
+            .ok_or_else(|| AtiumError::InvalidRequest(format!("Codec [{:?}] does not support rate control", self)))?;
+
+        match *rate_control {
+            RateControl::ConstantQuality(quality) => {
+                if quality > max_quality_value {
+                    return Err(AtiumError::InvalidRequest(format!("Codec [{:?}] quality must be between 0 and {}", self, max_quality_value)))
+                }
+                let mut args = vec![String::from("-crf"), quality.to_string()];
+                args.extend(constant_quality_bitrate);
+                Ok(args)
+            }
+            RateControl::AverageBitrate(bitrate) => {
+                if bitrate == 0 {
+                    return Err(AtiumError::InvalidRequest("Bitrate must be greater than 0".to_string()))
+                }
+                Ok(vec![String::from("-b:v"), format!("{}k", bitrate)])
+            }
+            RateControl::ConstrainedVbr { bitrate, max_rate, buffer_size } => {
+                if *self == OutputCodec::Av1Svt {
+                    return Err(AtiumError::InvalidRequest(format!("Codec [{:?}] does not support constrained VBR", self)))
+                }
+                if bitrate == 0 || buffer_size == 0 || max_rate < bitrate {
+                    return Err(AtiumError::InvalidRequest("Constrained VBR needs 0 < bitrate <= maxrate and a buffer size greater than 0".to_string()))
+                }
+                Ok(vec![
+                    String::from("-b:v"), format!("{}k", bitrate),
+                    String::from("-maxrate"), format!("{}k", max_rate),
+                    String::from("-bufsize"), format!("{}k", buffer_size)
+                ])
+            }
+        }
+    }
+    fn preset_args(&self, preset: &str) -> Result<Vec<String>, AtiumError> {
+        let numeric_preset = |max: u32| preset.parse::<u32>().ok().filter(|p| *p <= max);
+        let args = match self {
+            OutputCodec::H264 | OutputCodec::H265 if X26X_PRESETS.contains(&preset) =>
+                Some((String::from("-preset"), preset.to_string())),
+            OutputCodec::Vp9 if ["good", "best", "realtime"].contains(&preset) =>
+                Some((String::from("-deadline"), preset.to_string())),
+            OutputCodec::Av1Aom => numeric_preset(8)
+                .map(|p| (String::from("-cpu-used"), p.to_string())),
+            OutputCodec::Av1Svt => numeric_preset(13)
+                .map(|p| (String::from("-preset"), p.to_string())),
+            _ => None
+        };
+
+        args.map(|(name, value)| vec![name, value])
+            .ok_or_else(|| AtiumError::InvalidRequest(format!("Preset [{}] is not supported by codec [{:?}]", preset, self)))
+    }
+    fn tune_args(&self, tune: &str) -> Result<Vec<String>, AtiumError> {
+        let args = match self {
+            OutputCodec::H264 if X264_TUNES.contains(&tune) =>
+                Some(String::from("-tune")),
+            OutputCodec::H265 if X265_TUNES.contains(&tune) =>
+                Some(String::from("-tune")),
+            OutputCodec::Vp9 if ["default", "screen", "film"].contains(&tune) =>
+                Some(String::from("-tune-content")),
+            OutputCodec::Av1Aom if ["psnr", "ssim"].contains(&tune) =>
+                Some(String::from("-tune")),
+            _ => None
+        };
+
+        args.map(|name| vec![name, tune.to_string()])
+            .ok_or_else(|| AtiumError::InvalidRequest(format!("Tune [{}] is not supported by codec [{:?}]", tune, self)))
+    }
+}
+
+const X26X_PRESETS: [&str; 10] = [
+    "ultrafast", "superfast", "veryfast", "faster", "fast", "medium", "slow", "slower", "veryslow", "placebo"
+];
+const X264_TUNES: [&str; 8] = [
+    "film", "animation", "grain", "stillimage", "fastdecode", "zerolatency", "psnr", "ssim"
+];
+const X265_TUNES: [&str; 6] = [
+    "psnr", "ssim", "grain", "zerolatency", "fastdecode", "animation"
+];
+
+/// Rate control modes, bitrates are expressed in kbit/s:
+/// * ConstantQuality -> CRF for x264/x265/SVT-AV1, CQ for libvpx-vp9/libaom-av1
+/// * AverageBitrate  -> target average bitrate
+/// * ConstrainedVbr  -> average bitrate bounded by a maximum rate and a decoder buffer size
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RateControl {
+    ConstantQuality(u32),
+    AverageBitrate(u32),
+    ConstrainedVbr { bitrate: u32, max_rate: u32, buffer_size: u32 }
+}
+
+/// Returns an optional [`RateControl`] based on the given options:
+/// * crf                      -> ConstantQuality
+/// * bitrate                  -> AverageBitrate
+/// * bitrate, maxrate         -> ConstrainedVbr, with a buffer size of twice the maxrate
+/// * bitrate, maxrate, bufsize -> ConstrainedVbr
+///
+/// Fails when a constant quality is mixed with bitrate options
+pub fn parse_rate_control(crf: Option<u32>, bitrate: Option<u32>, max_rate: Option<u32>, buffer_size: Option<u32>) -> Result<Option<RateControl>, AtiumError> {
+    match (crf, bitrate, max_rate, buffer_size) {
+        (None, None, None, None) => Ok(None),
+        (Some(crf), None, None, None) => Ok(Some(RateControl::ConstantQuality(crf))),
+        (None, Some(bitrate), None, None) => Ok(Some(RateControl::AverageBitrate(bitrate))),
+        (None, Some(bitrate), Some(max_rate), buffer_size) => Ok(Some(RateControl::ConstrainedVbr {
+            bitrate,
+            max_rate,
+            buffer_size: buffer_size.unwrap_or(max_rate.saturating_mul(2))
+        })),
+        (Some(_), _, _, _) => Err(AtiumError::InvalidRequest("Constant quality cannot be combined with bitrate options".to_string())),
+        _ => Err(AtiumError::InvalidRequest("Maxrate and bufsize need a target bitrate".to_string()))
+    }
+}
+
+/// Returns a value of [`OutputCodec`] based on input:
+/// Valid inputs are:
+/// * h264, avc         -> H264
+/// * h265, hevc        -> H265
+/// * vp9               -> VP9
+/// * av1, av1-aom      -> AV1 (libaom)
+/// * av1-svt, svtav1   -> AV1 (SVT-AV1)
+/// * prores            -> ProRes
+/// * copy              -> Copy
+///
+/// Fails on any other input
+pub fn parse_codec(codec_string: &str) -> Result<OutputCodec, AtiumError> {
+    match codec_string.to_lowercase().as_str() {
+        "h264" | "avc" => Ok(OutputCodec::H264),
+        "h265" | "hevc" => Ok(OutputCodec::H265),
+        "vp9" => Ok(OutputCodec::Vp9),
+        "av1" | "av1-aom" => Ok(OutputCodec::Av1Aom),
+        "av1-svt" | "svtav1" => Ok(OutputCodec::Av1Svt),
+        "prores" => Ok(OutputCodec::ProRes),
+        "copy" => Ok(OutputCodec::Copy),
+        _ => Err(AtiumError::InvalidRequest(format!("Codec [{}] is not supported, expected h264/h265/vp9/av1/av1-svt/prores/copy", codec_string)))
+    }
+}
+
+/// Returns the [`OutputCodec`] producing a stream of the given `mediainfo` format,
+/// None if no codec matches
+pub fn codec_from_format(format: &str) -> Option<OutputCodec> {
+    match format {
+        "AVC" => Some(OutputCodec::H264),
+        "HEVC" => Some(OutputCodec::H265),
+        "VP9" => Some(OutputCodec::Vp9),
+        "AV1" => Some(OutputCodec::Av1Aom),
+        "ProRes" => Some(OutputCodec::ProRes),
+        _ => None
+    }
+}
+
+/// Returns the [`AudioCodec`] producing a stream of the given `mediainfo` format,
+/// None if no codec matches, e.g. for PCM
+pub fn audio_codec_from_format(format: &str) -> Option<AudioCodec> {
+    match format {
+        "AAC" => Some(AudioCodec::Aac),
+        "Opus" => Some(AudioCodec::Opus),
+        "MPEG Audio" => Some(AudioCodec::Mp3),
+        "FLAC" => Some(AudioCodec::Flac),
+        _ => None
+    }
+}
+
+/// Output container options:
+/// * Mp4   -> MPEG-4 Part 14
+/// * Mkv   -> Matroska
+/// * WebM  -> WebM
+/// * Mov   -> QuickTime
+/// * Ts    -> MPEG-2 Transport Stream
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputContainer {
+    Mp4, Mkv, WebM, Mov, Ts
+}
+
+impl OutputContainer {
+    /// The file extension of the container
+    pub fn extension(&self) -> &'static str {
+        match self {
+            OutputContainer::Mp4 => "mp4",
+            OutputContainer::Mkv => "mkv",
+            OutputContainer::WebM => "webm",
+            OutputContainer::Mov => "mov",
+            OutputContainer::Ts => "ts"
+        }
+    }
+    /// The `ffmpeg` muxer writing the container
+    pub fn muxer(&self) -> &'static str {
+        match self {
+            OutputContainer::Mp4 => "mp4",
+            OutputContainer::Mkv => "matroska",
+            OutputContainer::WebM => "webm",
+            OutputContainer::Mov => "mov",
+            OutputContainer::Ts => "mpegts"
+        }
+    }
+    /// Tells whether a stream encoded with `codec` can be muxed into the container.
+    /// [`OutputCodec::Copy`] is always accepted, since it depends on the source stream.
+    pub fn supports(&self, codec: OutputCodec) -> bool {
+        match (self, codec) {
+            (_, OutputCodec::Copy) => true,
+            (OutputContainer::Mkv, _) => true,
+            (OutputContainer::Mp4, codec) => codec != OutputCodec::ProRes,
+            (OutputContainer::WebM, codec) => matches!(codec, OutputCodec::Vp9 | OutputCodec::Av1Aom | OutputCodec::Av1Svt),
+            (OutputContainer::Mov, codec) => matches!(codec, OutputCodec::H264 | OutputCodec::H265 | OutputCodec::ProRes),
+            (OutputContainer::Ts, codec) => matches!(codec, OutputCodec::H264 | OutputCodec::H265)
+        }
+    }
+    /// Tells whether an audio stream encoded with `codec` can be muxed into the container.
+    /// [`AudioCodec::Copy`] and [`AudioCodec::None`] are always accepted.
+    pub fn supports_audio(&self, codec: AudioCodec) -> bool {
+        match (self, codec) {
+            (_, AudioCodec::Copy | AudioCodec::None) => true,
+            (OutputContainer::Mkv | OutputContainer::Mp4, _) => true,
+            (OutputContainer::WebM, codec) => codec == AudioCodec::Opus,
+            (OutputContainer::Mov | OutputContainer::Ts, codec) => matches!(codec, AudioCodec::Aac | AudioCodec::Mp3)
+        }
+    }
+}
+
+/// Returns a value of [`OutputContainer`] based on input:
+/// Valid inputs are:
+/// * mp4, m4v   -> MP4
+/// * mkv        -> MKV
+/// * webm       -> WEBM
+/// * mov        -> MOV
+/// * ts         -> TS
+///
+/// Fails on any other input
+pub fn parse_container(container_string: &str) -> Result<OutputContainer, AtiumError> {
+    match container_string.to_lowercase().as_str() {
+        "mp4" | "m4v" => Ok(OutputContainer::Mp4),
+        "mkv" => Ok(OutputContainer::Mkv),
+        "webm" => Ok(OutputContainer::WebM),
+        "mov" => Ok(OutputContainer::Mov),
+        "ts" => Ok(OutputContainer::Ts),
+        _ => Err(AtiumError::InvalidRequest(format!("Container [{}] is not supported, expected mp4/mkv/webm/mov/ts", container_string)))
+    }
+}
+
+/// Returns the requested [`OutputContainer`] or, when missing, the one matching the extension of `output_file`.
+/// MP4 is used when the output file has no extension.
+///
+/// Fails when the requested container, or the extension, is not supported
+pub fn output_container(container_string: Option<&str>, output_file: &str) -> Result<OutputContainer, AtiumError> {
+    container_string
+        .or_else(|| Path::new(output_file).extension().and_then(|e| e.to_str()))
+        .map(parse_container)
+        .unwrap_or(Ok(OutputContainer::Mp4))
+}
+
+/// Audio codec options:
+/// * Aac   -> AAC-LC through the native `aac` encoder
+/// * Opus  -> Opus through `libopus`
+/// * Mp3   -> MP3 through `libmp3lame`
+/// * Flac  -> FLAC through the native `flac` encoder
+/// * Copy  -> audio streams are copied without re-encoding
+/// * None  -> audio streams are dropped
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioCodec {
+    Aac, Opus, Mp3, Flac, Copy, None
+}
+
+impl AudioCodec {
+    /// The `ffmpeg` encoder backing this codec, None when the streams are copied or dropped
+    pub fn encoder(&self) -> Option<&'static str> {
+        match self {
+            AudioCodec::Aac => Some("aac"),
+            AudioCodec::Opus => Some("libopus"),
+            AudioCodec::Mp3 => Some("libmp3lame"),
+            AudioCodec::Flac => Some("flac"),
+            AudioCodec::Copy | AudioCodec::None => None
+        }
+    }
+    fn supports_sample_rate(&self, sample_rate: u32) -> bool {
+        match self {
+            AudioCodec::Opus => [8000, 12000, 16000, 24000, 48000].contains(&sample_rate),
+            AudioCodec::Mp3 => [8000, 11025, 12000, 16000, 22050, 24000, 32000, 44100, 48000].contains(&sample_rate),
+            _ => (8000..=192000).contains(&sample_rate)
+        }
+    }
+}
+
+/// Returns a value of [`AudioCodec`] based on input:
+/// Valid inputs are:
+/// * aac   -> AAC
+/// * opus  -> OPUS
+/// * mp3   -> MP3
+/// * flac  -> FLAC
+/// * copy  -> COPY
+/// * none  -> NONE
+///
+/// Fails on any other input
+pub fn parse_audio_codec(codec_string: &str) -> Result<AudioCodec, AtiumError> {
+    match codec_string.to_lowercase().as_str() {
+        "aac" => Ok(AudioCodec::Aac),
+        "opus" => Ok(AudioCodec::Opus),
+        "mp3" => Ok(AudioCodec::Mp3),
+        "flac" => Ok(AudioCodec::Flac),
+        "copy" => Ok(AudioCodec::Copy),
+        "none" => Ok(AudioCodec::None),
+        _ => Err(AtiumError::InvalidRequest(format!("Audio codec [{}] is not supported, expected aac/opus/mp3/flac/copy/none", codec_string)))
+    }
+}
+
+/// Audio channel layouts, sources with more channels are downmixed:
+/// * Mono        -> 1 channel
+/// * Stereo      -> 2 channels
+/// * Surround51  -> 6 channels
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AudioChannels {
+    Mono, Stereo, Surround51
+}
+
+impl AudioChannels {
+    /// The number of channels of the layout
+    pub fn count(&self) -> u32 {
+        match self {
+            AudioChannels::Mono => 1,
+            AudioChannels::Stereo => 2,
+            AudioChannels::Surround51 => 6
+        }
+    }
+}
+
+/// Returns a value of [`AudioChannels`] based on input:
+/// Valid inputs are:
+/// * mono         -> MONO
+/// * stereo       -> STEREO
+/// * 5.1          -> SURROUND-5.1
+///
+/// Fails on any other input
+pub fn parse_audio_channels(channels_string: &str) -> Result<AudioChannels, AtiumError> {
+    match channels_string.to_lowercase().as_str() {
+        "mono" => Ok(AudioChannels::Mono),
+        "stereo" => Ok(AudioChannels::Stereo),
+        "5.1" => Ok(AudioChannels::Surround51),
+        _ => Err(AtiumError::InvalidRequest(format!("Audio channel layout [{}] is not supported, expected mono/stereo/5.1", channels_string)))
+    }
+}
+
+/// Audio output options, bitrate is expressed in kbit/s and sample rate in Hz
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AudioOutput {
+    pub codec: AudioCodec,
+    pub bitrate: Option<u32>,
+    pub channels: Option<AudioChannels>,
+    pub sample_rate: Option<u32>
+}
+
+impl AudioOutput {
+    /// The `ffmpeg` arguments for the audio streams, `source_channels` is the number of channels
+    /// of the source: the output is never upmixed.
+    /// Fails when one of the options is not supported by the codec.
+    pub fn audio_args(&self, source_channels: Option<u32>) -> Result<Vec<String>, AtiumError> {
+        match self.codec {
+            AudioCodec::None => return Ok(vec![String::from("-an")]),
+            AudioCodec::Copy => {
+                if self.bitrate.is_some() || self.channels.is_some() || self.sample_rate.is_some() {
+                    return Err(AtiumError::InvalidRequest("Copied audio streams cannot be changed".to_string()))
+                }
+                return Ok(vec![String::from("-c:a"), String::from("copy")])
+            }
+            _ => {}
+        }
+
+        let mut args = vec![
+            String::from("-c:a"),
+            self.codec.encoder().unwrap_or("copy").to_string()
+        ];
+
+        if let Some(bitrate) = self.bitrate {
+            if self.codec == AudioCodec::Flac {
+                return Err(AtiumError::InvalidRequest("FLAC is lossless and does not take a bitrate".to_string()))
+            }
+            if bitrate == 0 {
+                return Err(AtiumError::InvalidRequest("Audio bitrate must be greater than 0".to_string()))
+            }
+            args.push(String::from("-b:a"));
+            args.push(format!("{}k", bitrate));
+        }
+        if let Some(channels) = self.channels {
+            let count = match source_channels {
+                Some(source_channels) if source_channels < channels.count() => source_channels,
+                _ => channels.count()
+            };
+            args.push(String::from("-ac"));
+            args.push(count.to_string());
+        }
+        if let Some(sample_rate) = self.sample_rate {
+            if !self.codec.supports_sample_rate(sample_rate) {
+                return Err(AtiumError::InvalidRequest(format!("Sample rate [{}] is not supported by codec [{:?}]", sample_rate, self.codec)))
+            }
+            args.push(String::from("-ar"));
+            args.push(sample_rate.to_string());
+        }
+
+        Ok(args)
+    }
+}
+
+/// Returns an [`AudioOutput`] when any of the audio options is given, the codec defaults to AAC.
+///
+/// Fails on an unknown codec or channel layout
+pub fn parse_audio_output(
+    codec: Option<&str>,
+    bitrate: Option<u32>,
+    channels: Option<&str>,
+    sample_rate: Option<u32>
+) -> Result<Option<AudioOutput>, AtiumError> {
+    if codec.is_none() && bitrate.is_none() && channels.is_none() && sample_rate.is_none() {
+        return Ok(None)
+    }
+
+    Ok(Some(AudioOutput {
+        codec: codec.map(parse_audio_codec).transpose()?.unwrap_or(AudioCodec::Aac),
+        bitrate,
+        channels: channels.map(parse_audio_channels).transpose()?,
+        sample_rate
+    }))
+}
+
+/// Loudness normalization target:
+/// * integrated      -> integrated loudness in LUFS, between -70 and -5
+/// * true_peak       -> maximum true peak in dBTP, between -9 and 0
+/// * loudness_range  -> loudness range in LU, between 1 and 50
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LoudnessTarget {
+    pub integrated: f64,
+    pub true_peak: f64,
+    pub loudness_range: f64
+}
+
+impl Default for LoudnessTarget {
+    /// EBU R128 target
+    fn default() -> Self {
+        LoudnessTarget { integrated: -23.0, true_peak: -1.0, loudness_range: 7.0 }
+    }
+}
+
+impl LoudnessTarget {
+    /// Fails when a value is out of the range accepted by the `loudnorm` filter
+    pub fn validate(&self) -> Result<(), AtiumError> {
+        if !(-70.0..=-5.0).contains(&self.integrated) {
+            return Err(AtiumError::InvalidRequest("Integrated loudness must be between -70 and -5 LUFS".to_string()))
+        }
+        if !(-9.0..=0.0).contains(&self.true_peak) {
+            return Err(AtiumError::InvalidRequest("True peak must be between -9 and 0 dBTP".to_string()))
+        }
+        if !(1.0..=50.0).contains(&self.loudness_range) {
+            return Err(AtiumError::InvalidRequest("Loudness range must be between 1 and 50 LU".to_string()))
+        }
+        Ok(())
+    }
+}
+
+/// Returns a [`LoudnessTarget`] when any of the loudness options is given,
+/// the missing values are taken from the EBU R128 target
+pub fn parse_loudness_target(
+    integrated: Option<f64>,
+    true_peak: Option<f64>,
+    loudness_range: Option<f64>
+) -> Option<LoudnessTarget> {
+    if integrated.is_none() && true_peak.is_none() && loudness_range.is_none() {
+        return None
+    }
+
+    let default = LoudnessTarget::default();
+    Some(LoudnessTarget {
+        integrated: integrated.unwrap_or(default.integrated),
+        true_peak: true_peak.unwrap_or(default.true_peak),
+        loudness_range: loudness_range.unwrap_or(default.loudness_range)
+    })
+}
+
+/// Loudness values measured on an audio stream
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LoudnessMeasurement {
+    /// Integrated loudness in LUFS
+    pub integrated: f64,
+    /// True peak in dBTP
+    pub true_peak: f64,
+    /// Loudness range in LU
+    pub loudness_range: f64,
+    /// Gating threshold in LUFS
+    pub threshold: f64,
+    /// Gain offset applied to reach the target, in LU
+    pub target_offset: f64
+}
+
+/// Loudness normalization outcome
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LoudnessReport {
+    /// Loudness of the source
+    pub measured: LoudnessMeasurement,
+    /// Loudness of the output
+    pub achieved: LoudnessMeasurement,
+    /// Whether the normalization was linear, `loudnorm` falls back to a
+    /// dynamic one when the target cannot be reached linearly
+    pub linear: bool
+}
+
+/// Portion of the source to convert, a missing bound defaults to the start or the end of the source.
+///
+/// With [`OutputCodec::Copy`] the streams are copied and the cut snaps to the keyframe preceding the start,
+/// other codecs cut on the exact frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ClipRange {
+    pub start: Option<Timestamp>,
+    pub end: Option<Timestamp>
+}
+
+impl ClipRange {
+    /// Resolves the range within a `duration` long source into its start and length,
+    /// timecodes need the source `frame_rate`.
+    ///
+    /// Fails when a bound exceeds the duration or when the start is not before the end
+    pub fn resolve(&self, duration: Duration, frame_rate: Option<f64>) -> Result<(Duration, Duration), AtiumError> {
+        let start = match &self.start {
+            Some(start) => start.resolve(duration, frame_rate)?,
+            None => Duration::ZERO
+        };
+        let end = match &self.end {
+            Some(end) => end.resolve(duration, frame_rate)?,
+            None => duration
+        };
+
+        if start >= duration {
+            return Err(AtiumError::InvalidRequest(format!(
+                "Start [{}] is past the end of the {:.3}s long source", Timestamp::Time(start), duration.as_secs_f64()
+            )))
+        }
+        if end > duration {
+            return Err(AtiumError::InvalidRequest(format!(
+                "End [{}] is past the end of the {:.3}s long source", Timestamp::Time(end), duration.as_secs_f64()
+            )))
+        }
+        if end <= start {
+            return Err(AtiumError::InvalidRequest(format!(
+                "End [{}] must be after start [{}]", Timestamp::Time(end), Timestamp::Time(start)
+            )))
+        }
+
+        Ok((start, end - start))
+    }
+}
+
+/// Returns a [`ClipRange`] when a start or an end is given, both being parsed as a [`Timestamp`]
+pub fn parse_clip_range(start: Option<&str>, end: Option<&str>) -> Result<Option<ClipRange>, AtiumError> {
+    if start.is_none() && end.is_none() {
+        return Ok(None)
+    }
+
+    Ok(Some(ClipRange {
+        start: start.map(str::parse).transpose()?,
+        end: end.map(str::parse).transpose()?
+    }))
+}
+
+/// Path of the `index`-th segment of `output_file`, numbered from 0 before the extension,
+/// e.g. `movie_002.mp4`
+pub fn segment_file(output_file: &str, index: usize) -> String {
+    segment_name(output_file, &format!("{:03}", index))
+}
+
+/// The `ffmpeg` pattern of the segment paths of `output_file`, e.g. `movie_%03d.mp4`
+pub fn segment_pattern(output_file: &str) -> String {
+    segment_name(output_file, "%03d")
+}
+
+fn segment_name(output_file: &str, number: &str) -> String {
+    let path = Path::new(output_file);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => path
+            .with_file_name(format!("{}_{}.{}", stem.to_string_lossy(), number, extension.to_string_lossy()))
+            .to_string_lossy()
+            .to_string(),
+        _ => format!("{}_{}", output_file, number)
+    }
+}
+
+/// A rung of an adaptive bitrate ladder, the bitrate is expressed in kbit/s
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LadderRung {
+    pub resolution: OutputResolution,
+    pub bitrate: u32
+}
+
+impl LadderRung {
+    /// Peak video bitrate allowed to the rung, 110% of its average bitrate
+    pub fn max_rate(&self) -> u32 {
+        self.bitrate.saturating_mul(11) / 10
+    }
+    /// The constrained VBR of the rung, bounded by [`LadderRung::max_rate`] with a two seconds buffer
+    pub fn rate_control(&self) -> RateControl {
+        RateControl::ConstrainedVbr {
+            bitrate: self.bitrate,
+            max_rate: self.max_rate(),
+            buffer_size: self.bitrate.saturating_mul(2)
+        }
+    }
+}
+
+/// Returns the ladder described by a comma separated list of `resolution:bitrate` rungs,
+/// e.g. `fhd:5000,hd:2800,sd:1200`. Resolutions are parsed as in [`parse_resolution`].
+///
+/// Fails when a rung has no valid bitrate
+pub fn parse_ladder(ladder_string: &str) -> Result<Vec<LadderRung>, AtiumError> {
+    ladder_string.split(',')
+        .map(str::trim)
+        .filter(|rung| !rung.is_empty())
+        .map(|rung| {
+            let (resolution, bitrate) = rung.split_once(':')
+                .ok_or_else(|| AtiumError::InvalidRequest(format!("Ladder rung [{}] must be written as resolution:bitrate", rung)))?;
+            let bitrate = bitrate.trim().parse::<u32>().ok()
+                .filter(|bitrate| *bitrate > 0)
+                .ok_or_else(|| AtiumError::InvalidRequest(format!("Ladder rung [{}] needs a bitrate greater than 0", rung)))?;
+
+            Ok(LadderRung { resolution: parse_resolution(resolution.trim()), bitrate })
+        })
+        .collect()
+}
+
+/// The default ladder, from 4k down to 480p.
+/// Rungs above the source resolution are skipped when packaging.
+pub fn default_ladder() -> Vec<LadderRung> {
+    vec![
+        LadderRung { resolution: OutputResolution::UltraHd, bitrate: 12000 },
+        LadderRung { resolution: OutputResolution::FullHd, bitrate: 5000 },
+        LadderRung { resolution: OutputResolution::Hd, bitrate: 2800 },
+        LadderRung { resolution: OutputResolution::Sd, bitrate: 1200 }
+    ]
+}
+
+/// MPEG-DASH output options: every rung of the ladder is encoded as a representation of
+/// fragmented MP4 segments, in a directory next to the manifest
+pub struct DashOutput {
+    pub ladder: Vec<LadderRung>,
+    /// Target duration of the segments in seconds
+    pub segment_duration: u32
+}
+
+/// A video representation of a DASH output
+#[derive(Clone, Debug, PartialEq)]
+pub struct Representation {
+    /// Id of the representation in the manifest
+    pub id: String,
+    pub rung: LadderRung,
+    /// Frame size in pixels
+    pub resolution: (u32, u32),
+    /// RFC 6381 codecs of the video stream, e.g. `avc1.64001f`
+    pub codecs: String,
+    /// Directory of the initialization and media segments
+    pub directory: String
 }
 
 /// Conversion output options
 pub struct ConversionOutput {
     pub file: String,
     pub resolution: OutputResolution,
+    pub scaling: ScalingMode,
+    pub rotation: RotationMode,
     pub codec: OutputCodec,
+    pub container: OutputContainer,
+    pub rate_control: Option<RateControl>,
+    pub preset: Option<String>,
+    pub tune: Option<String>,
+    /// Runs an analysis pass before the actual encoding, needs a bitrate based [`RateControl`]
+    pub two_pass: bool,
+    /// If None, audio is encoded with the `ffmpeg` defaults of the container
+    pub audio: Option<AudioOutput>,
+    /// If Some, audio is normalized to the given loudness
+    pub loudness: Option<LoudnessTarget>,
+    /// If Some, only the given range of the source is converted
+    pub range: Option<ClipRange>,
+    /// If Some, the output is cut into segments of the given duration, written to the [`segment_file`] paths
+    pub segment_duration: Option<Duration>,
+    /// If Some, `file` is the manifest of a DASH output encoding the ladder,
+    /// `resolution` and `rate_control` are then set by every rung
+    pub dash: Option<DashOutput>,
     pub thumbnail_request: Option<ThumbnailRequest>
 }
 
@@ -81,6 +894,211 @@ pub struct ConversionRequest {
 
 /// Conversion response containing the output filepath
 pub struct ConversionResponse {
+    /// The first segment when the output is segmented, the manifest of a DASH output
     pub output_file: String,
-    pub thumbnail_response: Option<ThumbnailResponse>
-}
\ No newline at end of file
+    /// Every segment in order, empty unless a segment duration was requested
+    pub segments: Vec<String>,
+    /// The video representations of a DASH output, empty otherwise
+    pub representations: Vec<Representation>,
+    pub thumbnail_response: Option<ThumbnailResponse>,
+    /// Some when loudness normalization was requested and the source has audio
+    pub loudness: Option<LoudnessReport>
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_container_supports_codec() {
+        assert!(OutputContainer::Mp4.supports(OutputCodec::H265));
+        assert!(OutputContainer::Mkv.supports(OutputCodec::ProRes));
+        assert!(OutputContainer::WebM.supports(OutputCodec::Vp9));
+        assert!(!OutputContainer::WebM.supports(OutputCodec::H264));
+        assert!(!OutputContainer::Ts.supports(OutputCodec::Av1Svt));
+        assert!(!OutputContainer::Mp4.supports(OutputCodec::ProRes));
+        assert!(OutputContainer::WebM.supports(OutputCodec::Copy));
+    }
+
+    #[test]
+    fn test_encoding_args() {
+        let args = OutputCodec::H264.encoding_args(Some(&RateControl::ConstantQuality(23)), Some("slow"), Some("film")).unwrap();
+        assert_eq!(args, vec!["-c:v", "libx264", "-crf", "23", "-preset", "slow", "-tune", "film"]);
+
+        let args = OutputCodec::Vp9.encoding_args(Some(&RateControl::ConstantQuality(31)), Some("good"), None).unwrap();
+        assert_eq!(args, vec!["-c:v", "libvpx-vp9", "-crf", "31", "-b:v", "0", "-deadline", "good"]);
+
+        let rate_control = RateControl::ConstrainedVbr { bitrate: 3000, max_rate: 4500, buffer_size: 9000 };
+        let args = OutputCodec::H265.encoding_args(Some(&rate_control), None, None).unwrap();
+        assert_eq!(args, vec!["-c:v", "libx265", "-b:v", "3000k", "-maxrate", "4500k", "-bufsize", "9000k"]);
+    }
+
+    #[test]
+    fn test_encoding_args_validation() {
+        assert!(OutputCodec::H264.encoding_args(Some(&RateControl::ConstantQuality(52)), None, None).is_err());
+        assert!(OutputCodec::Av1Svt.encoding_args(None, Some("14"), None).is_err());
+        assert!(OutputCodec::Vp9.encoding_args(None, Some("slow"), None).is_err());
+        assert!(OutputCodec::H265.encoding_args(None, None, Some("film")).is_err());
+        assert!(OutputCodec::Copy.encoding_args(Some(&RateControl::AverageBitrate(1000)), None, None).is_err());
+    }
+
+    #[test]
+    fn test_pass_args() {
+        assert_eq!(OutputCodec::H264.pass_args(1, "/tmp/log").unwrap(), vec!["-pass", "1", "-passlogfile", "/tmp/log"]);
+        assert_eq!(OutputCodec::H265.pass_args(2, "/tmp/log").unwrap(), vec!["-x265-params", "pass=2:stats=/tmp/log.log"]);
+        assert!(OutputCodec::Av1Svt.pass_args(1, "/tmp/log").is_err());
+        assert!(OutputCodec::Copy.pass_args(1, "/tmp/log").is_err());
+    }
+
+    #[test]
+    fn test_parse_rate_control() {
+        assert_eq!(parse_rate_control(None, None, None, None).unwrap(), None);
+        assert_eq!(parse_rate_control(Some(20), None, None, None).unwrap(), Some(RateControl::ConstantQuality(20)));
+        assert_eq!(
+            parse_rate_control(None, Some(2000), Some(3000), None).unwrap(),
+            Some(RateControl::ConstrainedVbr { bitrate: 2000, max_rate: 3000, buffer_size: 6000 })
+        );
+        assert!(parse_rate_control(Some(20), Some(2000), None, None).is_err());
+        assert!(parse_rate_control(None, None, Some(3000), None).is_err());
+    }
+
+    #[test]
+    fn test_audio_args() {
+        let audio = AudioOutput {
+            codec: AudioCodec::Aac,
+            bitrate: Some(128),
+            channels: Some(AudioChannels::Stereo),
+            sample_rate: Some(48000)
+        };
+        assert_eq!(audio.audio_args(Some(6)).unwrap(), vec!["-c:a", "aac", "-b:a", "128k", "-ac", "2", "-ar", "48000"]);
+
+        let audio = AudioOutput { codec: AudioCodec::Opus, bitrate: None, channels: Some(AudioChannels::Surround51), sample_rate: None };
+        // No upmix of a stereo source
+        assert_eq!(audio.audio_args(Some(2)).unwrap(), vec!["-c:a", "libopus", "-ac", "2"]);
+
+        let audio = AudioOutput { codec: AudioCodec::None, bitrate: None, channels: None, sample_rate: None };
+        assert_eq!(audio.audio_args(None).unwrap(), vec!["-an"]);
+    }
+
+    #[test]
+    fn test_audio_args_validation() {
+        let flac = AudioOutput { codec: AudioCodec::Flac, bitrate: Some(320), channels: None, sample_rate: None };
+        assert!(flac.audio_args(None).is_err());
+
+        let opus = AudioOutput { codec: AudioCodec::Opus, bitrate: None, channels: None, sample_rate: Some(44100) };
+        assert!(opus.audio_args(None).is_err());
+
+        let copy = AudioOutput { codec: AudioCodec::Copy, bitrate: Some(128), channels: None, sample_rate: None };
+        assert!(copy.audio_args(None).is_err());
+    }
+
+    #[test]
+    fn test_container_supports_audio() {
+        assert!(OutputContainer::WebM.supports_audio(AudioCodec::Opus));
+        assert!(!OutputContainer::WebM.supports_audio(AudioCodec::Aac));
+        assert!(!OutputContainer::Ts.supports_audio(AudioCodec::Flac));
+        assert!(OutputContainer::Mkv.supports_audio(AudioCodec::Flac));
+    }
+
+    #[test]
+    fn test_clip_range() {
+        let duration = Duration::from_secs(600);
+        let range = parse_clip_range(Some("00:01:30"), Some("00:02:45.5")).unwrap().unwrap();
+        assert_eq!(range.resolve(duration, None).unwrap(), (Duration::from_secs(90), Duration::from_millis(75_500)));
+
+        let open_end = parse_clip_range(Some("50%"), None).unwrap().unwrap();
+        assert_eq!(open_end.resolve(duration, None).unwrap(), (Duration::from_secs(300), Duration::from_secs(300)));
+
+        assert_eq!(parse_clip_range(None, None).unwrap(), None);
+        assert!(parse_clip_range(Some("soon"), None).is_err());
+    }
+
+    #[test]
+    fn test_clip_range_validation() {
+        let duration = Duration::from_secs(600);
+        let resolve = |start, end| parse_clip_range(start, end).unwrap().unwrap().resolve(duration, None);
+
+        assert!(resolve(Some("00:10:00"), None).is_err());
+        assert!(resolve(None, Some("00:10:01")).is_err());
+        assert!(resolve(Some("00:02:00"), Some("00:01:00")).is_err());
+        assert!(resolve(Some("00:01:00"), Some("00:01:00")).is_err());
+        assert!(resolve(None, Some("00:10:00")).is_ok());
+    }
+
+    #[test]
+    fn test_segment_file() {
+        assert_eq!(segment_file("/out/movie.mp4", 2), "/out/movie_002.mp4");
+        assert_eq!(segment_pattern("/out/movie.mp4"), "/out/movie_%03d.mp4");
+        assert_eq!(segment_pattern("movie"), "movie_%03d");
+    }
+
+    #[test]
+    fn test_parse_scaling_mode() {
+        assert_eq!(parse_scaling_mode("Crop").unwrap(), ScalingMode::Crop);
+        assert!(parse_scaling_mode("stretch").is_err());
+    }
+
+    #[test]
+    fn test_parse_rotation_mode() {
+        assert_eq!(parse_rotation_mode("METADATA").unwrap(), RotationMode::Metadata);
+        assert!(parse_rotation_mode("keep").is_err());
+    }
+
+    #[test]
+    fn test_parse_audio_output() {
+        assert_eq!(parse_audio_output(None, None, None, None).unwrap(), None);
+        assert_eq!(parse_audio_output(None, Some(96), Some("Mono"), None).unwrap(), Some(AudioOutput {
+            codec: AudioCodec::Aac,
+            bitrate: Some(96),
+            channels: Some(AudioChannels::Mono),
+            sample_rate: None
+        }));
+        assert!(parse_audio_output(Some("vorbis"), None, None, None).is_err());
+        assert!(parse_audio_output(None, None, Some("7.1"), None).is_err());
+    }
+
+    #[test]
+    fn test_parse_codec() {
+        assert_eq!(parse_codec("HEVC").unwrap(), OutputCodec::H265);
+        assert_eq!(parse_codec("svtav1").unwrap(), OutputCodec::Av1Svt);
+        assert!(parse_codec("h246").is_err());
+        assert!(parse_codec("").is_err());
+    }
+
+    #[test]
+    fn test_parse_container() {
+        assert_eq!(parse_container("MKV").unwrap(), OutputContainer::Mkv);
+        assert_eq!(parse_container("m4v").unwrap(), OutputContainer::Mp4);
+        assert!(parse_container("avi").is_err());
+    }
+
+    #[test]
+    fn test_output_container() {
+        assert_eq!(output_container(None, "/videos/movie.webm").unwrap(), OutputContainer::WebM);
+        assert_eq!(output_container(Some("mkv"), "/videos/movie.mp4").unwrap(), OutputContainer::Mkv);
+        assert_eq!(output_container(None, "/videos/movie").unwrap(), OutputContainer::Mp4);
+        assert!(output_container(None, "/videos/movie.avi").is_err());
+        assert!(output_container(Some("avi"), "/videos/movie.mp4").is_err());
+    }
+
+    #[test]
+    fn test_parse_ladder() {
+        let ladder = parse_ladder("fhd:5000, hd:2800,sd:1200,").unwrap();
+
+        assert_eq!(ladder, vec![
+            LadderRung { resolution: OutputResolution::FullHd, bitrate: 5000 },
+            LadderRung { resolution: OutputResolution::Hd, bitrate: 2800 },
+            LadderRung { resolution: OutputResolution::Sd, bitrate: 1200 }
+        ]);
+        assert!(parse_ladder("fhd").is_err());
+        assert!(parse_ladder("fhd:0").is_err());
+        assert!(parse_ladder("fhd:fast").is_err());
+    }
+
+    #[test]
+    fn test_rung_rate_control() {
+        let rung = LadderRung { resolution: OutputResolution::Hd, bitrate: 2800 };
+
+        assert_eq!(rung.rate_control(), RateControl::ConstrainedVbr { bitrate: 2800, max_rate: 3080, buffer_size: 5600 });
+    }
+}
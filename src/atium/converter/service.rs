@@ -1,194 +1,1196 @@
-use std::fs;
+use std::{env, fs};
+use std::path::Path;
+use std::process::Output;
+use std::time::Duration;
 
-use log::{debug, error, warn};
+use log::{debug, error, info, warn};
+use rand::Rng;
 use uuid::Uuid;
 
-use crate::{InfoExtractorRequest, MediaInfoExtractorService, ThumbnailRequest};
-use crate::atium::common::analysis_helper::{AnalysisOutput, compute_output_file, MediaInfoJsonLoader};
+use crate::atium::common::model::ThumbnailRequest;
+use crate::atium::common::analysis_helper::{AnalysisOutput, compute_output_file};
+use crate::atium::common::analyzer::{load_analyzer, MediaAnalyzer};
 
-use crate::atium::common::command_manager::CommandManager;
+use crate::atium::common::command_manager::{CommandManager, ExecutionControl};
 use crate::atium::common::error::AtiumError;
-use crate::atium::common::model::{InfoFormat, InfoOutputType, ThumbnailResponse};
+use crate::atium::common::model::ThumbnailResponse;
+use crate::atium::common::progress::{Progress, ProgressParser, ProgressStage};
 use crate::atium::common::thumbnail_service::FFMPEGThumbnailService;
-use crate::converter::model::{ConversionInput, ConversionRequest, ConversionResponse, get_width_height, InputSourceType, OutputResolution};
+use crate::atium::common::media::VideoTrack;
+use crate::atium::common::timestamp::seek_position;
+use crate::atium::packager::codecs::CodecProfile;
+use crate::converter::{dash, loudness};
+use crate::converter::model::{AudioChannels, AudioCodec, AudioOutput, codec_from_format, ConversionInput, ConversionOutput, ConversionRequest, ConversionResponse, DashOutput, get_width_height, InputSourceType, LadderRung, LoudnessMeasurement, LoudnessReport, LoudnessTarget, OutputCodec, OutputContainer, OutputResolution, RateControl, Representation, RotationMode, ScalingMode, segment_file, segment_pattern};
 
 
 pub struct FFMPEGConversionService {
-    command_manager: CommandManager
+    command_manager: CommandManager,
+    analyzer: Box<dyn MediaAnalyzer>
 }
 
-impl FFMPEGConversionService {
-    fn extract_info(&self, file: &String) -> Result<AnalysisOutput, AtiumError> {
-        let service = MediaInfoExtractorService::new()?;
-        let request = InfoExtractorRequest {
-            input: file.to_string(),
-            format: Some(InfoFormat::Json),
-            full: None,
-            output_file: None,
-            output_type: Some(InfoOutputType::Plain)
-        };
-
-        let response = service.get_info(request)?
-            .output
-            .content
-            .unwrap_or(String::from(""));
+/// What a successful encoding wrote: `output_file` is the segment pattern when the output is segmented
+struct EncodedOutput {
+    output_file: String,
+    segments: Vec<String>,
+    duration: Option<Duration>,
+    analysis_output: AnalysisOutput,
+    loudness: Option<LoudnessReport>,
+    representations: Vec<Representation>
+}
 
+/// A DASH output as it will be encoded, `directories` receive the segments of every stream
+struct DashPlan {
+    args: Vec<String>,
+    representations: Vec<Representation>,
+    directories: Vec<String>
+}
 
-        let media_info_loader = MediaInfoJsonLoader{};
-        match media_info_loader.load_json_from_string(&response) {
-            Ok(output) => {
-                debug!("Analysis done!");
-                Ok(output)
-            }
-            Err(err) => {
-                error!("Could not analyze input file: {}", err);
-                Err(err)
-            }
-        }
-    }
-    fn load_source_file(&self, source: ConversionInput) -> Result<String, &'static str> {
+impl FFMPEGConversionService {
+    fn load_source_file(&self, source: ConversionInput) -> Result<String, AtiumError> {
         match source.source_type {
             InputSourceType::Local => {
-                let uuid = Uuid::new_v4().to_string();
-                let mut new_path = String::from("/tmp/");
-                new_path.push_str(uuid.as_str());
-                new_path.push_str(".mp4");
+                let mut file_name = Uuid::new_v4().to_string();
+                // Keeping the real extension lets ffmpeg probe the copy correctly
+                if let Some(extension) = Path::new(&source.file_name).extension().and_then(|e| e.to_str()) {
+                    file_name.push('.');
+                    file_name.push_str(extension);
+                }
+                let new_path = env::temp_dir().join(file_name).to_string_lossy().to_string();
 
-                match fs::copy(source.file_name, new_path.clone()) {
+                match fs::copy(&source.file_name, new_path.clone()) {
                     Ok(_) => {
                         debug!("Successfully copied source file!");
                         Ok(new_path)
                     },
                     Err(err) => {
                         error!("Error when trying to copy input file: {}", err);
-                        Err("Error when trying to copy input file")
+                        Err(AtiumError::Input { message: format!("Could not copy input file [{}]", source.file_name), source: Some(err) })
                     }
                 }
             }
         }
     }
     fn cleanup_tmp_file(&self, tmp_filepath: String) {
-        match fs::remove_file(tmp_filepath) {
+        let removal = if Path::new(&tmp_filepath).is_dir() {
+            fs::remove_dir_all(tmp_filepath)
+        } else {
+            fs::remove_file(tmp_filepath)
+        };
+
+        match removal {
             Ok(_) => debug!("Temporary file removed successfully"),
             Err(err) => warn!("Temporary file not removed: {}", err)
         }
     }
-    fn compute_resolution(&self, resolution: OutputResolution, current_resolution: (String,String)) -> Result<(i32,i32), AtiumError> {
-        let (width, height) = get_width_height(resolution);
-        let current_width = current_resolution.0.parse::<i32>()
-            .map_err(|_| AtiumError::IOError("Could not convert to Integer".to_string()))?;
-        let current_height = current_resolution.1.parse::<i32>()
-            .map_err(|_| AtiumError::IOError("Could not convert to Integer".to_string()))?;
+    fn check_encoders(&self, output: &ConversionOutput) -> Result<(), AtiumError> {
+        let encoders: Vec<&str> = vec![
+            output.codec.encoder(),
+            output.audio.as_ref().and_then(|audio| audio.codec.encoder())
+        ].into_iter().flatten().collect();
 
-        let mut width = width;
-        let mut height = height;
-        if width > current_width {
-            width = current_width;
+        if encoders.is_empty() {
+            return Ok(())
         }
-        if height > current_height {
-            height = current_height;
+
+        let result = self.command_manager.execute_with_args(vec!["-hide_banner", "-encoders"])?;
+        let listing = self.command_manager.get_command_output_as_string(result.stdout)?;
+
+        for encoder in encoders {
+            if !is_encoder_listed(&listing, encoder) {
+                return Err(AtiumError::Dependency(format!("Encoder [{}] is not available in the local ffmpeg build", encoder)))
+            }
+            debug!("Encoder [{}] is available", encoder);
         }
 
-        Ok((width, height))
+        Ok(())
     }
-    fn build_args(&self, resolution: OutputResolution, analysis_output: AnalysisOutput, input_file_path: String, output_file: String) -> Result<Vec<String>, AtiumError> {
-        let curr_width = analysis_output.extract_field_from_track(1, &"Width".to_string())?;
-        let curr_height = analysis_output.extract_field_from_track(1, &"Height".to_string())?;
+    fn check_rotation(&self, output: &ConversionOutput) -> Result<(), AtiumError> {
+        if output.rotation != RotationMode::Metadata || output.codec == OutputCodec::Copy {
+            return Ok(())
+        }
 
-        let (width, height) = self.compute_resolution(resolution, (curr_width, curr_height))?;
+        let result = self.command_manager.execute_with_args(vec!["-hide_banner", "-h", "long"])?;
+        let listing = self.command_manager.get_command_output_as_string(result.stdout)?;
 
-        debug!("Requested resolution is [{}x{}]", width, height);
+        if !listing.contains("-display_rotation") {
+            return Err(AtiumError::Dependency("Keeping the rotation as metadata needs ffmpeg 6 or later, for its -display_rotation option".to_string()))
+        }
 
-        Ok(vec![
+        Ok(())
+    }
+    fn check_container(&self, codec: OutputCodec, container: OutputContainer) -> Result<(), AtiumError> {
+        if container.supports(codec) {
+            Ok(())
+        } else {
+            Err(AtiumError::InvalidRequest(format!("Codec [{:?}] cannot be muxed into a [{}] container", codec, container.extension())))
+        }
+    }
+    fn check_encoding(&self, output: &ConversionOutput) -> Result<(), AtiumError> {
+        output.codec.encoding_args(output.rate_control.as_ref(), output.preset.as_deref(), output.tune.as_deref())?;
+
+        if let Some(loudness) = &output.loudness {
+            loudness.validate()?;
+            if let Some(AudioCodec::Copy | AudioCodec::None) = output.audio.as_ref().map(|audio| audio.codec) {
+                return Err(AtiumError::InvalidRequest("Loudness normalization needs the audio to be encoded".to_string()))
+            }
+        }
+
+        if let Some(audio) = &output.audio {
+            if !output.container.supports_audio(audio.codec) {
+                return Err(AtiumError::InvalidRequest(format!("Audio codec [{:?}] cannot be muxed into a [{}] container", audio.codec, output.container.extension())))
+            }
+            audio.audio_args(None)?;
+        }
+
+        if output.two_pass {
+            match output.rate_control {
+                Some(RateControl::AverageBitrate(_)) | Some(RateControl::ConstrainedVbr { .. }) => {
+                    output.codec.pass_args(1, "")?;
+                }
+                _ => return Err(AtiumError::InvalidRequest("Two-pass encoding needs a target bitrate".to_string()))
+            }
+        }
+
+        if matches!(output.segment_duration, Some(duration) if duration.as_millis() == 0) {
+            return Err(AtiumError::InvalidRequest("Segment duration must be at least 1ms".to_string()))
+        }
+
+        Ok(())
+    }
+    fn input_args(&self, output: &ConversionOutput, analysis_output: &AnalysisOutput, input_file_path: String, trim: Option<(Duration, Duration)>) -> Result<Vec<String>, AtiumError> {
+        let keep_rotation = output.rotation == RotationMode::Metadata && output.codec != OutputCodec::Copy;
+        let mut args = Vec::new();
+
+        // Filters will then work on the stored frames, instead of the displayed ones
+        if keep_rotation {
+            args.push(String::from("-noautorotate"));
+            let rotation = rotation_degrees(&analysis_output.video_track()?);
+            if rotation != 0 {
+                debug!("Rotation of {} degrees kept as metadata", rotation);
+                args.extend(display_rotation_args(rotation));
+            }
+        }
+        if let Some((start, length)) = trim {
+            debug!("Converting [{}] from [{}]", seek_position(length), seek_position(start));
+            args.extend(trim_args(start, length));
+        }
+        args.push(String::from("-i"));
+        args.push(input_file_path);
+
+        Ok(args)
+    }
+    fn check_dash(&self, output: &ConversionOutput) -> Result<(), AtiumError> {
+        let dash = match &output.dash {
+            Some(dash) => dash,
+            None => return Ok(())
+        };
+
+        if !matches!(output.codec, OutputCodec::H264 | OutputCodec::H265) {
+            return Err(AtiumError::InvalidRequest(format!("Codec [{:?}] is not supported for DASH, use h264 or h265", output.codec)))
+        }
+        if output.container != OutputContainer::Mp4 {
+            return Err(AtiumError::InvalidRequest("DASH segments are fragmented MP4, the container must be mp4".to_string()))
+        }
+        if dash.ladder.is_empty() {
+            return Err(AtiumError::InvalidRequest("The ladder needs at least one rung".to_string()))
+        }
+        if dash.segment_duration == 0 {
+            return Err(AtiumError::InvalidRequest("Segment duration must be greater than 0".to_string()))
+        }
+        if output.rate_control.is_some() || output.two_pass {
+            return Err(AtiumError::InvalidRequest("The rate control of a DASH output is set by the ladder, two-pass is not supported".to_string()))
+        }
+        if output.segment_duration.is_some() {
+            return Err(AtiumError::InvalidRequest("A DASH output cannot be cut into segment files as well".to_string()))
+        }
+        if matches!(&output.thumbnail_request, Some(request) if request.input_file.is_none()) {
+            return Err(AtiumError::InvalidRequest("A DASH manifest cannot be a thumbnail source, give the thumbnail its own source".to_string()))
+        }
+        for rung in &dash.ladder {
+            output.codec.encoding_args(Some(&rung.rate_control()), output.preset.as_deref(), output.tune.as_deref())?;
+        }
+
+        Ok(())
+    }
+    fn build_args(&self, output: &ConversionOutput, analysis_output: &AnalysisOutput, input_file_path: String, trim: Option<(Duration, Duration)>, audio_filter: Option<String>) -> Result<Vec<String>, AtiumError> {
+        let mut args = self.input_args(output, analysis_output, input_file_path, trim)?;
+
+        if output.codec == OutputCodec::Copy {
+            debug!("Video stream will be copied, requested resolution is ignored");
+            let source_format = analysis_output.video_track()?.format.unwrap_or_default();
+            if let Some(source_codec) = codec_from_format(&source_format) {
+                self.check_container(source_codec, output.container)?;
+            }
+        } else {
+            let video_track = analysis_output.video_track()?;
+            let geometry = compute_video_geometry(output.resolution, output.scaling, output.rotation, &video_track)?;
+
+            debug!("Requested resolution is [{}x{}]", geometry.frame.0, geometry.frame.1);
+
+            args.push(String::from("-vf"));
+            args.push(geometry.to_filter());
+        }
+
+        args.extend(output.codec.encoding_args(output.rate_control.as_ref(), output.preset.as_deref(), output.tune.as_deref())?);
+
+        if let Some(audio) = &output.audio {
+            let audio_tracks = analysis_output.audio_tracks();
+            match audio_tracks.first() {
+                Some(audio_track) => args.extend(audio.audio_args(audio_track.channels)?),
+                None => debug!("Source has no audio, audio options are ignored")
+            }
+        }
+
+        args.extend(self.audio_filter_args(output, analysis_output, audio_filter));
+
+        Ok(args)
+    }
+    fn audio_filter_args(&self, output: &ConversionOutput, analysis_output: &AnalysisOutput, audio_filter: Option<String>) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(audio_filter) = audio_filter {
+            args.push(String::from("-af"));
+            args.push(audio_filter);
+            // loudnorm upsamples to 192kHz, the source sample rate is restored unless one is requested
+            if output.audio.as_ref().and_then(|audio| audio.sample_rate).is_none() {
+                let sample_rate = analysis_output.audio_tracks().first()
+                    .and_then(|audio_track| audio_track.sampling_rate)
+                    .unwrap_or(48000);
+                args.push(String::from("-ar"));
+                args.push(sample_rate.to_string());
+            }
+        }
+
+        args
+    }
+    /// Arguments of a DASH output, the source is decoded once and split into one scaled stream per
+    /// representation. The audio, if any, is encoded once as a representation of its own
+    fn build_dash_args(&self, output: &ConversionOutput, dash: &DashOutput, manifest: &str, analysis_output: &AnalysisOutput, mut args: Vec<String>, audio_filter: Option<String>) -> Result<DashPlan, AtiumError> {
+        let video_track = analysis_output.video_track()?;
+        let (variants, skipped) = plan_ladder(&dash.ladder, output.scaling, output.rotation, &video_track)?;
+        skipped.iter().for_each(|rung| info!("Rung [{:?}] at {} kbit/s is above the source resolution, skipped", rung.resolution, rung.bitrate));
+
+        let splits: String = (0..variants.len()).map(|index| format!("[s{}]", index)).collect();
+        let mut graph = vec![format!("[0:v]split={}{}", variants.len(), splits)];
+        graph.extend(variants.iter().enumerate()
+            .map(|(index, variant)| format!("[s{}]{}[v{}]", index, variant.geometry.to_filter(), index)));
+        args.push(String::from("-filter_complex"));
+        args.push(graph.join(";"));
+
+        let mut representations = Vec::new();
+        for (index, variant) in variants.into_iter().enumerate() {
+            let profile = CodecProfile::select(output.codec, variant.geometry.frame, video_track.frame_rate, variant.rung.max_rate())?;
+            let mut video_args = output.codec.encoding_args(Some(&variant.rung.rate_control()), output.preset.as_deref(), output.tune.as_deref())?;
+            video_args.extend(profile.encoder_args());
+            // Keyframes on segment boundaries keep the segments of every representation aligned
+            video_args.push(String::from("-force_key_frames"));
+            video_args.push(format!("expr:gte(t,n_forced*{})", dash.segment_duration));
+
+            args.push(String::from("-map"));
+            args.push(format!("[v{}]", index));
+            args.extend(stream_args(video_args, index));
+            representations.push(Representation {
+                id: index.to_string(),
+                rung: variant.rung,
+                resolution: variant.geometry.frame,
+                codecs: profile.codecs(),
+                directory: dash::representation_dir(manifest, index)
+            });
+        }
+
+        let audio = output.audio.clone().unwrap_or(AudioOutput {
+            codec: AudioCodec::Aac,
+            bitrate: Some(128),
+            channels: Some(AudioChannels::Stereo),
+            sample_rate: None
+        });
+        let audio_track = analysis_output.audio_tracks().into_iter().next()
+            .filter(|_| audio.codec != AudioCodec::None);
+        match &audio_track {
+            Some(audio_track) => {
+                args.push(String::from("-map"));
+                args.push(String::from("0:a:0"));
+                args.extend(audio.audio_args(audio_track.channels)?);
+                args.extend(self.audio_filter_args(output, analysis_output, audio_filter));
+            }
+            None => debug!("DASH output has no audio")
+        }
+
+        args.extend(dash::dash_args(manifest, dash.segment_duration, audio_track.is_some()));
+
+        let streams = representations.len() + usize::from(audio_track.is_some());
+        let directories = (0..streams).map(|index| dash::representation_dir(manifest, index)).collect();
+
+        Ok(DashPlan { args, representations, directories })
+    }
+    fn encode_dash(&self, manifest: &str, args: Vec<String>, directories: &[String], duration: Option<Duration>, control: &ExecutionControl, on_progress: &mut dyn FnMut(Progress)) -> Result<Output, AtiumError> {
+        // the dash muxer does not create the directories of the segments
+        for directory in directories {
+            fs::create_dir_all(directory)
+                .map_err(|err| AtiumError::Output { message: format!("Could not create the representation directory [{}]", directory), source: Some(err) })?;
+        }
+
+        debug!("Encoding {} DASH representations", directories.len());
+
+        match self.execute(args, ProgressStage::Encoding, duration, control, on_progress) {
+            Err(err @ (AtiumError::Timeout { .. } | AtiumError::Cancelled { .. })) => {
+                // the representations left behind by a killed ffmpeg are incomplete
+                directories.iter().for_each(|directory| self.cleanup_tmp_file(directory.clone()));
+                if Path::new(manifest).exists() {
+                    self.cleanup_tmp_file(manifest.to_string());
+                }
+                Err(err)
+            }
+            result => result
+        }
+    }
+    fn build_output_args(&self, output: &ConversionOutput, output_file: String, segment_list: Option<&str>) -> Vec<String> {
+        match (output.segment_duration, segment_list) {
+            (Some(segment_duration), Some(segment_list)) => segment_args(output.codec, output.container, segment_duration, output_file, segment_list),
+            _ => vec![
+                String::from("-f"),
+                output.container.muxer().to_string(),
+                output_file
+            ]
+        }
+    }
+    fn execute(&self, args: Vec<String>, stage: ProgressStage, duration: Option<Duration>, control: &ExecutionControl, on_progress: &mut dyn FnMut(Progress)) -> Result<Output, AtiumError> {
+        let mut progress_args = vec![String::from("-nostdin"), String::from("-progress"), String::from("pipe:1"), String::from("-nostats")];
+        progress_args.extend(args);
+
+        let mut parser = ProgressParser::new(stage, duration);
+        let mut on_line = |line: &str| {
+            if let Some(progress) = parser.parse_line(line) {
+                on_progress(progress);
+            }
+        };
+
+        let message = match stage {
+            ProgressStage::LoudnessAnalysis => "ffmpeg could not measure the loudness",
+            ProgressStage::FirstPass => "ffmpeg first pass failed",
+            ProgressStage::Encoding => "ffmpeg conversion failed"
+        };
+
+        self.command_manager.execute_streaming(progress_args.iter().map(AsRef::as_ref).collect(), control, &mut on_line)
+            .map_err(|err| err.into_encode(message))
+    }
+    fn encode_two_pass(&self, output: &ConversionOutput, built_args: Vec<String>, output_args: Vec<String>, duration: Option<Duration>, control: &ExecutionControl, on_progress: &mut dyn FnMut(Progress)) -> Result<Output, AtiumError> {
+        let pass_log_dir = env::temp_dir().join(Uuid::new_v4().to_string()).to_string_lossy().to_string();
+        fs::create_dir(&pass_log_dir)
+            .map_err(|err| AtiumError::Output { message: "Could not create pass log directory".to_string(), source: Some(err) })?;
+        let pass_log_file = format!("{}/ffmpeg2pass", pass_log_dir);
+
+        debug!("Running first pass, statistics written to [{}]", pass_log_dir);
+
+        let mut first_pass = vec![String::from("-y")];
+        first_pass.extend(built_args.clone());
+        first_pass.extend(output.codec.pass_args(1, &pass_log_file)?);
+        first_pass.extend(vec![
+            String::from("-an"),
+            String::from("-f"),
+            String::from("null"),
+            String::from("-")
+        ]);
+
+        let result = self.execute(first_pass, ProgressStage::FirstPass, duration, control, on_progress)
+            .and_then(|_| {
+                debug!("Running second pass");
+                let mut second_pass = built_args;
+                second_pass.extend(output.codec.pass_args(2, &pass_log_file)?);
+                second_pass.extend(output_args);
+                self.execute(second_pass, ProgressStage::Encoding, duration, control, on_progress)
+            });
+
+        self.cleanup_tmp_file(pass_log_dir);
+
+        result
+    }
+    fn measure_loudness(&self, target: &LoudnessTarget, input_file_path: &str, trim: Option<(Duration, Duration)>, duration: Option<Duration>, control: &ExecutionControl, on_progress: &mut dyn FnMut(Progress)) -> Result<LoudnessMeasurement, AtiumError> {
+        debug!("Measuring loudness of [{}]", input_file_path);
+
+        let mut args = vec![String::from("-hide_banner")];
+        if let Some((start, length)) = trim {
+            args.extend(trim_args(start, length));
+        }
+        args.extend(vec![
             String::from("-i"),
-            input_file_path,
-            String::from("-vf"),
-            format!("scale={}:{}", width, height),
-            output_file
-        ])
-    }
-    fn extract_thumbnail(&self, thumbnail_request: Option<ThumbnailRequest>, video_file: String, analysis_output: AnalysisOutput) -> Option<ThumbnailResponse> {
-        return match thumbnail_request {
+            input_file_path.to_string(),
+            String::from("-vn"),
+            String::from("-sn"),
+            String::from("-af"),
+            loudness::measure_filter(target),
+            String::from("-f"),
+            String::from("null"),
+            String::from("-")
+        ]);
+
+        let result = self.execute(args, ProgressStage::LoudnessAnalysis, duration, control, on_progress)?;
+        let stderr = String::from_utf8_lossy(&result.stderr);
+
+        Ok(loudness::parse_loudnorm_output(&stderr)?.input)
+    }
+    fn encode(&self, output: &ConversionOutput, input_file_path: &str, control: &ExecutionControl, on_progress: &mut dyn FnMut(Progress)) -> Result<EncodedOutput, AtiumError> {
+        let analysis_output = self.analyzer.analyze_with_control(input_file_path, control)?;
+        let trim = match &output.range {
+            Some(range) => {
+                let frame_rate = analysis_output.video_track().ok().and_then(|video| video.frame_rate);
+                Some(range.resolve(analysis_output.duration()?, frame_rate)?)
+            }
+            None => None
+        };
+        let duration = match trim {
+            Some((_, length)) => Some(length),
+            None => analysis_output.duration().ok()
+        };
+
+        let measured_loudness = match &output.loudness {
+            Some(target) if !analysis_output.audio_tracks().is_empty() => {
+                let measured = self.measure_loudness(target, input_file_path, trim, duration, control, on_progress)?;
+                if measured.integrated.is_finite() {
+                    Some((target, measured))
+                } else {
+                    warn!("Source audio is silent, loudness normalization is skipped");
+                    None
+                }
+            }
+            Some(_) => {
+                debug!("Source has no audio, loudness normalization is skipped");
+                None
+            }
+            None => None
+        };
+
+        let audio_filter = measured_loudness.map(|(target, measured)| loudness::normalize_filter(target, &measured));
+        if let Some(dash) = &output.dash {
+            let manifest = compute_output_file(&output.file, "mpd")?;
+            let input_args = self.input_args(output, &analysis_output, input_file_path.to_string(), trim)?;
+            let plan = self.build_dash_args(output, dash, &manifest, &analysis_output, input_args, audio_filter)?;
+
+            debug!("Converting file at path [{}] to DASH", input_file_path);
+
+            let result = self.encode_dash(&manifest, plan.args, &plan.directories, duration, control, on_progress)?;
+            let loudness_report = self.loudness_report(measured_loudness.map(|(_, measured)| measured), &result)?;
+
+            return Ok(EncodedOutput { output_file: manifest, segments: Vec::new(), duration, analysis_output, loudness: loudness_report, representations: plan.representations })
+        }
+
+        let segment_base = output.segment_duration.map(|_| fresh_segment_base(&output.file));
+        // the segment muxer lists the segments it completes, the only reliable record of this run
+        let segment_list = segment_base.as_ref()
+            .map(|_| env::temp_dir().join(format!("{}.txt", Uuid::new_v4())).to_string_lossy().to_string());
+        let output_file = match &segment_base {
+            Some(base) => segment_pattern(base),
+            None => compute_output_file(&output.file, output.container.extension())?
+        };
+        let built_args = self.build_args(
+            output,
+            &analysis_output,
+            input_file_path.to_string(),
+            trim,
+            audio_filter)?;
+
+        debug!("Converting file at path [{}]", input_file_path);
+
+        let output_args = self.build_output_args(output, output_file.clone(), segment_list.as_deref());
+        let result = if output.two_pass {
+            self.encode_two_pass(output, built_args, output_args, duration, control, on_progress)
+        } else {
+            let mut args = built_args;
+            args.extend(output_args);
+            self.execute(args, ProgressStage::Encoding, duration, control, on_progress)
+        };
+        let segments = match &segment_list {
+            Some(list) => {
+                let segments = read_segment_list(list, &output_file);
+                if Path::new(list).exists() {
+                    self.cleanup_tmp_file(list.to_string());
+                }
+                segments
+            }
+            None => Ok(Vec::new())
+        };
+        let result = match result {
+            Ok(result) => result,
+            Err(err @ (AtiumError::Timeout { .. } | AtiumError::Cancelled { .. })) => {
+                // the output left behind by a killed ffmpeg is incomplete
+                match segment_base {
+                    Some(base) => {
+                        let mut segments = segments.unwrap_or_default();
+                        // the segment being written when ffmpeg was killed is not listed yet
+                        segments.push(segment_file(&base, segments.len()));
+                        segments.into_iter()
+                            .filter(|segment| Path::new(segment).exists())
+                            .for_each(|segment| self.cleanup_tmp_file(segment));
+                    }
+                    None if Path::new(&output_file).exists() => self.cleanup_tmp_file(output_file),
+                    None => {}
+                }
+                return Err(err)
+            }
+            Err(err) => return Err(err)
+        };
+        let segments = segments?;
+
+        let loudness_report = self.loudness_report(measured_loudness.map(|(_, measured)| measured), &result)?;
+
+        Ok(EncodedOutput { output_file, segments, duration, analysis_output, loudness: loudness_report, representations: Vec::new() })
+    }
+    fn loudness_report(&self, measured_loudness: Option<LoudnessMeasurement>, result: &Output) -> Result<Option<LoudnessReport>, AtiumError> {
+        match measured_loudness {
+            Some(measured) => {
+                let stderr = String::from_utf8_lossy(&result.stderr);
+                let achieved = loudness::parse_loudnorm_output(&stderr)?;
+                debug!("Loudness normalized from [{}] to [{}] LUFS", measured.integrated, achieved.output.integrated);
+                Ok(Some(LoudnessReport { measured, achieved: achieved.output, linear: achieved.linear }))
+            }
+            None => Ok(None)
+        }
+    }
+    fn extract_thumbnail(&self, thumbnail_request: Option<ThumbnailRequest>, video_file: String, duration: Option<Duration>, analysis_output: AnalysisOutput) -> Result<Option<ThumbnailResponse>, AtiumError> {
+        match thumbnail_request {
             None => {
                 debug!("Thumbnail extraction not requested");
-                None
+                Ok(None)
             },
             Some(req) => {
-                match FFMPEGThumbnailService::new() {
-                    Ok(service) => {
-                        let duration = analysis_output
-                            .extract_field_from_track(0, &"Duration".to_string())
-                            .unwrap_or("1.0".to_string());
-                        let input_file =
-                        if req.input_file.is_none() {
-                                Some(video_file)
-                            } else {
-                                req.input_file
-                            };
-                        let request = ThumbnailRequest {
-                            timestamp: req.timestamp,
-                            input_file,
-                            output_file: req.output_file,
-                            input_duration: Some(duration)
-                        };
-
-                        match service.extract_thumbnail(request) {
-                            Ok(response) => Some(response),
-                            Err(err) => {
-                                error!("An error occurred when extracting thumbnail [{}]", err);
-                                None
-                            }
-                        }
+                let service = FFMPEGThumbnailService::new()?;
+                let input_file =
+                if req.input_file.is_none() {
+                        Some(video_file)
+                    } else {
+                        req.input_file
+                    };
+                let request = ThumbnailRequest {
+                    selection: req.selection,
+                    input_file,
+                    output_file: req.output_file,
+                    image: req.image,
+                    input_duration: duration,
+                    input_frame_rate: analysis_output.video_track().ok().and_then(|video| video.frame_rate)
+                };
+
+                match service.extract_thumbnail(request) {
+                    Ok(response) => Ok(Some(response)),
+                    Err(err) => {
+                        error!("An error occurred when extracting thumbnail [{}]", err);
+                        Ok(None)
                     }
-                    Err(err) => panic!("{}", err)
                 }
             }
         }
     }
     /// Converts a media info and returns a [`ConversionResponse`]
     pub fn convert(&self, conversion_request: ConversionRequest) -> Result<ConversionResponse, AtiumError> {
-        let input_file_path = self.load_source_file(conversion_request.input)
-            .map_err(|err_msg| AtiumError::ConversionError(err_msg.to_string()))?;
+        self.convert_with_progress(conversion_request, |_| {})
+    }
+    /// Converts a media info like [`FFMPEGConversionService::convert`], reporting the progress
+    /// of every `ffmpeg` run to `on_progress`.
+    ///
+    /// The callback runs on the calling thread, a [`std::sync::mpsc::Sender`] can be moved into it
+    /// to consume the updates elsewhere.
+    pub fn convert_with_progress<F: FnMut(Progress)>(&self, conversion_request: ConversionRequest, on_progress: F) -> Result<ConversionResponse, AtiumError> {
+        self.convert_with_control(conversion_request, &ExecutionControl::default(), on_progress)
+    }
+    /// Converts a media info like [`FFMPEGConversionService::convert_with_progress`], every external
+    /// command is killed when it runs longer than the timeout of `control` or when its token is cancelled.
+    ///
+    /// The temporary copy of the source is removed in any case, a partially written output as well.
+    pub fn convert_with_control<F: FnMut(Progress)>(&self, conversion_request: ConversionRequest, control: &ExecutionControl, mut on_progress: F) -> Result<ConversionResponse, AtiumError> {
+        self.check_container(conversion_request.output.codec, conversion_request.output.container)?;
+        self.check_encoders(&conversion_request.output)?;
+        self.check_rotation(&conversion_request.output)?;
+        self.check_encoding(&conversion_request.output)?;
+        self.check_dash(&conversion_request.output)?;
 
-        let analysis_output = self.extract_info(&input_file_path)?;
+        let input_file_path = self.load_source_file(conversion_request.input)?;
 
-        let output_file = compute_output_file(&conversion_request.output.file, "mp4")?;
-        let built_args = self.build_args(
-            conversion_request.output.resolution,
-            analysis_output.clone(),
-            input_file_path.clone(),
-            output_file.clone())
-            .map_err(|err_msg| AtiumError::ConversionError(err_msg.to_string()))?;
+        let result = self.encode(&conversion_request.output, &input_file_path, control, &mut on_progress);
 
-        debug!("Converting file at path [{}]", input_file_path);
-
-        match self.command_manager.execute_with_args(built_args.iter().map(AsRef::as_ref).collect()) {
-            Ok(result) => {
-                if !result.status.success() {
-                    self.command_manager.print_command_output(result.stderr)?;
-                    return Err(AtiumError::ConversionError("Execution of command returned ERROR".to_string()))
-                }
+        self.cleanup_tmp_file(input_file_path);
 
-                self.cleanup_tmp_file(input_file_path);
+        let EncodedOutput { output_file, segments, duration, analysis_output, loudness, representations } = result?;
 
-                debug!("Conversion done!");
+        debug!("Conversion done!");
 
-                Ok(ConversionResponse {
-                    output_file: output_file.clone(),
-                    thumbnail_response: self.extract_thumbnail(conversion_request.output.thumbnail_request, output_file, analysis_output)
-                })
-            }
-            Err(_) => {
-                self.cleanup_tmp_file(input_file_path);
-                Err(AtiumError::ConversionError("conversion command execution failed".to_string()))
+        let (output_file, segments, duration) = match conversion_request.output.segment_duration {
+            Some(_) => {
+                debug!("Output cut into {} segments", segments.len());
+                let first = segments.first().cloned().unwrap_or(output_file);
+                // the thumbnail service probes the first segment, whose length depends on the keyframes when copied
+                (first, segments, None)
             }
-        }
+            None => (output_file, Vec::new(), duration)
+        };
+
+        Ok(ConversionResponse {
+            output_file: output_file.clone(),
+            segments,
+            representations,
+            thumbnail_response: self.extract_thumbnail(conversion_request.output.thumbnail_request, output_file, duration, analysis_output)?,
+            loudness
+        })
     }
     /// Constructs a new instance of [`FFMPEGConversionService`]
     pub fn new() -> Result<Self, AtiumError> {
         let command_manager =
             CommandManager::new("ffmpeg".to_string(), vec!["-version"])?;
+        let analyzer = load_analyzer()?;
+
+        Ok(Self { command_manager, analyzer })
+    }
+}
+
+/// Result of the scaling computation: the size the source is scaled to
+/// and the size of the output frame, after padding or cropping
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct ScaleGeometry {
+    pub(crate) scaled: (u32, u32),
+    pub(crate) frame: (u32, u32)
+}
+
+impl ScaleGeometry {
+    pub(crate) fn to_filter(&self) -> String {
+        let (width, height) = self.scaled;
+        let (frame_width, frame_height) = self.frame;
+        // Sizes are computed on square pixels
+        let mut filter = format!("scale={}:{},setsar=1", width, height);
+
+        if (frame_width, frame_height) != (width, height) {
+            if frame_width > width || frame_height > height {
+                filter.push_str(&format!(",pad={}:{}:(ow-iw)/2:(oh-ih)/2", frame_width, frame_height));
+            } else {
+                filter.push_str(&format!(",crop={}:{}", frame_width, frame_height));
+            }
+        }
+
+        filter
+    }
+}
+
+/// Rounds to the nearest even number, as required by most encoders
+fn even(value: f64) -> u32 {
+    (((value / 2.0).round() as u32) * 2).max(2)
+}
+
+/// Rounds down to an even number
+fn even_floor(value: u32) -> u32 {
+    (value / 2 * 2).max(2)
+}
+
+/// Returns the source rotation in degrees, normalised to 0, 90, 180 or 270
+fn rotation_degrees(video_track: &VideoTrack) -> u32 {
+    let rotation = video_track.rotation.unwrap_or(0.0).rem_euclid(360.0);
+
+    ((rotation / 90.0).round() as u32 % 4) * 90
+}
+
+/// Returns the size of the source as it is displayed, taking into account
+/// the display aspect ratio and the rotation
+pub(crate) fn compute_display_size(video_track: &VideoTrack) -> Result<(u32, u32), AtiumError> {
+    let width = video_track.width
+        .ok_or_else(|| AtiumError::probe("Could not extract video width"))?;
+    let height = video_track.height
+        .ok_or_else(|| AtiumError::probe("Could not extract video height"))?;
+
+    let display_width = match (video_track.display_aspect_ratio, video_track.pixel_aspect_ratio) {
+        (Some(dar), _) if dar > 0.0 => (height as f64 * dar).round() as u32,
+        (None, Some(par)) if par > 0.0 => (width as f64 * par).round() as u32,
+        _ => width
+    };
+
+    match rotation_degrees(video_track) {
+        90 | 270 => Ok((height, display_width)),
+        _ => Ok((display_width, height))
+    }
+}
+
+/// Computes the output geometry of a video track.
+///
+/// The geometry is computed on the displayed frame: when the rotation is kept
+/// as metadata the filters work on the stored frame, so sizes are swapped back.
+pub(crate) fn compute_video_geometry(resolution: OutputResolution, mode: ScalingMode, rotation_mode: RotationMode, video_track: &VideoTrack) -> Result<ScaleGeometry, AtiumError> {
+    let display_size = compute_display_size(video_track)?;
+    let geometry = compute_resolution(resolution, mode, display_size);
+
+    debug!("Source display size is [{}x{}]", display_size.0, display_size.1);
+
+    match (rotation_mode, rotation_degrees(video_track)) {
+        (RotationMode::Metadata, 90 | 270) => Ok(ScaleGeometry {
+            scaled: (geometry.scaled.1, geometry.scaled.0),
+            frame: (geometry.frame.1, geometry.frame.0)
+        }),
+        _ => Ok(geometry)
+    }
+}
+
+/// Returns the box of the requested resolution, turned to portrait for portrait sources
+fn resolution_box(resolution: OutputResolution, display_size: (u32, u32)) -> (u32, u32) {
+    match get_width_height(resolution) {
+        (width, height) if display_size.1 > display_size.0 => (height, width),
+        size => size
+    }
+}
+/// Returns true when fitting the source into the requested resolution would need an upscale,
+/// which [`compute_resolution`] avoids by clamping to the source size
+pub(crate) fn exceeds_source(resolution: OutputResolution, display_size: (u32, u32)) -> bool {
+    let (box_width, box_height) = resolution_box(resolution, display_size);
+
+    box_width > display_size.0 && box_height > display_size.1
+}
+/// Computes the output geometry for the requested resolution without upscaling the source:
+/// the requested box follows the source orientation, it is clamped to the source display size
+/// and the scale factor never exceeds 1
+fn compute_resolution(resolution: OutputResolution, mode: ScalingMode, display_size: (u32, u32)) -> ScaleGeometry {
+    let (box_width, box_height) = resolution_box(resolution, display_size);
+    let box_width = box_width.min(display_size.0);
+    let box_height = box_height.min(display_size.1);
+    let (source_width, source_height) = (display_size.0 as f64, display_size.1 as f64);
+
+    let width_factor = box_width as f64 / source_width;
+    let height_factor = box_height as f64 / source_height;
+    let factor = match mode {
+        ScalingMode::Fit | ScalingMode::Pad => width_factor.min(height_factor),
+        ScalingMode::Fill | ScalingMode::Crop => width_factor.max(height_factor)
+    }.min(1.0);
+
+    // rounding to the nearest even size would upscale an odd source by a pixel
+    let scaled = (
+        even(source_width * factor).min(even_floor(display_size.0)),
+        even(source_height * factor).min(even_floor(display_size.1))
+    );
+    let frame_box = (even_floor(box_width), even_floor(box_height));
+
+    match mode {
+        ScalingMode::Fit | ScalingMode::Fill => ScaleGeometry { scaled, frame: scaled },
+        ScalingMode::Pad => ScaleGeometry {
+            scaled: (scaled.0.min(frame_box.0), scaled.1.min(frame_box.1)),
+            frame: frame_box
+        },
+        ScalingMode::Crop => ScaleGeometry {
+            scaled: (scaled.0.max(frame_box.0), scaled.1.max(frame_box.1)),
+            frame: frame_box
+        }
+    }
+}
+
+/// Input options attaching a display matrix rotating the stored frames by `rotation` degrees clockwise,
+/// the encoded stream carries it to the output. The legacy `rotate` tag is ignored by current muxers
+fn display_rotation_args(rotation: u32) -> Vec<String> {
+    vec![
+        String::from("-display_rotation:v:0"),
+        // the display matrix angle is counter-clockwise
+        ((360 - rotation) % 360).to_string()
+    ]
+}
+
+/// Input options reading `length` of the source from `start`, seeking before the input is
+/// frame accurate when encoding and keyframe aligned when copying streams
+fn trim_args(start: Duration, length: Duration) -> Vec<String> {
+    vec![
+        String::from("-ss"),
+        seek_position(start),
+        String::from("-t"),
+        seek_position(length)
+    ]
+}
+
+/// Output options cutting the output into `segment_duration` long segments written to `pattern`,
+/// the names of the completed segments are listed in `segment_list`.
+/// Encoded segments start on a forced keyframe to be cut exactly, copied ones start on the next source keyframe.
+fn segment_args(codec: OutputCodec, container: OutputContainer, segment_duration: Duration, pattern: String, segment_list: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    if codec != OutputCodec::Copy {
+        args.push(String::from("-force_key_frames"));
+        args.push(format!("expr:gte(t,n_forced*{})", seek_position(segment_duration)));
+    }
+    args.extend(vec![
+        String::from("-f"),
+        String::from("segment"),
+        String::from("-segment_format"),
+        container.muxer().to_string(),
+        String::from("-segment_time"),
+        seek_position(segment_duration),
+        String::from("-reset_timestamps"),
+        String::from("1"),
+        String::from("-segment_list"),
+        segment_list.to_string(),
+        String::from("-segment_list_type"),
+        String::from("flat"),
+        pattern
+    ]);
+
+    args
+}
+
+/// The base name of the segments of `output_file`, a fresh one when any of its segments already exists
+/// like [`compute_output_file`] does for a single output
+fn fresh_segment_base(output_file: &str) -> String {
+    let path = Path::new(output_file);
+    let mut base = output_file.to_string();
+    let mut rng = rand::thread_rng();
+    while segments_exist(&base) {
+        let stem = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+        let name = match path.extension() {
+            Some(extension) => format!("{}-{}.{}", stem, rng.gen_range(0..10000), extension.to_string_lossy()),
+            None => format!("{}-{}", stem, rng.gen_range(0..10000))
+        };
+        base = path.with_file_name(name).to_string_lossy().to_string();
+    }
+
+    base
+}
+
+/// Whether the directory of `output_file` holds a file named like one of its segments, whatever its number
+fn segments_exist(output_file: &str) -> bool {
+    let directory = Path::new(output_file).parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+
+    fs::read_dir(directory)
+        .map(|entries| entries
+            .filter_map(Result::ok)
+            .any(|entry| is_segment_name(&entry.file_name().to_string_lossy(), output_file)))
+        .unwrap_or(false)
+}
+
+/// Whether `file_name` matches the [`segment_pattern`] of `output_file`, e.g. `movie_007.mp4` or `movie_1234.mp4`
+fn is_segment_name(file_name: &str, output_file: &str) -> bool {
+    let pattern = segment_pattern(output_file);
+    let pattern_name = Path::new(&pattern).file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+    let (prefix, suffix) = pattern_name.split_once("%03d").unwrap_or((&pattern_name, ""));
+
+    file_name.strip_prefix(prefix)
+        .and_then(|rest| rest.strip_suffix(suffix))
+        .map(|number| !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()))
+        .unwrap_or(false)
+}
+
+/// The segments listed in the flat `segment_list` written by the segment muxer, in order
+fn read_segment_list(segment_list: &str, pattern: &str) -> Result<Vec<String>, AtiumError> {
+    let listing = fs::read_to_string(segment_list)
+        .map_err(|err| AtiumError::Output { message: "Could not read the segment list".to_string(), source: Some(err) })?;
+
+    Ok(segment_list_entries(&listing, pattern))
+}
+
+/// The paths of the segments named in `listing`, the muxer only writes their file names
+/// which belong to the directory of `pattern`
+fn segment_list_entries(listing: &str, pattern: &str) -> Vec<String> {
+    listing.lines()
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| Path::new(pattern).with_file_name(entry).to_string_lossy().to_string())
+        .collect()
+}
+
+/// A rung of the ladder as it will be encoded
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct PlannedVariant {
+    pub(crate) name: String,
+    pub(crate) rung: LadderRung,
+    pub(crate) geometry: ScaleGeometry
+}
+
+/// Computes the variants of the ladder, skipping the rungs that would upscale the source.
+/// When every rung is above the source, the lowest bitrate one is kept at the source size.
+///
+/// Fails when two rungs end up with the same size and bitrate
+pub(crate) fn plan_ladder(ladder: &[LadderRung], scaling: ScalingMode, rotation: RotationMode, video_track: &VideoTrack) -> Result<(Vec<PlannedVariant>, Vec<LadderRung>), AtiumError> {
+    let display_size = compute_display_size(video_track)?;
+    let (mut kept, mut skipped): (Vec<LadderRung>, Vec<LadderRung>) = ladder.iter()
+        .partition(|rung| !exceeds_source(rung.resolution, display_size));
+
+    if kept.is_empty() {
+        if let Some(index) = (0..skipped.len()).min_by_key(|index| skipped[*index].bitrate) {
+            warn!("Every rung is above the source resolution, the lowest one is kept at the source size");
+            kept.push(skipped.remove(index));
+        }
+    }
+
+    let mut variants: Vec<PlannedVariant> = Vec::new();
+    for rung in kept {
+        let geometry = compute_video_geometry(rung.resolution, scaling, rotation, video_track)?;
+        let name = format!("{}p_{}k", geometry.frame.0.min(geometry.frame.1), rung.bitrate);
+        if variants.iter().any(|variant| variant.name == name) {
+            return Err(AtiumError::InvalidRequest(format!("The ladder holds the variant [{}] twice", name)))
+        }
+        variants.push(PlannedVariant { name, rung, geometry });
+    }
 
-        Ok(Self { command_manager })
+    Ok((variants, skipped))
+}
+
+/// Binds encoder options to the output video stream `index`,
+/// `args` is a list of option and value pairs
+pub(crate) fn stream_args(args: Vec<String>, index: usize) -> Vec<String> {
+    args.chunks(2)
+        .flat_map(|pair| {
+            let option = match pair[0].strip_suffix(":v") {
+                Some(option) => format!("{}:v:{}", option, index),
+                None => format!("{}:v:{}", pair[0], index)
+            };
+            std::iter::once(option).chain(pair.iter().skip(1).cloned())
+        })
+        .collect()
+}
+
+/// Tells whether `encoder` appears in the listing printed by `ffmpeg -encoders`
+pub(crate) fn is_encoder_listed(listing: &str, encoder: &str) -> bool {
+    listing.lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .any(|name| name == encoder)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use crate::atium::common::analysis_helper::MediaInfoJsonLoader;
+    use super::*;
+
+    const ENCODERS: &str = "Encoders:
+ V..... = Video
+ A..... = Audio
+ ------
+ V....D libx264              libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 (codec h264)
+ V....D libx264rgb           libx264 H.264 / AVC / MPEG-4 AVC / MPEG-4 part 10 RGB (codec h264)
+ V....D libvpx-vp9           libvpx VP9 (codec vp9)
+ A....D aac                  AAC (Advanced Audio Coding)";
+
+    fn video_track(width: u32, height: u32, display_aspect_ratio: Option<f64>, rotation: Option<f64>) -> VideoTrack {
+        VideoTrack {
+            width: Some(width),
+            height: Some(height),
+            display_aspect_ratio,
+            rotation,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_display_size() {
+        assert_eq!(compute_display_size(&video_track(1920, 800, Some(2.4), None)).unwrap(), (1920, 800));
+        // Anamorphic DVD
+        assert_eq!(compute_display_size(&video_track(720, 576, Some(1.778), None)).unwrap(), (1024, 576));
+        assert_eq!(compute_display_size(&video_track(1920, 1080, Some(1.778), Some(90.0))).unwrap(), (1080, 1920));
+    }
+
+    #[test]
+    fn test_scope_source_to_hd() {
+        let fit = compute_resolution(OutputResolution::Hd, ScalingMode::Fit, (1920, 800));
+        assert_eq!(fit, ScaleGeometry { scaled: (1280, 534), frame: (1280, 534) });
+
+        let pad = compute_resolution(OutputResolution::Hd, ScalingMode::Pad, (1920, 800));
+        assert_eq!(pad, ScaleGeometry { scaled: (1280, 534), frame: (1280, 720) });
+        assert_eq!(pad.to_filter(), "scale=1280:534,setsar=1,pad=1280:720:(ow-iw)/2:(oh-ih)/2");
+
+        let fill = compute_resolution(OutputResolution::Hd, ScalingMode::Fill, (1920, 800));
+        assert_eq!(fill, ScaleGeometry { scaled: (1728, 720), frame: (1728, 720) });
+
+        let crop = compute_resolution(OutputResolution::Hd, ScalingMode::Crop, (1920, 800));
+        assert_eq!(crop.to_filter(), "scale=1728:720,setsar=1,crop=1280:720");
+    }
+
+    #[test]
+    fn test_portrait_source_gets_portrait_box() {
+        let fit = compute_resolution(OutputResolution::Hd, ScalingMode::Fit, (1080, 1920));
+        assert_eq!(fit.frame, (720, 1280));
+    }
+
+    fn load_rotated_fixture() -> VideoTrack {
+        let json_loader = MediaInfoJsonLoader{};
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/info_rotated.json");
+
+        json_loader.load_json_from_file(&d.to_str().unwrap().to_string()).unwrap()
+            .video_track().unwrap()
+    }
+
+    #[test]
+    fn test_rotation_baked_into_pixels() {
+        let video_track = load_rotated_fixture();
+        assert_eq!(rotation_degrees(&video_track), 90);
+
+        let geometry = compute_video_geometry(OutputResolution::Hd, ScalingMode::Fit, RotationMode::Bake, &video_track).unwrap();
+        assert_eq!(geometry.frame, (720, 1280));
+    }
+
+    #[test]
+    fn test_rotation_kept_as_metadata() {
+        let video_track = load_rotated_fixture();
+
+        let geometry = compute_video_geometry(OutputResolution::Hd, ScalingMode::Fit, RotationMode::Metadata, &video_track).unwrap();
+        assert_eq!(geometry.frame, (1280, 720));
+        assert_eq!(geometry.to_filter(), "scale=1280:720,setsar=1");
+    }
+
+    #[test]
+    fn test_display_rotation_args() {
+        assert_eq!(display_rotation_args(90), vec!["-display_rotation:v:0", "270"]);
+        assert_eq!(display_rotation_args(270), vec!["-display_rotation:v:0", "90"]);
+        assert_eq!(display_rotation_args(180), vec!["-display_rotation:v:0", "180"]);
+    }
+
+    #[test]
+    fn test_unrotated_fixture() {
+        let json_loader = MediaInfoJsonLoader{};
+        let mut d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        d.push("resources/test/info.json");
+        let video_track = json_loader.load_json_from_file(&d.to_str().unwrap().to_string()).unwrap()
+            .video_track().unwrap();
+
+        let geometry = compute_video_geometry(OutputResolution::Hd, ScalingMode::Fit, RotationMode::Metadata, &video_track).unwrap();
+        assert_eq!(geometry.frame, (1280, 720));
+    }
+
+    #[test]
+    fn test_rotation_degrees_normalised() {
+        let mut video_track = VideoTrack::default();
+        assert_eq!(rotation_degrees(&video_track), 0);
+
+        video_track.rotation = Some(-90.0);
+        assert_eq!(rotation_degrees(&video_track), 270);
+    }
+
+    #[test]
+    fn test_no_upscaling() {
+        let fit = compute_resolution(OutputResolution::FullHd, ScalingMode::Pad, (641, 361));
+        assert_eq!(fit, ScaleGeometry { scaled: (640, 360), frame: (640, 360) });
+        assert_eq!(fit.to_filter(), "scale=640:360,setsar=1");
+
+        for mode in [ScalingMode::Fit, ScalingMode::Fill] {
+            let geometry = compute_resolution(OutputResolution::FullHd, mode, (641, 361));
+            assert_eq!(geometry, ScaleGeometry { scaled: (640, 360), frame: (640, 360) });
+        }
+    }
+
+    #[test]
+    fn test_exceeds_source() {
+        assert!(exceeds_source(OutputResolution::FullHd, (1280, 720)));
+        assert!(!exceeds_source(OutputResolution::FullHd, (1920, 800)));
+        assert!(!exceeds_source(OutputResolution::Hd, (1280, 720)));
+        // the box follows the source orientation
+        assert!(!exceeds_source(OutputResolution::Hd, (720, 1280)));
+    }
+
+    #[test]
+    fn test_trim_args() {
+        assert_eq!(trim_args(Duration::from_secs(90), Duration::from_millis(75_500)), vec!["-ss", "90.000000", "-t", "75.500000"]);
+    }
+
+    #[test]
+    fn test_segment_args() {
+        let encoded = segment_args(OutputCodec::H264, OutputContainer::Mp4, Duration::from_secs(600), "out_%03d.mp4".to_string(), "/tmp/list.txt");
+        assert_eq!(encoded, vec![
+            "-force_key_frames", "expr:gte(t,n_forced*600.000000)",
+            "-f", "segment", "-segment_format", "mp4", "-segment_time", "600.000000", "-reset_timestamps", "1",
+            "-segment_list", "/tmp/list.txt", "-segment_list_type", "flat", "out_%03d.mp4"
+        ]);
+
+        let copied = segment_args(OutputCodec::Copy, OutputContainer::Mkv, Duration::from_secs(600), "out_%03d.mkv".to_string(), "/tmp/list.txt");
+        assert_eq!(copied[..4], ["-f", "segment", "-segment_format", "matroska"]);
+    }
+
+    #[test]
+    fn test_segment_list_entries() {
+        assert_eq!(
+            segment_list_entries("movie_000.mp4\nmovie_001.mp4\n", "/videos/movie_%03d.mp4"),
+            vec!["/videos/movie_000.mp4", "/videos/movie_001.mp4"]
+        );
+        assert_eq!(segment_list_entries("out_000.mkv\n", "out_%03d.mkv"), vec!["out_000.mkv"]);
+        assert!(segment_list_entries("", "out_%03d.mkv").is_empty());
+    }
+
+    #[test]
+    fn test_fresh_segment_base() {
+        let dir = env::temp_dir().join(Uuid::new_v4().to_string());
+        fs::create_dir(&dir).unwrap();
+        let output = dir.join("movie.mp4").to_string_lossy().to_string();
+        assert_eq!(fresh_segment_base(&output), output);
+
+        fs::write(segment_file(&output, 0), b"").unwrap();
+        let base = fresh_segment_base(&output);
+        assert_ne!(base, output);
+        assert!(Path::new(&base).file_name().unwrap().to_string_lossy().starts_with("movie-"));
+        assert!(!Path::new(&segment_file(&base, 0)).exists());
+
+        fs::remove_file(segment_file(&output, 0)).unwrap();
+        fs::write(segment_file(&output, 3), b"").unwrap();
+        assert_ne!(fresh_segment_base(&output), output);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_is_segment_name() {
+        assert!(is_segment_name("movie_000.mp4", "/videos/movie.mp4"));
+        assert!(is_segment_name("movie_1234.mp4", "/videos/movie.mp4"));
+        assert!(is_segment_name("movie_007", "movie"));
+        assert!(!is_segment_name("movie.mp4", "/videos/movie.mp4"));
+        assert!(!is_segment_name("movie_.mp4", "/videos/movie.mp4"));
+        assert!(!is_segment_name("movie_00a.mp4", "/videos/movie.mp4"));
+        assert!(!is_segment_name("movie_001.mkv", "/videos/movie.mp4"));
+        assert!(!is_segment_name("other_001.mp4", "/videos/movie.mp4"));
+    }
+
+    fn rung(resolution: OutputResolution, bitrate: u32) -> LadderRung {
+        LadderRung { resolution, bitrate }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_plan_ladder_skips_upscaling_rungs() {
+        let ladder = vec![
+            rung(OutputResolution::UltraHd, 12000),
+            rung(OutputResolution::FullHd, 5000),
+            rung(OutputResolution::Hd, 2800),
+            rung(OutputResolution::Sd, 1200)
+        ];
+
+        let (variants, skipped) = plan_ladder(&ladder, ScalingMode::Fit, RotationMode::Bake, &video_track(1280, 720, None, None)).unwrap();
+
+        assert_eq!(skipped, ladder[..2].to_vec());
+        assert_eq!(variants.len(), 2);
+        assert_eq!(variants[0].name, "720p_2800k");
+        assert_eq!(variants[0].geometry.frame, (1280, 720));
+        assert_eq!(variants[1].name, "360p_1200k");
+        assert_eq!(variants[1].geometry.frame, (640, 360));
+    }
+
+    #[test]
+    fn test_plan_ladder_keeps_lowest_rung() {
+        let ladder = vec![rung(OutputResolution::Hd, 2800), rung(OutputResolution::Sd, 1200)];
+
+        let (variants, skipped) = plan_ladder(&ladder, ScalingMode::Fit, RotationMode::Bake, &video_track(480, 270, None, None)).unwrap();
+
+        assert_eq!(skipped, vec![rung(OutputResolution::Hd, 2800)]);
+        assert_eq!(variants[0].name, "270p_1200k");
+        assert_eq!(variants[0].geometry.frame, (480, 270));
+    }
+
+    #[test]
+    fn test_plan_ladder_rejects_duplicates() {
+        let ladder = vec![rung(OutputResolution::Hd, 2800), rung(OutputResolution::Hd, 2800)];
+
+        assert!(plan_ladder(&ladder, ScalingMode::Fit, RotationMode::Bake, &video_track(1920, 1080, None, None)).is_err());
+    }
+
+    #[test]
+    fn test_stream_args() {
+        let args = vec!["-c:v", "libx264", "-b:v", "2800k", "-maxrate", "3080k", "-x265-params", "scenecut=0"]
+            .into_iter().map(String::from).collect();
+
+        assert_eq!(stream_args(args, 1), vec![
+            "-c:v:1", "libx264", "-b:v:1", "2800k", "-maxrate:v:1", "3080k", "-x265-params:v:1", "scenecut=0"
+        ]);
+    }
+
+    #[test]
+    fn test_encoder_listed() {
+        assert!(is_encoder_listed(ENCODERS, "libx264"));
+        assert!(is_encoder_listed(ENCODERS, "libvpx-vp9"));
+    }
+
+    #[test]
+    fn test_encoder_not_listed() {
+        assert!(!is_encoder_listed(ENCODERS, "libx265"));
+        assert!(!is_encoder_listed(ENCODERS, "libsvtav1"));
+    }
+}
@@ -1,2 +1,5 @@
 pub mod converter;
-pub mod common;
\ No newline at end of file
+pub mod common;
+pub mod batch;
+pub mod packager;
+pub mod concat;
@@ -0,0 +1,163 @@
+use crate::atium::common::error::AtiumError;
+use crate::converter::model::OutputCodec;
+
+/// RFC 6381 codecs of an AAC-LC audio stream
+pub const AAC_LC_CODECS: &str = "mp4a.40.2";
+
+/// Frame rate assumed for level selection when the source one is unknown
+const DEFAULT_FRAME_RATE: f64 = 30.0;
+
+/// H.264 levels as (level, MaxMBPS, MaxFS, MaxBR in kbit/s for the Baseline/Main profiles)
+const H264_LEVELS: [(u32, u64, u64, u64); 12] = [
+    (30, 40500, 1620, 10000),
+    (31, 108000, 3600, 14000),
+    (32, 216000, 5120, 20000),
+    (40, 245760, 8192, 20000),
+    (41, 245760, 8192, 50000),
+    (42, 522240, 8704, 50000),
+    (50, 589824, 22080, 135000),
+    (51, 983040, 36864, 240000),
+    (52, 2073600, 36864, 240000),
+    (60, 4177920, 139264, 240000),
+    (61, 8355840, 139264, 480000),
+    (62, 16711680, 139264, 800000)
+];
+
+/// HEVC Main tier levels as (level, MaxLumaPs, MaxLumaSr, MaxBR in kbit/s)
+const HEVC_LEVELS: [(u32, u64, u64, u64); 10] = [
+    (30, 552960, 16588800, 6000),
+    (31, 983040, 33177600, 10000),
+    (40, 2228224, 66846720, 12000),
+    (41, 2228224, 133693440, 20000),
+    (50, 8912896, 267386880, 25000),
+    (51, 8912896, 534773760, 40000),
+    (52, 8912896, 1069547520, 60000),
+    (60, 35651584, 1069547520, 60000),
+    (61, 35651584, 2139095040, 120000),
+    (62, 35651584, 4278190080, 240000)
+];
+
+/// The profile and level a rendition is encoded with, so that its
+/// RFC 6381 codecs can be announced by manifests and playlists.
+///
+/// H.264 renditions use the High profile, HEVC ones the Main profile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CodecProfile {
+    pub codec: OutputCodec,
+    /// Level multiplied by 10, e.g. 31 for level 3.1
+    pub level: u32
+}
+
+impl CodecProfile {
+    /// Selects the lowest level able to decode a `size` video at `frame_rate` and `max_rate` kbit/s.
+    ///
+    /// Fails when the codec is neither H.264 nor HEVC, or when no level fits
+    pub fn select(codec: OutputCodec, size: (u32, u32), frame_rate: Option<f64>, max_rate: u32) -> Result<Self, AtiumError> {
+        let frame_rate = frame_rate.filter(|rate| *rate > 0.0).unwrap_or(DEFAULT_FRAME_RATE);
+        let (width, height) = (size.0 as u64, size.1 as u64);
+        let max_rate = max_rate as u64;
+
+        let level = match codec {
+            OutputCodec::H264 => {
+                let frame_size = macroblocks(width) * macroblocks(height);
+                let macroblock_rate = (frame_size as f64 * frame_rate).ceil() as u64;
+                let longest_side = macroblocks(width.max(height));
+                // the High profile allows 25% more bitrate
+                H264_LEVELS.iter()
+                    .find(|(_, max_mbps, max_fs, max_br)| frame_size <= *max_fs
+                        && macroblock_rate <= *max_mbps
+                        && longest_side * longest_side <= max_fs * 8
+                        && max_rate * 4 <= max_br * 5)
+                    .map(|(level, ..)| *level)
+            }
+            OutputCodec::H265 => {
+                let picture_size = width * height;
+                let sample_rate = (picture_size as f64 * frame_rate).ceil() as u64;
+                let longest_side = width.max(height);
+                HEVC_LEVELS.iter()
+                    .find(|(_, max_ps, max_sr, max_br)| picture_size <= *max_ps
+                        && sample_rate <= *max_sr
+                        && longest_side * longest_side <= max_ps * 8
+                        && max_rate <= *max_br)
+                    .map(|(level, ..)| *level)
+            }
+            _ => return Err(AtiumError::InvalidRequest(format!("Codec [{:?}] is not supported for packaging, use h264 or h265", codec)))
+        };
+
+        level.map(|level| CodecProfile { codec, level })
+            .ok_or_else(|| AtiumError::InvalidRequest(format!(
+                "No [{:?}] level fits {}x{} at {:.2} fps and {} kbit/s", codec, size.0, size.1, frame_rate, max_rate
+            )))
+    }
+    /// The level as written by encoders, e.g. `3.1`
+    pub fn level_name(&self) -> String {
+        format!("{}.{}", self.level / 10, self.level % 10)
+    }
+    /// The RFC 6381 codecs of the video stream, e.g. `avc1.64001f` or `hvc1.1.6.L93.B0`
+    pub fn codecs(&self) -> String {
+        match self.codec {
+            OutputCodec::H265 => format!("hvc1.1.6.L{}.B0", self.level * 3),
+            _ => format!("avc1.6400{:02x}", self.level)
+        }
+    }
+    /// The `ffmpeg` arguments enforcing the profile and level, keyframes are only placed
+    /// where requested so that segments are aligned across renditions
+    pub fn encoder_args(&self) -> Vec<String> {
+        match self.codec {
+            OutputCodec::H265 => vec![
+                String::from("-profile:v"), String::from("main"),
+                String::from("-tag:v"), String::from("hvc1"),
+                String::from("-x265-params"), format!("level-idc={}:scenecut=0", self.level_name())
+            ],
+            _ => vec![
+                String::from("-profile:v"), String::from("high"),
+                String::from("-level:v"), self.level_name(),
+                String::from("-sc_threshold"), String::from("0")
+            ]
+        }
+    }
+}
+
+/// Number of 16x16 macroblocks covering `pixels`
+// `u64::div_ceil` is not available on the toolchain of the Docker image
+#[allow(clippy::manual_div_ceil)]
+fn macroblocks(pixels: u64) -> u64 {
+    (pixels + 15) / 16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_h264_level_selection() {
+        let hd = CodecProfile::select(OutputCodec::H264, (1280, 720), Some(29.97), 3080).unwrap();
+        assert_eq!(hd.level, 31);
+        assert_eq!(hd.codecs(), "avc1.64001f");
+
+        let full_hd = CodecProfile::select(OutputCodec::H264, (1920, 1080), Some(25.0), 5500).unwrap();
+        assert_eq!(full_hd.codecs(), "avc1.640028");
+
+        let high_rate = CodecProfile::select(OutputCodec::H264, (1920, 1080), Some(60.0), 5500).unwrap();
+        assert_eq!(high_rate.level_name(), "4.2");
+
+        let sd = CodecProfile::select(OutputCodec::H264, (640, 360), None, 1320).unwrap();
+        assert_eq!(sd.codecs(), "avc1.64001e");
+    }
+
+    #[test]
+    fn test_hevc_level_selection() {
+        let uhd = CodecProfile::select(OutputCodec::H265, (3840, 2160), Some(30.0), 13200).unwrap();
+        assert_eq!(uhd.codecs(), "hvc1.1.6.L150.B0");
+
+        let hd = CodecProfile::select(OutputCodec::H265, (1280, 720), Some(30.0), 3080).unwrap();
+        assert_eq!(hd.codecs(), "hvc1.1.6.L93.B0");
+        assert_eq!(hd.encoder_args()[5], "level-idc=3.1:scenecut=0");
+    }
+
+    #[test]
+    fn test_level_selection_failures() {
+        assert!(CodecProfile::select(OutputCodec::Vp9, (1280, 720), None, 3000).is_err());
+        assert!(CodecProfile::select(OutputCodec::H264, (1920, 1080), Some(30.0), 2_000_000).is_err());
+    }
+}
@@ -0,0 +1,133 @@
+use crate::atium::packager::model::{SegmentType, Variant};
+
+/// A media segment listed in a media playlist
+#[derive(Clone, Debug, PartialEq)]
+pub struct MediaSegment {
+    /// Duration in seconds, as announced by `#EXTINF`
+    pub duration: f64,
+    /// URI of the segment, relative to the playlist
+    pub uri: String
+}
+
+/// Returns the segments listed in an HLS media playlist
+pub fn parse_media_playlist(content: &str) -> Vec<MediaSegment> {
+    let mut segments = Vec::new();
+    let mut duration = None;
+
+    for line in content.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        if let Some(extinf) = line.strip_prefix("#EXTINF:") {
+            duration = extinf.split(',').next().and_then(|value| value.trim().parse::<f64>().ok());
+        } else if !line.starts_with('#') {
+            if let Some(duration) = duration.take() {
+                segments.push(MediaSegment { duration, uri: line.to_string() });
+            }
+        }
+    }
+
+    segments
+}
+
+/// Computes the peak and the average bitrate in bit/s of the given `(duration, size in bytes)` segments,
+/// None when there is no segment with a duration
+pub fn segment_bandwidth(segments: &[(f64, u64)]) -> Option<(u64, u64)> {
+    let segments: Vec<&(f64, u64)> = segments.iter().filter(|(duration, _)| *duration > 0.0).collect();
+    if segments.is_empty() {
+        return None
+    }
+
+    let peak = segments.iter()
+        .map(|(duration, size)| *size as f64 * 8.0 / duration)
+        .fold(0.0, f64::max);
+    let total_duration: f64 = segments.iter().map(|(duration, _)| duration).sum();
+    let total_bits: f64 = segments.iter().map(|(_, size)| *size as f64 * 8.0).sum();
+
+    Some((peak.ceil() as u64, (total_bits / total_duration).ceil() as u64))
+}
+
+/// Renders the master playlist listing the `variants` in the given order
+pub fn master_playlist(variants: &[Variant], segment_type: SegmentType) -> String {
+    // fMP4 segments need EXT-X-MAP in the media playlists, introduced by version 6
+    let version = match segment_type {
+        SegmentType::MpegTs => 3,
+        SegmentType::Fmp4 => 7
+    };
+    let mut playlist = format!("#EXTM3U\n#EXT-X-VERSION:{}\n#EXT-X-INDEPENDENT-SEGMENTS\n", version);
+
+    for variant in variants {
+        let mut attributes = vec![
+            format!("BANDWIDTH={}", variant.bandwidth),
+            format!("AVERAGE-BANDWIDTH={}", variant.average_bandwidth),
+            format!("RESOLUTION={}x{}", variant.resolution.0, variant.resolution.1)
+        ];
+        if let Some(frame_rate) = variant.frame_rate {
+            attributes.push(format!("FRAME-RATE={:.3}", frame_rate));
+        }
+        attributes.push(format!("CODECS=\"{}\"", variant.codecs));
+
+        playlist.push_str(&format!("#EXT-X-STREAM-INF:{}\n{}\n", attributes.join(","), variant.playlist));
+    }
+
+    playlist
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::converter::model::{LadderRung, OutputResolution};
+    use super::*;
+
+    const MEDIA_PLAYLIST: &str = "#EXTM3U
+#EXT-X-VERSION:3
+#EXT-X-TARGETDURATION:6
+#EXT-X-MEDIA-SEQUENCE:0
+#EXT-X-PLAYLIST-TYPE:VOD
+#EXTINF:6.006000,
+segment_00000.ts
+#EXTINF:6.006000,
+segment_00001.ts
+#EXTINF:2.502500,
+segment_00002.ts
+#EXT-X-ENDLIST
+";
+
+    #[test]
+    fn test_parse_media_playlist() {
+        let segments = parse_media_playlist(MEDIA_PLAYLIST);
+
+        assert_eq!(segments.len(), 3);
+        assert_eq!(segments[0], MediaSegment { duration: 6.006, uri: "segment_00000.ts".to_string() });
+        assert_eq!(segments[2].duration, 2.5025);
+    }
+
+    #[test]
+    fn test_segment_bandwidth() {
+        let (peak, average) = segment_bandwidth(&[(6.0, 750_000), (6.0, 1_500_000), (0.0, 10)]).unwrap();
+
+        assert_eq!(peak, 2_000_000);
+        assert_eq!(average, 1_500_000);
+        assert_eq!(segment_bandwidth(&[]), None);
+    }
+
+    #[test]
+    fn test_master_playlist() {
+        let variant = Variant {
+            name: "720p_2800k".to_string(),
+            rung: LadderRung { resolution: OutputResolution::Hd, bitrate: 2800 },
+            resolution: (1280, 720),
+            frame_rate: Some(29.97),
+            bandwidth: 3_350_000,
+            average_bandwidth: 2_900_000,
+            codecs: "avc1.64001f,mp4a.40.2".to_string(),
+            playlist: "720p_2800k/index.m3u8".to_string()
+        };
+        let no_frame_rate = Variant { frame_rate: None, ..variant.clone() };
+
+        assert_eq!(master_playlist(&[variant, no_frame_rate], SegmentType::MpegTs), "#EXTM3U
+#EXT-X-VERSION:3
+#EXT-X-INDEPENDENT-SEGMENTS
+#EXT-X-STREAM-INF:BANDWIDTH=3350000,AVERAGE-BANDWIDTH=2900000,RESOLUTION=1280x720,FRAME-RATE=29.970,CODECS=\"avc1.64001f,mp4a.40.2\"
+720p_2800k/index.m3u8
+#EXT-X-STREAM-INF:BANDWIDTH=3350000,AVERAGE-BANDWIDTH=2900000,RESOLUTION=1280x720,CODECS=\"avc1.64001f,mp4a.40.2\"
+720p_2800k/index.m3u8
+");
+    }
+}
@@ -0,0 +1,4 @@
+pub mod model;
+pub mod codecs;
+pub mod hls;
+pub mod service;
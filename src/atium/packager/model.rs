@@ -0,0 +1,98 @@
+use crate::atium::common::error::AtiumError;
+use crate::converter::model::{LadderRung, OutputCodec};
+
+/// HLS segment formats:
+/// * MpegTs -> MPEG-2 Transport Stream segments
+/// * Fmp4   -> fragmented MP4 segments sharing an initialization segment, required for HEVC
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SegmentType {
+    MpegTs, Fmp4
+}
+
+impl SegmentType {
+    /// The segment format expected by players for `codec`
+    pub fn default_for(codec: OutputCodec) -> Self {
+        match codec {
+            OutputCodec::H265 => SegmentType::Fmp4,
+            _ => SegmentType::MpegTs
+        }
+    }
+    /// The file extension of the segments
+    pub fn extension(&self) -> &'static str {
+        match self {
+            SegmentType::MpegTs => "ts",
+            SegmentType::Fmp4 => "m4s"
+        }
+    }
+}
+
+/// Returns a value of [`SegmentType`] based on input:
+/// Valid inputs are:
+/// * ts, mpegts  -> MPEG-TS
+/// * fmp4, cmaf  -> FMP4
+///
+/// Fails on any other input
+pub fn parse_segment_type(segment_string: &str) -> Result<SegmentType, AtiumError> {
+    match segment_string.to_lowercase().as_str() {
+        "ts" | "mpegts" => Ok(SegmentType::MpegTs),
+        "fmp4" | "cmaf" => Ok(SegmentType::Fmp4),
+        _ => Err(AtiumError::InvalidRequest(format!("Segment type [{}] is not supported, expected ts/fmp4", segment_string)))
+    }
+}
+
+/// HLS packaging request
+pub struct HlsRequest {
+    pub input: String,
+    /// Directory receiving the master playlist and a sub directory per variant
+    pub output_dir: String,
+    pub ladder: Vec<LadderRung>,
+    /// Only [`OutputCodec::H264`] and [`OutputCodec::H265`] are supported
+    pub codec: OutputCodec,
+    pub segment_type: SegmentType,
+    /// Target duration of the segments in seconds
+    pub segment_duration: u32,
+    pub preset: Option<String>,
+    /// AAC stereo bitrate in kbit/s, shared by every variant
+    pub audio_bitrate: u32,
+    /// Runs one `ffmpeg` per rung instead of a single run encoding the whole ladder
+    pub per_rung: bool
+}
+
+/// A packaged variant, as listed in the master playlist
+#[derive(Clone, Debug, PartialEq)]
+pub struct Variant {
+    /// Name of the variant directory, e.g. `720p_2800k`
+    pub name: String,
+    pub rung: LadderRung,
+    /// Frame size in pixels
+    pub resolution: (u32, u32),
+    pub frame_rate: Option<f64>,
+    /// Peak segment bitrate in bit/s
+    pub bandwidth: u64,
+    /// Average segment bitrate in bit/s
+    pub average_bandwidth: u64,
+    /// RFC 6381 codecs of the variant, e.g. `avc1.64001f,mp4a.40.2`
+    pub codecs: String,
+    /// Path of the media playlist, relative to the output directory
+    pub playlist: String
+}
+
+/// HLS packaging response
+pub struct HlsResponse {
+    pub master_playlist: String,
+    pub variants: Vec<Variant>,
+    /// Rungs above the source resolution, not packaged
+    pub skipped: Vec<LadderRung>
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_segment_type() {
+        assert_eq!(parse_segment_type("MPEGTS").unwrap(), SegmentType::MpegTs);
+        assert_eq!(parse_segment_type("cmaf").unwrap(), SegmentType::Fmp4);
+        assert!(parse_segment_type("mp4").is_err());
+    }
+}
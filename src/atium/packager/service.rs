@@ -0,0 +1,300 @@
+use std::fs;
+use std::path::Path;
+use std::process::Output;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+
+use crate::atium::common::analyzer::{load_analyzer, MediaAnalyzer};
+use crate::atium::common::command_manager::{CommandManager, ExecutionControl};
+use crate::atium::common::error::AtiumError;
+use crate::atium::common::progress::{Progress, ProgressParser, ProgressStage};
+use crate::atium::packager::codecs::{AAC_LC_CODECS, CodecProfile};
+use crate::atium::packager::hls;
+use crate::atium::packager::model::{HlsRequest, HlsResponse, SegmentType, Variant};
+use crate::converter::model::{AudioChannels, AudioCodec, AudioOutput, OutputCodec, RotationMode, ScalingMode};
+use crate::converter::service::{is_encoder_listed, plan_ladder, PlannedVariant, stream_args};
+
+const MASTER_PLAYLIST: &str = "master.m3u8";
+const MEDIA_PLAYLIST: &str = "index.m3u8";
+
+/// Packages a source into adaptive bitrate renditions, one directory per variant
+/// next to the master playlist
+pub struct FFMPEGPackagingService {
+    command_manager: CommandManager,
+    analyzer: Box<dyn MediaAnalyzer>
+}
+
+impl FFMPEGPackagingService {
+    fn check_request(&self, request: &HlsRequest) -> Result<(), AtiumError> {
+        if !matches!(request.codec, OutputCodec::H264 | OutputCodec::H265) {
+            return Err(AtiumError::InvalidRequest(format!("Codec [{:?}] is not supported for packaging, use h264 or h265", request.codec)))
+        }
+        if request.codec == OutputCodec::H265 && request.segment_type == SegmentType::MpegTs {
+            return Err(AtiumError::InvalidRequest("HEVC renditions need fMP4 segments".to_string()))
+        }
+        if request.ladder.is_empty() {
+            return Err(AtiumError::InvalidRequest("The ladder needs at least one rung".to_string()))
+        }
+        if request.segment_duration == 0 {
+            return Err(AtiumError::InvalidRequest("Segment duration must be greater than 0".to_string()))
+        }
+        for rung in &request.ladder {
+            request.codec.encoding_args(Some(&rung.rate_control()), request.preset.as_deref(), None)?;
+        }
+
+        self.audio_output(request).audio_args(None).map(|_| ())
+    }
+    fn check_encoders(&self, request: &HlsRequest) -> Result<(), AtiumError> {
+        let result = self.command_manager.execute_with_args(vec!["-hide_banner", "-encoders"])?;
+        let listing = self.command_manager.get_command_output_as_string(result.stdout)?;
+
+        for encoder in [request.codec.encoder(), AudioCodec::Aac.encoder()].into_iter().flatten() {
+            if !is_encoder_listed(&listing, encoder) {
+                return Err(AtiumError::Dependency(format!("Encoder [{}] is not available in the local ffmpeg build", encoder)))
+            }
+        }
+
+        Ok(())
+    }
+    fn audio_output(&self, request: &HlsRequest) -> AudioOutput {
+        AudioOutput {
+            codec: AudioCodec::Aac,
+            bitrate: Some(request.audio_bitrate),
+            channels: Some(AudioChannels::Stereo),
+            sample_rate: None
+        }
+    }
+    fn video_args(&self, request: &HlsRequest, variant: &PlannedVariant, profile: &CodecProfile) -> Result<Vec<String>, AtiumError> {
+        let mut args = request.codec.encoding_args(Some(&variant.rung.rate_control()), request.preset.as_deref(), None)?;
+        args.extend(profile.encoder_args());
+        // Keyframes on segment boundaries keep the segments of every variant aligned
+        args.push(String::from("-force_key_frames"));
+        args.push(format!("expr:gte(t,n_forced*{})", request.segment_duration));
+
+        Ok(args)
+    }
+    fn hls_args(&self, request: &HlsRequest, variant_dir: &str) -> Vec<String> {
+        let mut args = vec![
+            String::from("-f"), String::from("hls"),
+            String::from("-hls_time"), request.segment_duration.to_string(),
+            String::from("-hls_playlist_type"), String::from("vod"),
+            String::from("-hls_segment_type"), match request.segment_type {
+                SegmentType::MpegTs => String::from("mpegts"),
+                SegmentType::Fmp4 => String::from("fmp4")
+            }
+        ];
+        if request.segment_type == SegmentType::Fmp4 {
+            args.push(String::from("-hls_fmp4_init_filename"));
+            args.push(String::from("init.mp4"));
+        }
+        args.push(String::from("-hls_segment_filename"));
+        args.push(format!("{}/{}/segment_%05d.{}", request.output_dir, variant_dir, request.segment_type.extension()));
+        args.push(format!("{}/{}/{}", request.output_dir, variant_dir, MEDIA_PLAYLIST));
+
+        args
+    }
+    /// Arguments of a single run encoding every variant, the source is decoded once
+    /// and split into one scaled stream per variant
+    fn build_ladder_args(&self, request: &HlsRequest, variants: &[(PlannedVariant, CodecProfile)], audio_args: Option<Vec<String>>) -> Result<Vec<String>, AtiumError> {
+        let splits: String = (0..variants.len()).map(|index| format!("[s{}]", index)).collect();
+        let mut graph = vec![format!("[0:v]split={}{}", variants.len(), splits)];
+        graph.extend(variants.iter().enumerate()
+            .map(|(index, (variant, _))| format!("[s{}]{}[v{}]", index, variant.geometry.to_filter(), index)));
+
+        let mut args = vec![
+            String::from("-y"),
+            String::from("-i"), request.input.clone(),
+            String::from("-filter_complex"), graph.join(";")
+        ];
+        let mut stream_map = Vec::new();
+
+        for (index, (variant, profile)) in variants.iter().enumerate() {
+            args.push(String::from("-map"));
+            args.push(format!("[v{}]", index));
+            args.extend(stream_args(self.video_args(request, variant, profile)?, index));
+            if audio_args.is_some() {
+                args.push(String::from("-map"));
+                args.push(String::from("0:a:0"));
+                stream_map.push(format!("v:{},a:{},name:{}", index, index, variant.name));
+            } else {
+                stream_map.push(format!("v:{},name:{}", index, variant.name));
+            }
+        }
+
+        args.extend(audio_args.unwrap_or_default());
+        args.push(String::from("-var_stream_map"));
+        args.push(stream_map.join(" "));
+        args.extend(self.hls_args(request, "%v"));
+
+        Ok(args)
+    }
+    /// Arguments of a run encoding a single variant
+    fn build_rung_args(&self, request: &HlsRequest, variant: &PlannedVariant, profile: &CodecProfile, audio_args: Option<Vec<String>>) -> Result<Vec<String>, AtiumError> {
+        let mut args = vec![
+            String::from("-y"),
+            String::from("-i"), request.input.clone(),
+            String::from("-map"), String::from("0:v:0")
+        ];
+        if audio_args.is_some() {
+            args.push(String::from("-map"));
+            args.push(String::from("0:a:0"));
+        }
+        args.push(String::from("-vf"));
+        args.push(variant.geometry.to_filter());
+        args.extend(self.video_args(request, variant, profile)?);
+        args.extend(audio_args.unwrap_or_default());
+        args.extend(self.hls_args(request, &variant.name));
+
+        Ok(args)
+    }
+    fn execute(&self, args: Vec<String>, duration: Option<Duration>, control: &ExecutionControl, on_progress: &mut dyn FnMut(Progress)) -> Result<Output, AtiumError> {
+        let mut progress_args = vec![String::from("-nostdin"), String::from("-progress"), String::from("pipe:1"), String::from("-nostats")];
+        progress_args.extend(args);
+
+        let mut parser = ProgressParser::new(ProgressStage::Encoding, duration);
+        let mut on_line = |line: &str| {
+            if let Some(progress) = parser.parse_line(line) {
+                on_progress(progress);
+            }
+        };
+
+        self.command_manager.execute_streaming(progress_args.iter().map(AsRef::as_ref).collect(), control, &mut on_line)
+            .map_err(|err| err.into_encode("ffmpeg packaging failed"))
+    }
+    /// Measures the peak and average bitrate of a packaged variant out of its segments
+    fn measure_variant(&self, request: &HlsRequest, variant: &PlannedVariant) -> Result<Option<(u64, u64)>, AtiumError> {
+        let variant_dir = Path::new(&request.output_dir).join(&variant.name);
+        let content = fs::read_to_string(variant_dir.join(MEDIA_PLAYLIST))
+            .map_err(|err| AtiumError::Output { message: format!("Could not read the media playlist of variant [{}]", variant.name), source: Some(err) })?;
+
+        let segments = hls::parse_media_playlist(&content).into_iter()
+            .map(|segment| fs::metadata(variant_dir.join(&segment.uri))
+                .map(|metadata| (segment.duration, metadata.len()))
+                .map_err(|err| AtiumError::Output { message: format!("Could not read segment [{}] of variant [{}]", segment.uri, variant.name), source: Some(err) }))
+            .collect::<Result<Vec<(f64, u64)>, AtiumError>>()?;
+
+        Ok(hls::segment_bandwidth(&segments))
+    }
+    fn cleanup_variants(&self, request: &HlsRequest, variants: &[(PlannedVariant, CodecProfile)]) {
+        for (variant, _) in variants {
+            let variant_dir = Path::new(&request.output_dir).join(&variant.name);
+            if let Err(err) = fs::remove_dir_all(&variant_dir) {
+                warn!("Variant directory [{}] not removed: {}", variant_dir.display(), err);
+            }
+        }
+    }
+    fn encode(&self, request: &HlsRequest, variants: &[(PlannedVariant, CodecProfile)], audio_args: Option<Vec<String>>, duration: Option<Duration>, control: &ExecutionControl, on_progress: &mut dyn FnMut(Progress)) -> Result<(), AtiumError> {
+        if request.per_rung {
+            for (variant, profile) in variants {
+                debug!("Packaging variant [{}]", variant.name);
+                let args = self.build_rung_args(request, variant, profile, audio_args.clone())?;
+                self.execute(args, duration, control, on_progress)?;
+            }
+            Ok(())
+        } else {
+            debug!("Packaging {} variants in a single run", variants.len());
+            let args = self.build_ladder_args(request, variants, audio_args)?;
+            self.execute(args, duration, control, on_progress).map(|_| ())
+        }
+    }
+    /// Packages the source as HLS and returns an [`HlsResponse`]
+    pub fn package_hls(&self, request: HlsRequest) -> Result<HlsResponse, AtiumError> {
+        self.package_hls_with_control(request, &ExecutionControl::default(), |_| {})
+    }
+    /// Packages the source as HLS like [`FFMPEGPackagingService::package_hls`], reporting the progress
+    /// of every `ffmpeg` run to `on_progress`. Every external command is bound to `control`.
+    ///
+    /// Rungs above the source resolution are skipped, the variants left behind by a killed `ffmpeg` are removed.
+    pub fn package_hls_with_control<F: FnMut(Progress)>(&self, request: HlsRequest, control: &ExecutionControl, mut on_progress: F) -> Result<HlsResponse, AtiumError> {
+        self.check_request(&request)?;
+        self.check_encoders(&request)?;
+
+        if !Path::new(&request.input).is_file() {
+            return Err(AtiumError::Input { message: format!("Input file [{}] does not exist", request.input), source: None })
+        }
+
+        let analysis_output = self.analyzer.analyze_with_control(&request.input, control)?;
+        let video_track = analysis_output.video_track()?;
+        let duration = analysis_output.duration().ok();
+        let audio_args = match analysis_output.audio_tracks().first() {
+            Some(audio_track) => Some(self.audio_output(&request).audio_args(audio_track.channels)?),
+            None => {
+                debug!("Source has no audio, variants are video only");
+                None
+            }
+        };
+
+        let (planned, skipped) = plan_ladder(&request.ladder, ScalingMode::Fit, RotationMode::Bake, &video_track)?;
+        skipped.iter().for_each(|rung| info!("Rung [{:?}] at {} kbit/s is above the source resolution, skipped", rung.resolution, rung.bitrate));
+
+        let variants = planned.into_iter()
+            .map(|variant| {
+                let profile = CodecProfile::select(request.codec, variant.geometry.frame, video_track.frame_rate, variant.rung.max_rate())?;
+                Ok((variant, profile))
+            })
+            .collect::<Result<Vec<(PlannedVariant, CodecProfile)>, AtiumError>>()?;
+
+        for (variant, _) in &variants {
+            fs::create_dir_all(Path::new(&request.output_dir).join(&variant.name))
+                .map_err(|err| AtiumError::Output { message: format!("Could not create the directory of variant [{}]", variant.name), source: Some(err) })?;
+        }
+
+        match self.encode(&request, &variants, audio_args.clone(), duration, control, &mut on_progress) {
+            Ok(_) => {}
+            Err(err @ (AtiumError::Timeout { .. } | AtiumError::Cancelled { .. })) => {
+                // the variants left behind by a killed ffmpeg are incomplete
+                self.cleanup_variants(&request, &variants);
+                return Err(err)
+            }
+            Err(err) => return Err(err)
+        }
+
+        let audio_bitrate = audio_args.map(|_| request.audio_bitrate as u64).unwrap_or(0);
+        let mut packaged = Vec::new();
+        for (variant, profile) in variants {
+            let (bandwidth, average_bandwidth) = match self.measure_variant(&request, &variant)? {
+                Some(measured) => measured,
+                None => {
+                    warn!("Variant [{}] has no segment, its nominal bitrate is announced", variant.name);
+                    ((variant.rung.max_rate() as u64 + audio_bitrate) * 1000, (variant.rung.bitrate as u64 + audio_bitrate) * 1000)
+                }
+            };
+            let codecs = match audio_bitrate {
+                0 => profile.codecs(),
+                _ => format!("{},{}", profile.codecs(), AAC_LC_CODECS)
+            };
+
+            packaged.push(Variant {
+                playlist: format!("{}/{}", variant.name, MEDIA_PLAYLIST),
+                name: variant.name,
+                rung: variant.rung,
+                resolution: variant.geometry.frame,
+                frame_rate: video_track.frame_rate,
+                bandwidth,
+                average_bandwidth,
+                codecs
+            });
+        }
+
+        let master_playlist = Path::new(&request.output_dir).join(MASTER_PLAYLIST);
+        fs::write(&master_playlist, hls::master_playlist(&packaged, request.segment_type))
+            .map_err(|err| AtiumError::Output { message: "Could not write the master playlist".to_string(), source: Some(err) })?;
+
+        debug!("Packaging done!");
+
+        Ok(HlsResponse {
+            master_playlist: master_playlist.to_string_lossy().to_string(),
+            variants: packaged,
+            skipped
+        })
+    }
+    /// Constructs a new instance of [`FFMPEGPackagingService`]
+    pub fn new() -> Result<Self, AtiumError> {
+        let command_manager =
+            CommandManager::new("ffmpeg".to_string(), vec!["-version"])?;
+        let analyzer = load_analyzer()?;
+
+        Ok(Self { command_manager, analyzer })
+    }
+}
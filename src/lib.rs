@@ -0,0 +1,378 @@
+//! # Atium
+//!
+//! Atium is a simple video conversion tool that lets you obtain a converted video by
+//! specifying some parameters like resolution, codec, and container.
+//!
+//! This is available as an importable library, exporting the conversion, packaging, concatenation, analysis and
+//! thumbnail services along with their request and response models, but its main usage
+//! occurs through a CLI, powered by `Clap`.
+//!
+//! ## API
+//! ### Conversion
+//!
+//! Basic usage for conversion API is simple as it follows:
+//! First you need to build a new instance of [FFMPEGConversionService](crate::converter::service::FFMPEGConversionService)
+//! ```no_run
+//! # use atium::converter::service::FFMPEGConversionService;
+//! let conversion_service = FFMPEGConversionService::new().expect("could not load service");
+//! ```
+//! The following step is to create a [ConversionRequest](crate::converter::model::ConversionRequest) that contains the required options in order to
+//! tune the conversion output.
+//! Please take a look at the structure documentation to understand each field's meaning.
+//! ```
+//! # use atium::common::model::ThumbnailRequest;
+//! # use atium::converter::model::*;
+//! let request = ConversionRequest{
+//!     input: ConversionInput {
+//!         source_type: InputSourceType::Local,
+//!         file_name:  String::from("/path/to/input.mp4")
+//!     },
+//!     output: ConversionOutput {
+//!         file: String::from("/path/to/output.mp4"),
+//!         resolution: OutputResolution::Hd,
+//!         scaling: ScalingMode::Fit,
+//!         rotation: RotationMode::Bake,
+//!         codec: OutputCodec::H264,
+//!         container: OutputContainer::Mp4,
+//!         rate_control: Some(RateControl::ConstantQuality(23)),
+//!         preset: Some(String::from("medium")),
+//!         tune: None,
+//!         two_pass: false,
+//!         audio: Some(AudioOutput {
+//!             codec: AudioCodec::Aac,
+//!             bitrate: Some(128),
+//!             channels: Some(AudioChannels::Stereo),
+//!             sample_rate: None
+//!         }),
+//!         loudness: Some(LoudnessTarget::default()),
+//!         // converts 00:01:30 to 00:02:45 only, `segment_duration` would cut the output into chunks
+//!         range: parse_clip_range(Some("00:01:30"), Some("00:02:45")).unwrap(),
+//!         segment_duration: None,
+//!         dash: None,
+//!         thumbnail_request: ThumbnailRequest::new(
+//!               &Some(String::from("00:00:01")),
+//!               // this could be blank since it will use the converted video
+//!               // as a source for thumbnail extraction
+//!               &Some(String::from("/path/to/input.mp4")),
+//!               &Some(String::from("/path/to/thumb.jpg"))
+//!         )
+//!     }
+//!  };
+//! ```
+//! Now you can perform the actual conversion! Use the previously created `conversion_service`
+//! and call the `convert` method.
+//! ```no_run
+//! # use atium::common::model::ThumbnailRequest;
+//! # use atium::converter::model::*;
+//! # use atium::converter::service::FFMPEGConversionService;
+//! # let conversion_service = FFMPEGConversionService::new().unwrap();
+//! # let request = ConversionRequest {
+//! #     input: ConversionInput { source_type: InputSourceType::Local, file_name: String::from("/path/to/input.mp4") },
+//! #     output: ConversionOutput {
+//! #         file: String::from("/path/to/output.mp4"),
+//! #         resolution: OutputResolution::Hd,
+//! #         scaling: ScalingMode::Fit,
+//! #         rotation: RotationMode::Bake,
+//! #         codec: OutputCodec::H264,
+//! #         container: OutputContainer::Mp4,
+//! #         rate_control: None,
+//! #         preset: None,
+//! #         tune: None,
+//! #         two_pass: false,
+//! #         audio: None,
+//! #         loudness: None,
+//! #         range: None,
+//! #         segment_duration: None,
+//! #         dash: None,
+//! #         thumbnail_request: None
+//! #     }
+//! # };
+//! # use atium::common::error::AtiumError;
+//! let result: Result<ConversionResponse, AtiumError> = conversion_service.convert(request);
+//! ```
+//! The output is a `Result<ConversionResponse, AtiumError>`, so now is up to you.
+//! This contains a [ConversionResponse](crate::converter::model::ConversionResponse) that holds the output path of the converted video!
+//!
+//! Long conversions can report their progress through `convert_with_progress`, the callback receives
+//! a [Progress](crate::common::progress::Progress) for every update printed by `ffmpeg`.
+//! ```no_run
+//! # use atium::common::model::ThumbnailRequest;
+//! # use atium::converter::model::*;
+//! # use atium::converter::service::FFMPEGConversionService;
+//! # let conversion_service = FFMPEGConversionService::new().unwrap();
+//! # let request = ConversionRequest {
+//! #     input: ConversionInput { source_type: InputSourceType::Local, file_name: String::from("/path/to/input.mp4") },
+//! #     output: ConversionOutput {
+//! #         file: String::from("/path/to/output.mp4"),
+//! #         resolution: OutputResolution::Hd,
+//! #         scaling: ScalingMode::Fit,
+//! #         rotation: RotationMode::Bake,
+//! #         codec: OutputCodec::H264,
+//! #         container: OutputContainer::Mp4,
+//! #         rate_control: None,
+//! #         preset: None,
+//! #         tune: None,
+//! #         two_pass: false,
+//! #         audio: None,
+//! #         loudness: None,
+//! #         range: None,
+//! #         segment_duration: None,
+//! #         dash: None,
+//! #         thumbnail_request: None
+//! #     }
+//! # };
+//! let (sender, receiver) = std::sync::mpsc::channel();
+//! let result = conversion_service.convert_with_progress(request, move |progress| {
+//!     let _ = sender.send(progress);
+//! });
+//! ```
+//! `convert_with_control` additionally takes an [ExecutionControl](crate::common::command_manager::ExecutionControl),
+//! holding a timeout and a cancellation token that kill the running `ffmpeg` when triggered.
+//!
+//! Setting `dash` to a [DashOutput](crate::converter::model::DashOutput) turns the output file into an MPEG-DASH
+//! manifest: every rung of the ladder is encoded as a representation of fragmented MP4 segments, and `ffmpeg`
+//! writes the manifest along with its segment timeline.
+//!
+//! ### Packaging
+//!
+//! [FFMPEGPackagingService](crate::packager::service::FFMPEGPackagingService) packages a source into
+//! an HLS ladder, skipping the rungs above the source resolution.
+//! ```no_run
+//! # use atium::converter::model::{OutputCodec, parse_ladder};
+//! # use atium::packager::model::*;
+//! # use atium::packager::service::FFMPEGPackagingService;
+//! let packaging_service = FFMPEGPackagingService::new().expect("could not load service");
+//! let request = HlsRequest {
+//!     input: String::from("/path/to/input.mp4"),
+//!     output_dir: String::from("/path/to/hls"),
+//!     ladder: parse_ladder("fhd:5000,hd:2800,sd:1200").unwrap(),
+//!     codec: OutputCodec::H264,
+//!     segment_type: SegmentType::MpegTs,
+//!     segment_duration: 6,
+//!     preset: None,
+//!     audio_bitrate: 128,
+//!     per_rung: false
+//! };
+//! let response = packaging_service.package_hls(request);
+//! ```
+//!
+//! ### Thumbnails
+//!
+//! [FFMPEGThumbnailService](crate::common::thumbnail_service::FFMPEGThumbnailService) extracts a single frame,
+//! or a sprite sheet of frames taken at a regular interval along with its WebVTT thumbnail track.
+//! ```no_run
+//! # use std::time::Duration;
+//! # use atium::common::model::SpriteRequest;
+//! # use atium::common::thumbnail_service::FFMPEGThumbnailService;
+//! let thumbnail_service = FFMPEGThumbnailService::new().expect("could not load service");
+//! let request = SpriteRequest {
+//!     input_file: String::from("/path/to/input.mp4"),
+//!     output_file: Some(String::from("/path/to/sprite.jpg")),
+//!     vtt_file: None,
+//!     interval: Duration::from_secs(10),
+//!     tile_width: 160,
+//!     tile_height: None,
+//!     columns: 10
+//! };
+//! let response = thumbnail_service.extract_sprite(request);
+//! ```
+//! [extract_preview](crate::common::thumbnail_service::FFMPEGThumbnailService::extract_preview) takes a
+//! [PreviewRequest](crate::common::model::PreviewRequest) and produces short animated GIF, WebP or MP4 clips.
+//!
+//! ### Concatenation
+//!
+//! [FFMPEGConcatService] joins several inputs in order, copying their
+//! streams when they match and normalizing them to the longest input otherwise.
+//! ```no_run
+//! # use atium::concat::model::ConcatRequest;
+//! # use atium::concat::service::FFMPEGConcatService;
+//! # use atium::converter::model::{OutputCodec, OutputContainer};
+//! let concat_service = FFMPEGConcatService::new().expect("could not load service");
+//! let request = ConcatRequest {
+//!     inputs: vec![String::from("/path/to/intro.mp4"), String::from("/path/to/content.mp4")],
+//!     output_file: String::from("/path/to/output.mp4"),
+//!     container: OutputContainer::Mp4,
+//!     codec: OutputCodec::H264,
+//!     rate_control: None,
+//!     preset: None,
+//!     normalize: false
+//! };
+//! let response = concat_service.concat(request);
+//! ```
+//!
+//! ## Command Line Interface
+//!
+//! After installing `atium` by entering `atium --help` the following helper shows up:
+//! ```text
+//! Usage: atium <COMMAND>
+//!
+//! Commands:
+//!   convert    Conversion tool for video media
+//!   analyze    Analyze media to extract useful infos
+//!   thumbnail  Thumbnail extraction tool
+//!   split      Splits a video into segments of a fixed duration
+//!   concat     Concatenates several videos into one
+//!   batch      Batch conversion of the jobs listed in a manifest
+//!   package    Adaptive streaming packaging tool
+//!   help       Print this message or the help of the given subcommand(s)
+//!
+//! Options:
+//!   -h, --help     Print help information
+//!   -V, --version  Print version information
+//! ```
+//! Here you can access all the tools offered by `atium`.
+//!
+//! ### Conversion
+//!
+//! ```text
+//! Conversion tool for video media
+//!
+//! Usage: atium convert [OPTIONS] --input <INPUT> --output <OUTPUT>
+//!
+//! Options:
+//!   -i, --input <INPUT>                Input file to convert
+//!   -s, --source-type <SOURCE_TYPE>    Type of source to convert
+//!   -o, --output <OUTPUT>              Output path for the converted file, the manifest of a DASH output
+//!   -r, --resolution <RESOLUTION>      Requested output resolution, DASH outputs take the resolutions of the ladder
+//!       --scaling <SCALING>            Scaling mode `fit/fill/pad/crop`, the source aspect ratio is always kept
+//!       --rotation <ROTATION>          Rotation handling `bake/metadata`, defaults to baking it into the frames
+//!   -c, --codec <CODEC>                Requested video codec `h264/h265/vp9/av1/av1-svt/prores/copy`
+//!       --container <CONTAINER>        Requested output container `mp4/mkv/webm/mov/ts`, defaults to the output file extension
+//!       --crf <CRF>                    Constant quality value (CRF/CQ), cannot be combined with bitrate options
+//!       --bitrate <BITRATE>            Target average video bitrate in kbit/s
+//!       --maxrate <MAXRATE>            Maximum video bitrate in kbit/s, enables constrained VBR
+//!       --bufsize <BUFSIZE>            Decoder buffer size in kbit, defaults to twice the maxrate
+//!       --preset <PRESET>              Encoder preset, e.g. `slow` for x264/x265 or `6` for SVT-AV1
+//!       --tune <TUNE>                  Encoder tune, e.g. `film` or `animation` for x264
+//!       --two-pass                     Runs a two-pass encoding, needs a target bitrate
+//!       --dash                         Writes an MPEG-DASH manifest, every rung of the ladder is encoded as a representation next to it
+//!       --ladder <LADDER>              Ladder of DASH representations as `resolution:bitrate` rungs in kbit/s, e.g. `fhd:5000,hd:2800,sd:1200`
+//!       --dash-segment-duration <SECONDS>
+//!                                      Target DASH segment duration in seconds, defaults to `6`
+//!       --audio-codec <AUDIO_CODEC>    Requested audio codec `aac/opus/mp3/flac/copy/none`
+//!       --audio-bitrate <AUDIO_BITRATE>
+//!                                      Audio bitrate in kbit/s
+//!       --audio-channels <AUDIO_CHANNELS>
+//!                                      Audio channel layout `mono/stereo/5.1`, sources with more channels are downmixed
+//!       --sample-rate <SAMPLE_RATE>    Audio sample rate in Hz
+//!       --loudness <LOUDNESS>          Normalizes audio loudness to the given integrated loudness in LUFS, e.g. `-23`
+//!       --true-peak <TRUE_PEAK>        Maximum true peak in dBTP for loudness normalization, defaults to `-1`
+//!       --loudness-range <LOUDNESS_RANGE>
+//!                                      Loudness range in LU for loudness normalization, defaults to `7`
+//!       --start <START>                Start of the converted range, e.g. `00:01:30`, defaults to the start of the source
+//!       --end <END>                    End of the converted range, e.g. `00:02:45`, defaults to the end of the source
+//!
+//!       --timeout <TIMEOUT>            Maximum running time in seconds of every external command, killed when exceeded
+//!       --thumb-ts <THUMB_TS>          Comma separated timestamps requested for thumbnail extraction, `auto` picks the best frame
+//!       --thumb-source <THUMB_SOURCE>  Source from where to extract the thumbnail
+//!       --thumb-out <THUMB_OUT>        Output path for the extracted thumbnail
+//!       --thumb-format <THUMB_FORMAT>  Thumbnail format `jpeg/png/webp/avif`, defaults to the thumbnail output extension
+//!       --thumb-width <THUMB_WIDTH>    Maximum thumbnail width in pixels, the aspect ratio is kept
+//!       --thumb-height <THUMB_HEIGHT>  Maximum thumbnail height in pixels, the aspect ratio is kept
+//!       --thumb-quality <THUMB_QUALITY>
+//!                                      Thumbnail quality from `1` to `100`
+//!   -h, --help                         Print help information
+//!   -V, --version                      Print version information
+//! ```
+//!
+//! ### Split
+//!
+//! ```text
+//! Splits a video into segments of a fixed duration
+//!
+//! Usage: atium split [OPTIONS] --input <INPUT> --output <OUTPUT>
+//!
+//! Options:
+//!   -i, --input <INPUT>            Input file to split
+//!   -o, --output <OUTPUT>          Output path of the segments, numbered from `000` before the extension
+//!   -d, --duration <DURATION>      Seconds of every segment, defaults to `600`
+//!       --start <START>            Start of the split range, defaults to the start of the source
+//!       --end <END>                End of the split range, defaults to the end of the source
+//!   -c, --codec <CODEC>            Requested video codec, defaults to `copy` whose cuts snap to keyframes
+//!   -r, --resolution <RESOLUTION>  Requested output resolution when encoding, defaults to the source one
+//!       --crf <CRF>                Constant quality value (CRF/CQ) when encoding
+//!       --preset <PRESET>          Encoder preset, e.g. `slow` for x264/x265
+//!       --timeout <TIMEOUT>        Maximum running time in seconds of every external command, killed when exceeded
+//!   -h, --help                     Print help information
+//!   -V, --version                  Print version information
+//! ```
+//!
+//! ### Concat
+//!
+//! ```text
+//! Concatenates several videos into one
+//!
+//! Usage: atium concat [OPTIONS] --input <INPUT> --output <OUTPUT>
+//!
+//! Options:
+//!   -i, --input <INPUT>          Input files in order, e.g. `-i intro.mp4 -i content.mp4 -i outro.mp4`
+//!   -o, --output <OUTPUT>        Output path for the concatenated file
+//!   -c, --codec <CODEC>          Video codec used when the inputs are normalized, defaults to `h264`
+//!       --container <CONTAINER>  Requested output container `mp4/mkv/webm/mov/ts`, defaults to the output file extension
+//!       --crf <CRF>              Constant quality value (CRF/CQ) when normalizing
+//!       --preset <PRESET>        Encoder preset when normalizing, e.g. `slow` for x264/x265
+//!       --normalize              Normalizes the inputs even when they could be stream copied
+//!       --timeout <TIMEOUT>      Maximum running time in seconds of every external command, killed when exceeded
+//!   -h, --help                   Print help information
+//!   -V, --version                Print version information
+//! ```
+//!
+//! ### Analyze
+//!
+//! ```text
+//! Analyze media to extract useful infos
+//!
+//! Usage: atium analyze [OPTIONS] --input <INPUT>
+//!
+//! Options:
+//!   -i, --input <INPUT>                  Input path of the file that will be analyzed
+//!   -f, --full <FULL>                    Whether you want the full analysis or not [possible values: true, false]
+//!       --output-format <OUTPUT_FORMAT>  Output format of the analysis tool
+//!       --output-file <OUTPUT_FILE>      Output file containing analysis result
+//!   -h, --help                           Print help information
+//!   -V, --version                        Print version information
+//! ```
+//!
+//! ### Thumbnail
+//!
+//! ```text
+//! Thumbnail extraction tool
+//!
+//! Usage: atium thumbnail [OPTIONS]
+//!
+//! Options:
+//!   -t, --timestamp <TIMESTAMP>      Comma separated timestamps of the video for thumbnail extraction, `auto` picks the best frame
+//!   -p, --percentages <PERCENTAGES>  Comma separated percentages of the duration for thumbnail extraction, e.g. `10,50,90`
+//!   -s, --source-path <SOURCE_PATH>  The source video for thumbnail extraction
+//!   -o, --output-path <OUTPUT_PATH>  Where to put the extracted thumbnail
+//!   -m, --mode <MODE>                Extraction mode `single/sprite/preview`, a sprite is a grid of frames along with a WebVTT track, a preview is a short animated clip
+//!       --interval <INTERVAL>        Seconds between two extracted frames, defaults to `10` for sprites
+//!       --tile-width <TILE_WIDTH>    Width in pixels of a sprite tile, defaults to `160`
+//!       --tile-height <TILE_HEIGHT>  Height in pixels of a sprite tile, defaults to the source aspect ratio
+//!       --columns <COLUMNS>          Number of sprite tiles per row, defaults to `10`
+//!       --vtt <VTT>                  Where to put the WebVTT track of the sprite, defaults to the sprite path with the `vtt` extension
+//!   -f, --format <FORMAT>            Output format, `jpeg/png/webp/avif` for thumbnails defaulting to the output extension, `gif/webp/mp4` for previews defaulting to `gif`
+//!       --duration <DURATION>        Seconds of every preview clip, defaults to `3`
+//!       --fps <FPS>                  Frames per second of the preview, defaults to `10`
+//!       --width <WIDTH>              Maximum width in pixels of the thumbnails, or width of the preview defaulting to `320`
+//!       --height <HEIGHT>            Maximum height in pixels of the thumbnails, the aspect ratio is kept
+//!   -q, --quality <QUALITY>          Quality of the thumbnails from `1` to `100`
+//!   -h, --help                       Print help information
+//!   -V, --version                    Print version information
+//! ```
+
+mod atium;
+
+pub use crate::atium::batch;
+pub use crate::atium::common;
+pub use crate::atium::concat;
+pub use crate::atium::converter;
+pub use crate::atium::packager;
+
+pub use crate::atium::batch::service::BatchService;
+pub use crate::atium::common::analysis_service::MediaInfoExtractorService;
+pub use crate::atium::common::error::AtiumError;
+pub use crate::atium::common::thumbnail_service::FFMPEGThumbnailService;
+pub use crate::atium::concat::service::FFMPEGConcatService;
+pub use crate::atium::converter::service::FFMPEGConversionService;
+pub use crate::atium::packager::service::FFMPEGPackagingService;
@@ -1,133 +1,26 @@
-//! # Atium
-//!
-//! Atium is a simple video conversion tool that lets you obtain a converted video by
-//! specifying some parameters like resolution, codec, and container.
-//!
-//! This is available as an importable library but its main usage occurs through a CLI,
-//! powered by `Clap`.
-//!
-//! ## API
-//! ### Conversion
-//!
-//! Basic usage for conversion API is simple as it follows:
-//! First you need to build a new instance of [FFMPEGConversionService](crate::converter::service::FFMPEGConversionService)
-//! ```
-//! let conversion_service = FFMPEGConversionService::new().expect("could not load service");
-//! ```
-//! The following step is to create a [ConversionRequest](crate::converter::model::ConversionRequest) that contains the required options in order to
-//! tune the conversion output.
-//! Please take a look at the structure documentation to understand each field's meaning.
-//! ```
-//! let request = ConversionRequest{
-//!     input: ConversionInput {
-//!         source_type: InputSourceType::Local,
-//!         file_name:  String::from("/path/to/input.mp4")
-//!     },
-//!     output: ConversionOutput {
-//!         file: String::from("/path/to/output.mp4"),
-//!         resolution: OutputResolution::Hd,
-//!         codec: OutputCodec::H264,
-//!         thumbnail_request: ThumbnailRequest::new(
-//!               String::from("00:00:01.000"),
-//!               // this could be blank since it will use the converted video
-//!               // as a source for thumbnail extraction
-//!               String::from("/path/to/input.mp4"),
-//!               String::from("/path/to/thumb.jpg")
-//!         )
-//!     }
-//!  };
-//! ```
-//! Now you can perform the actual conversion! Use the previously created `conversion_service`
-//! and call the `convert` method.
-//! ```
-//! let result: Result<ConversionResponse, AtiumError> = conversion_service.convert(request);
-//! ```
-//! The output is a `Result<ConversionResponse, AtiumError>`, so now is up to you.
-//! This contains a [ConversionResponse](crate::converter::model::ConversionResponse) that holds the output path of the converted video!
-//!
-//! ## Command Line Interface
-//!
-//! After installing `atium` by entering `atium --help` the following helper shows up:
-//! ```text
-//! Usage: atium <COMMAND>
-//!
-//! Commands:
-//!   convert    Conversion tool for video media
-//!   analyze    Analyze media to extract useful infos
-//!   thumbnail  Thumbnail extraction tool
-//!   help       Print this message or the help of the given subcommand(s)
-//!
-//! Options:
-//!   -h, --help     Print help information
-//!   -V, --version  Print version information
-//! ```
-//! Here you can access all the tools offered by `atium`.
-//!
-//! ### Conversion
-//!
-//! ```
-//! Conversion tool for video media
-//!
-//! Usage: atium convert [OPTIONS] --input <INPUT> --output <OUTPUT> --resolution <RESOLUTION>
-//!
-//! Options:
-//!   -i, --input <INPUT>                Input file to convert
-//!   -s, --source-type <SOURCE_TYPE>    Type of source to convert
-//!   -o, --output <OUTPUT>              Output path for the converted file
-//!   -r, --resolution <RESOLUTION>      Requested output resolution
-//!       --thumb-ts <THUMB_TS>          Timestamp requested for thumbnail extraction
-//!       --thumb-source <THUMB_SOURCE>  Source from where to extract the thumbnail
-//!       --thumb-out <THUMB_OUT>        Output path for the extracted thumbnail
-//!   -h, --help                         Print help information
-//!   -V, --version                      Print version information
-//! ```
-//!
-//! ### Analyze
-//!
-//! ```
-//! Analyze media to extract useful infos
-//!
-//! Usage: atium analyze [OPTIONS] --input <INPUT>
-//!
-//! Options:
-//!   -i, --input <INPUT>                  Input path of the file that will be analyzed
-//!   -f, --full <FULL>                    Whether you want the full analysis or not [possible values: true, false]
-//!       --output-format <OUTPUT_FORMAT>  Output format of the analysis tool
-//!       --output-file <OUTPUT_FILE>      Output file containing analysis result
-//!   -h, --help                           Print help information
-//!   -V, --version                        Print version information
-//! ```
-//!
-//! ### Thumbnail
-//!
-//! ```
-//! Thumbnail extraction tool
-//!
-//! Usage: atium thumbnail [OPTIONS]
-//!
-//! Options:
-//!   -t, --timestamp <TIMESTAMP>      The timestamp of the video for thumbnail extraction
-//!   -s, --source-path <SOURCE_PATH>  The source video for thumbnail extraction
-//!   -o, --output-path <OUTPUT_PATH>  Where to put the extracted thumbnail
-//!   -h, --help                       Print help information
-//!   -V, --version                    Print version information
-//! ```
-
+//! The `atium` command line interface, built on top of the [atium] library.
 
 use std::env;
+use std::error::Error;
+use std::process;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
 use clap::{Parser, Subcommand};
 use log::{error, info};
 
-use atium::converter;
-use crate::atium::common::analysis_service::MediaInfoExtractorService;
-use crate::atium::common::model::{InfoExtractorRequest, parse_info_format, parse_info_output_type, ThumbnailRequest};
-use crate::atium::common::thumbnail_service::FFMPEGThumbnailService;
-use crate::converter::model::{ConversionInput, ConversionOutput, ConversionRequest, InputSourceType, OutputCodec, parse_resolution};
-use crate::converter::service::FFMPEGConversionService;
-
-mod atium;
+use atium::batch::model::{BatchRequest, JobStatus, load_manifest_file, manifest_format};
+use atium::{AtiumError, BatchService, FFMPEGConcatService, FFMPEGConversionService, FFMPEGPackagingService, FFMPEGThumbnailService, MediaInfoExtractorService};
+use atium::common::command_manager::{CancellationToken, ExecutionControl};
+use atium::common::progress::{Progress, ProgressStage};
+use atium::concat::model::{ConcatMethod, ConcatRequest};
+use atium::common::model::{InfoExtractorRequest, parse_info_format, parse_image_output, parse_info_output_type, parse_percentages, parse_preview_format, parse_thumbnail_mode, parse_timestamps, PreviewFormat, PreviewRequest, SpriteRequest, ThumbnailMode, ThumbnailRequest, ThumbnailSelection};
+use atium::packager::model::{HlsRequest, parse_segment_type, SegmentType};
+use atium::converter::model::{AudioCodec, AudioOutput, ConversionInput, ConversionOutput, ConversionRequest, DashOutput, default_ladder, InputSourceType, LadderRung, output_container, OutputCodec, OutputContainer, OutputResolution, parse_audio_output, parse_clip_range, parse_codec, parse_ladder, parse_loudness_target, parse_rate_control, parse_resolution, parse_rotation_mode, parse_scaling_mode, RateControl, RotationMode, ScalingMode};
 
 #[derive(Subcommand)]
+#[allow(clippy::large_enum_variant)]
 enum Commands {
     /// Conversion tool for video media
     Convert {
@@ -137,13 +30,85 @@ enum Commands {
         /// Type of source to convert
         #[arg(short, long)]
         source_type: Option<String>,
-        /// Output path for the converted file
+        /// Output path for the converted file, the manifest of a DASH output
         #[arg(short, long)]
         output: String,
-        /// Requested output resolution
+        /// Requested output resolution, DASH outputs take the resolutions of the ladder
+        #[arg(short, long, required_unless_present = "dash")]
+        resolution: Option<String>,
+        /// Scaling mode `fit/fill/pad/crop`, the source aspect ratio is always kept
+        #[arg(long)]
+        scaling: Option<String>,
+        /// Rotation handling `bake/metadata`, defaults to baking it into the frames
+        #[arg(long)]
+        rotation: Option<String>,
+        /// Requested video codec `h264/h265/vp9/av1/av1-svt/prores/copy`
         #[arg(short, long)]
-        resolution: String,
-        /// Timestamp requested for thumbnail extraction
+        codec: Option<String>,
+        /// Requested output container `mp4/mkv/webm/mov/ts`, defaults to the output file extension
+        #[arg(long)]
+        container: Option<String>,
+        /// Constant quality value (CRF/CQ), cannot be combined with bitrate options
+        #[arg(long)]
+        crf: Option<u32>,
+        /// Target average video bitrate in kbit/s
+        #[arg(long)]
+        bitrate: Option<u32>,
+        /// Maximum video bitrate in kbit/s, enables constrained VBR
+        #[arg(long)]
+        maxrate: Option<u32>,
+        /// Decoder buffer size in kbit, defaults to twice the maxrate
+        #[arg(long)]
+        bufsize: Option<u32>,
+        /// Encoder preset, e.g. `slow` for x264/x265 or `6` for SVT-AV1
+        #[arg(long)]
+        preset: Option<String>,
+        /// Encoder tune, e.g. `film` or `animation` for x264
+        #[arg(long)]
+        tune: Option<String>,
+        /// Runs a two-pass encoding, needs a target bitrate
+        #[arg(long)]
+        two_pass: bool,
+        /// Writes an MPEG-DASH manifest, every rung of the ladder is encoded as a representation next to it
+        #[arg(long)]
+        dash: bool,
+        /// Ladder of DASH representations as `resolution:bitrate` rungs in kbit/s, e.g. `fhd:5000,hd:2800,sd:1200`
+        #[arg(long, requires = "dash")]
+        ladder: Option<String>,
+        /// Target DASH segment duration in seconds, defaults to `6`
+        #[arg(long, requires = "dash", value_name = "SECONDS")]
+        dash_segment_duration: Option<u32>,
+        /// Requested audio codec `aac/opus/mp3/flac/copy/none`
+        #[arg(long)]
+        audio_codec: Option<String>,
+        /// Audio bitrate in kbit/s
+        #[arg(long)]
+        audio_bitrate: Option<u32>,
+        /// Audio channel layout `mono/stereo/5.1`, sources with more channels are downmixed
+        #[arg(long)]
+        audio_channels: Option<String>,
+        /// Audio sample rate in Hz
+        #[arg(long)]
+        sample_rate: Option<u32>,
+        /// Normalizes audio loudness to the given integrated loudness in LUFS, e.g. `-23`
+        #[arg(long, allow_hyphen_values = true)]
+        loudness: Option<f64>,
+        /// Maximum true peak in dBTP for loudness normalization, defaults to `-1`
+        #[arg(long, allow_hyphen_values = true)]
+        true_peak: Option<f64>,
+        /// Loudness range in LU for loudness normalization, defaults to `7`
+        #[arg(long)]
+        loudness_range: Option<f64>,
+        /// Start of the converted range, e.g. `00:01:30`, defaults to the start of the source
+        #[arg(long)]
+        start: Option<String>,
+        /// End of the converted range, e.g. `00:02:45`, defaults to the end of the source
+        #[arg(long)]
+        end: Option<String>,
+        /// Maximum running time in seconds of every external command, killed when exceeded
+        #[arg(long)]
+        timeout: Option<u64>,
+        /// Comma separated timestamps requested for thumbnail extraction, `auto` picks the best frame
         #[arg(long)]
         thumb_ts: Option<String>,
         /// Source from where to extract the thumbnail
@@ -151,7 +116,19 @@ enum Commands {
         thumb_source: Option<String>,
         /// Output path for the extracted thumbnail
         #[arg(long)]
-        thumb_out: Option<String>
+        thumb_out: Option<String>,
+        /// Thumbnail format `jpeg/png/webp/avif`, defaults to the thumbnail output extension
+        #[arg(long)]
+        thumb_format: Option<String>,
+        /// Maximum thumbnail width in pixels, the aspect ratio is kept
+        #[arg(long)]
+        thumb_width: Option<u32>,
+        /// Maximum thumbnail height in pixels, the aspect ratio is kept
+        #[arg(long)]
+        thumb_height: Option<u32>,
+        /// Thumbnail quality from `1` to `100`
+        #[arg(long)]
+        thumb_quality: Option<u8>
     },
     /// Analyze media to extract useful infos
     Analyze {
@@ -173,15 +150,175 @@ enum Commands {
     },
     /// Thumbnail extraction tool
     Thumbnail {
-        /// The timestamp of the video for thumbnail extraction
-        #[arg(short, long)]
+        /// Comma separated timestamps of the video for thumbnail extraction, `auto` picks the best frame
+        #[arg(short, long, conflicts_with_all = ["percentages", "interval"])]
         timestamp: Option<String>,
+        /// Comma separated percentages of the duration for thumbnail extraction, e.g. `10,50,90`
+        #[arg(short, long, conflicts_with = "interval")]
+        percentages: Option<String>,
         /// The source video for thumbnail extraction
         #[arg(short, long)]
         source_path: Option<String>,
         /// Where to put the extracted thumbnail
         #[arg(short, long)]
-        output_path: Option<String>
+        output_path: Option<String>,
+        /// Extraction mode `single/sprite/preview`, a sprite is a grid of frames along with a WebVTT track,
+        /// a preview is a short animated clip
+        #[arg(short, long)]
+        mode: Option<String>,
+        /// Seconds between two extracted frames, defaults to `10` for sprites
+        #[arg(long)]
+        interval: Option<f64>,
+        /// Width in pixels of a sprite tile, defaults to `160`
+        #[arg(long)]
+        tile_width: Option<u32>,
+        /// Height in pixels of a sprite tile, defaults to the source aspect ratio
+        #[arg(long)]
+        tile_height: Option<u32>,
+        /// Number of sprite tiles per row, defaults to `10`
+        #[arg(long)]
+        columns: Option<u32>,
+        /// Where to put the WebVTT track of the sprite, defaults to the sprite path with the `vtt` extension
+        #[arg(long)]
+        vtt: Option<String>,
+        /// Output format, `jpeg/png/webp/avif` for thumbnails defaulting to the output extension, `gif/webp/mp4` for previews defaulting to `gif`
+        #[arg(short, long)]
+        format: Option<String>,
+        /// Seconds of every preview clip, defaults to `3`
+        #[arg(long)]
+        duration: Option<f64>,
+        /// Frames per second of the preview, defaults to `10`
+        #[arg(long)]
+        fps: Option<u32>,
+        /// Maximum width in pixels of the thumbnails, or width of the preview defaulting to `320`
+        #[arg(long)]
+        width: Option<u32>,
+        /// Maximum height in pixels of the thumbnails, the aspect ratio is kept
+        #[arg(long)]
+        height: Option<u32>,
+        /// Quality of the thumbnails from `1` to `100`
+        #[arg(short, long)]
+        quality: Option<u8>
+    },
+    /// Splits a video into segments of a fixed duration
+    Split {
+        /// Input file to split
+        #[arg(short, long)]
+        input: String,
+        /// Output path of the segments, numbered from `000` before the extension
+        #[arg(short, long)]
+        output: String,
+        /// Seconds of every segment, defaults to `600`
+        #[arg(short, long)]
+        duration: Option<f64>,
+        /// Start of the split range, defaults to the start of the source
+        #[arg(long)]
+        start: Option<String>,
+        /// End of the split range, defaults to the end of the source
+        #[arg(long)]
+        end: Option<String>,
+        /// Requested video codec, defaults to `copy` whose cuts snap to keyframes
+        #[arg(short, long)]
+        codec: Option<String>,
+        /// Requested output resolution when encoding, defaults to the source one
+        #[arg(short, long)]
+        resolution: Option<String>,
+        /// Constant quality value (CRF/CQ) when encoding
+        #[arg(long)]
+        crf: Option<u32>,
+        /// Encoder preset, e.g. `slow` for x264/x265
+        #[arg(long)]
+        preset: Option<String>,
+        /// Maximum running time in seconds of every external command, killed when exceeded
+        #[arg(long)]
+        timeout: Option<u64>
+    },
+    /// Concatenates several videos into one
+    Concat {
+        /// Input files in order, e.g. `-i intro.mp4 -i content.mp4 -i outro.mp4`
+        #[arg(short, long, required = true)]
+        input: Vec<String>,
+        /// Output path for the concatenated file
+        #[arg(short, long)]
+        output: String,
+        /// Video codec used when the inputs are normalized, defaults to `h264`
+        #[arg(short, long)]
+        codec: Option<String>,
+        /// Requested output container `mp4/mkv/webm/mov/ts`, defaults to the output file extension
+        #[arg(long)]
+        container: Option<String>,
+        /// Constant quality value (CRF/CQ) when normalizing
+        #[arg(long)]
+        crf: Option<u32>,
+        /// Encoder preset when normalizing, e.g. `slow` for x264/x265
+        #[arg(long)]
+        preset: Option<String>,
+        /// Normalizes the inputs even when they could be stream copied
+        #[arg(long)]
+        normalize: bool,
+        /// Maximum running time in seconds of every external command, killed when exceeded
+        #[arg(long)]
+        timeout: Option<u64>
+    },
+    /// Batch conversion of the jobs listed in a manifest
+    Batch {
+        /// Manifest of conversion jobs, whose fields match the convert options
+        #[arg(short, long)]
+        manifest: String,
+        /// Manifest format `json/yaml/csv`, defaults to the manifest extension
+        #[arg(short, long)]
+        format: Option<String>,
+        /// Number of conversions running in parallel, defaults to `2`
+        #[arg(short, long)]
+        workers: Option<usize>,
+        /// Output path for the JSON summary report
+        #[arg(short, long)]
+        report: Option<String>,
+        /// Maximum running time in seconds of every external command, killed when exceeded
+        #[arg(long)]
+        timeout: Option<u64>
+    },
+    /// Adaptive streaming packaging tool
+    Package {
+        #[command(subcommand)]
+        format: PackageFormat
+    }
+}
+
+#[derive(Subcommand)]
+enum PackageFormat {
+    /// Packages an HLS ladder along with its master playlist
+    Hls {
+        /// Input file to package
+        #[arg(short, long)]
+        input: String,
+        /// Output directory, receiving the master playlist and a directory per variant
+        #[arg(short, long)]
+        output_dir: String,
+        /// Ladder of `resolution:bitrate` rungs in kbit/s, e.g. `fhd:5000,hd:2800,sd:1200`
+        #[arg(short, long)]
+        ladder: Option<String>,
+        /// Requested video codec `h264/h265`
+        #[arg(short, long)]
+        codec: Option<String>,
+        /// Segment format `ts/fmp4`, defaults to `ts` for h264 and `fmp4` for h265
+        #[arg(long)]
+        segment_type: Option<String>,
+        /// Target segment duration in seconds, defaults to `6`
+        #[arg(long)]
+        segment_duration: Option<u32>,
+        /// Encoder preset, e.g. `slow`
+        #[arg(long)]
+        preset: Option<String>,
+        /// AAC stereo bitrate in kbit/s, defaults to `128`
+        #[arg(long)]
+        audio_bitrate: Option<u32>,
+        /// Runs one ffmpeg per rung instead of a single run encoding the whole ladder
+        #[arg(long)]
+        per_rung: bool,
+        /// Maximum running time in seconds of every external command, killed when exceeded
+        #[arg(long)]
+        timeout: Option<u64>
     }
 }
 
@@ -193,6 +330,109 @@ struct Cli {
      command: Commands
 }
 
+/// Exit code of a batch where some of the jobs failed or were cancelled
+const BATCH_FAILURE_EXIT_CODE: i32 = 1;
+
+const PROGRESS_BAR_WIDTH: usize = 30;
+
+/// Renders a [`Progress`] update as a single line progress bar
+fn format_progress(progress: &Progress) -> String {
+    let stage = match progress.stage {
+        ProgressStage::LoudnessAnalysis => "Loudness",
+        ProgressStage::FirstPass => "Pass 1",
+        ProgressStage::Encoding => "Encoding"
+    };
+    let bar = match progress.percentage {
+        Some(percentage) => {
+            let filled = (percentage / 100.0 * PROGRESS_BAR_WIDTH as f64).round() as usize;
+            format!("[{}{}] {:>5.1}%", "#".repeat(filled), "-".repeat(PROGRESS_BAR_WIDTH - filled), percentage)
+        }
+        None => format!("[{}] {:>7}", "?".repeat(PROGRESS_BAR_WIDTH), format!("{}s", progress.out_time.as_secs()))
+    };
+    let speed = progress.speed.map(|speed| format!("{:.2}x", speed)).unwrap_or_else(|| "-".to_string());
+    let fps = progress.fps.map(|fps| format!("{:.1}", fps)).unwrap_or_else(|| "-".to_string());
+
+    format!("{:<8} {} speed {} fps {}", stage, bar, speed, fps)
+}
+
+fn render_progress(progress: Progress) {
+    let mut stderr = io::stderr();
+    let _ = write!(stderr, "\r{}", format_progress(&progress));
+    if progress.finished {
+        let _ = writeln!(stderr);
+    }
+    let _ = stderr.flush();
+}
+
+#[cfg(unix)]
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn handle_interrupt(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst)
+}
+
+/// Returns a token cancelled on Ctrl-C.
+///
+/// External commands run in their own process group, so the terminal interrupt does not reach them.
+fn interrupt_token() -> CancellationToken {
+    let token = CancellationToken::new();
+
+    #[cfg(unix)]
+    {
+        // SAFETY: the handler only stores into an atomic, which is async-signal-safe
+        unsafe {
+            libc::signal(libc::SIGINT, handle_interrupt as extern "C" fn(libc::c_int) as libc::sighandler_t);
+        }
+
+        let interrupted = token.clone();
+        thread::spawn(move || {
+            while !INTERRUPTED.load(Ordering::SeqCst) {
+                thread::sleep(Duration::from_millis(100));
+            }
+            interrupted.cancel();
+        });
+    }
+
+    token
+}
+
+/// Logs `err` along with the failed command and its stderr tail,
+/// then exits with the code of the error category
+fn exit_with_error(context: &str, err: AtiumError) -> ! {
+    error!("{}: {}", context, err);
+    if let Some(failure) = err.command_failure() {
+        error!("{}", failure);
+        failure.stderr_tail.iter().for_each(|line| error!("  {}", line));
+    } else if let Some(source) = err.source() {
+        error!("Caused by: {}", source);
+    }
+
+    process::exit(err.exit_code())
+}
+
+/// Converts a number of seconds given on the command line,
+/// failing unless it is finite and greater than 0
+fn seconds(value: f64) -> Result<Duration, AtiumError> {
+    Duration::try_from_secs_f64(value).ok()
+        .filter(|duration| !duration.is_zero())
+        .ok_or_else(|| AtiumError::InvalidRequest(format!("[{}] is not a valid number of seconds, expected a finite value greater than 0", value)))
+}
+
+/// Parses an optional command line value, exiting when it is invalid
+fn parse_arg<T>(value: Option<&str>, parse: fn(&str) -> Result<T, AtiumError>, context: &str) -> Option<T> {
+    value.map(|value| parse(value).unwrap_or_else(|err| exit_with_error(context, err)))
+}
+
+/// Returns the requested ladder or, when missing, the default one
+fn load_ladder(ladder: Option<&str>) -> Vec<LadderRung> {
+    match ladder {
+        Some(ladder) => parse_ladder(ladder)
+            .unwrap_or_else(|err| exit_with_error("Invalid ladder", err)),
+        None => default_ladder()
+    }
+}
+
 fn setup_logger() {
     let rust_log = "RUST_LOG";
     if env::var(rust_log).is_err() {
@@ -216,7 +456,7 @@ fn main() {
             output_type
         } => {
             let info_extractor_service = MediaInfoExtractorService::new()
-                .expect("Error building media info service");
+                .unwrap_or_else(|err| exit_with_error("Error building media info service", err));
             let request = InfoExtractorRequest {
                 input: input.to_string(),
                 format: parse_info_format(output_format.clone()),
@@ -227,12 +467,12 @@ fn main() {
 
             match info_extractor_service.get_info(request) {
                 Ok(response) => {
-                    if response.output.file.is_some() {
-                        info!("Output written to {}", response.output.file.unwrap())
+                    if let Some(file) = response.output.file {
+                        info!("Output written to {}", file)
                     }
                     info!("Info extracted successfully")
                 }
-                Err(err) => error!("An error occurred when extracting info {}", err)
+                Err(err) => exit_with_error("An error occurred when extracting info", err)
             }
         },
         Commands::Convert {
@@ -240,12 +480,46 @@ fn main() {
             source_type:_,
             output,
             resolution,
+            scaling,
+            rotation,
+            codec,
+            container,
+            crf,
+            bitrate,
+            maxrate,
+            bufsize,
+            preset,
+            tune,
+            two_pass,
+            dash,
+            ladder,
+            dash_segment_duration,
+            audio_codec,
+            audio_bitrate,
+            audio_channels,
+            sample_rate,
+            loudness,
+            true_peak,
+            loudness_range,
+            start,
+            end,
+            timeout,
             thumb_ts,
             thumb_source,
-            thumb_out
+            thumb_out,
+            thumb_format,
+            thumb_width,
+            thumb_height,
+            thumb_quality
         } => {
+            let rate_control = match parse_rate_control(*crf, *bitrate, *maxrate, *bufsize) {
+                Ok(rate_control) => rate_control,
+                Err(err) => exit_with_error("Invalid rate control options", err)
+            };
+            let range = parse_clip_range(start.as_deref(), end.as_deref())
+                .unwrap_or_else(|err| exit_with_error("Invalid range", err));
             let conversion_service = FFMPEGConversionService::new()
-                .expect("Cannot build service");
+                .unwrap_or_else(|err| exit_with_error("Cannot build service", err));
             let request = ConversionRequest{
                 input: ConversionInput {
                     source_type: InputSourceType::Local,
@@ -253,45 +527,357 @@ fn main() {
                 },
                 output: ConversionOutput {
                     file: output.clone(),
-                    resolution: parse_resolution(resolution),
-                    codec: OutputCodec::H264,
+                    resolution: resolution.as_deref().map(parse_resolution).unwrap_or(OutputResolution::FullHd),
+                    scaling: parse_arg(scaling.as_deref(), parse_scaling_mode, "Invalid scaling mode").unwrap_or(ScalingMode::Fit),
+                    rotation: parse_arg(rotation.as_deref(), parse_rotation_mode, "Invalid rotation mode").unwrap_or(RotationMode::Bake),
+                    codec: parse_arg(codec.as_deref(), parse_codec, "Invalid codec").unwrap_or(OutputCodec::H264),
+                    // DASH segments are fragmented MP4 whatever the manifest extension
+                    container: match (*dash, container) {
+                        (true, None) => OutputContainer::Mp4,
+                        _ => output_container(container.as_deref(), output)
+                            .unwrap_or_else(|err| exit_with_error("Invalid container", err))
+                    },
+                    rate_control,
+                    preset: preset.clone(),
+                    tune: tune.clone(),
+                    two_pass: *two_pass,
+                    audio: parse_audio_output(audio_codec.as_deref(), *audio_bitrate, audio_channels.as_deref(), *sample_rate)
+                        .unwrap_or_else(|err| exit_with_error("Invalid audio options", err)),
+                    loudness: parse_loudness_target(*loudness, *true_peak, *loudness_range),
+                    range,
+                    segment_duration: None,
+                    dash: dash.then(|| DashOutput {
+                        ladder: load_ladder(ladder.as_deref()),
+                        segment_duration: dash_segment_duration.unwrap_or(6)
+                    }),
                     thumbnail_request: ThumbnailRequest::new(
                         thumb_ts,
                         thumb_source,
                         thumb_out
-                    )
+                    ).map(|request| ThumbnailRequest {
+                        image: parse_image_output(thumb_format.as_deref(), thumb_out.as_deref(), *thumb_width, *thumb_height, *thumb_quality)
+                            .unwrap_or_else(|err| exit_with_error("Invalid thumbnail format", err)),
+                        ..request
+                    })
                 }
             };
 
-            match conversion_service.convert(request) {
+            let control = ExecutionControl {
+                timeout: timeout.map(Duration::from_secs),
+                cancellation: Some(interrupt_token())
+            };
+
+            match conversion_service.convert_with_control(request, &control, render_progress) {
                 Ok(response) => {
-                    info!("Converted file available at [{}]", response.output_file)
+                    for representation in &response.representations {
+                        info!(
+                            "Representation [{}] {}x{} with codecs [{}] available at [{}]",
+                            representation.id, representation.resolution.0, representation.resolution.1, representation.codecs, representation.directory
+                        )
+                    }
+                    info!("Converted file available at [{}]", response.output_file);
+                    if let Some(thumbnail) = response.thumbnail_response {
+                        thumbnail.outputs.iter().for_each(|output| info!("Thumbnail available at [{}]", output))
+                    }
+                    if let Some(loudness) = response.loudness {
+                        info!(
+                            "Loudness normalized from {:.2} LUFS / {:.2} dBTP / {:.2} LU to {:.2} LUFS / {:.2} dBTP / {:.2} LU",
+                            loudness.measured.integrated, loudness.measured.true_peak, loudness.measured.loudness_range,
+                            loudness.achieved.integrated, loudness.achieved.true_peak, loudness.achieved.loudness_range
+                        )
+                    }
                 }
-                Err(msg) => error!("An error occurred when converting {}", msg)
+                Err(err) => exit_with_error("An error occurred when converting", err)
+            }
+        },
+        Commands::Split {
+            input,
+            output,
+            duration,
+            start,
+            end,
+            codec,
+            resolution,
+            crf,
+            preset,
+            timeout
+        } => {
+            let range = parse_clip_range(start.as_deref(), end.as_deref())
+                .unwrap_or_else(|err| exit_with_error("Invalid range", err));
+            let codec = parse_arg(codec.as_deref(), parse_codec, "Invalid codec").unwrap_or(OutputCodec::Copy);
+            let conversion_service = FFMPEGConversionService::new()
+                .unwrap_or_else(|err| exit_with_error("Cannot build service", err));
+            let request = ConversionRequest {
+                input: ConversionInput {
+                    source_type: InputSourceType::Local,
+                    file_name: input.clone()
+                },
+                output: ConversionOutput {
+                    file: output.clone(),
+                    // the resolution is clamped to the source one
+                    resolution: resolution.as_deref().map(parse_resolution).unwrap_or(OutputResolution::FullUltraHd),
+                    scaling: ScalingMode::Fit,
+                    rotation: RotationMode::Bake,
+                    codec,
+                    container: output_container(None, output)
+                        .unwrap_or_else(|err| exit_with_error("Invalid container", err)),
+                    rate_control: crf.map(RateControl::ConstantQuality),
+                    preset: preset.clone(),
+                    tune: None,
+                    two_pass: false,
+                    // copied segments keep the source audio as well
+                    audio: (codec == OutputCodec::Copy).then_some(AudioOutput { codec: AudioCodec::Copy, bitrate: None, channels: None, sample_rate: None }),
+                    loudness: None,
+                    range,
+                    segment_duration: Some(
+                        seconds(duration.unwrap_or(600.0)).unwrap_or_else(|err| exit_with_error("Invalid segment duration", err))
+                    ),
+                    dash: None,
+                    thumbnail_request: None
+                }
+            };
+
+            let control = ExecutionControl {
+                timeout: timeout.map(Duration::from_secs),
+                cancellation: Some(interrupt_token())
+            };
+
+            match conversion_service.convert_with_control(request, &control, render_progress) {
+                Ok(response) => response.segments.iter().for_each(|segment| info!("Segment available at [{}]", segment)),
+                Err(err) => exit_with_error("An error occurred when splitting", err)
+            }
+        },
+        Commands::Concat {
+            input,
+            output,
+            codec,
+            container,
+            crf,
+            preset,
+            normalize,
+            timeout
+        } => {
+            let service = FFMPEGConcatService::new()
+                .unwrap_or_else(|err| exit_with_error("Cannot build service", err));
+            let request = ConcatRequest {
+                inputs: input.clone(),
+                output_file: output.clone(),
+                container: output_container(container.as_deref(), output)
+                    .unwrap_or_else(|err| exit_with_error("Invalid container", err)),
+                codec: parse_arg(codec.as_deref(), parse_codec, "Invalid codec").unwrap_or(OutputCodec::H264),
+                rate_control: crf.map(RateControl::ConstantQuality),
+                preset: preset.clone(),
+                normalize: *normalize
+            };
+            let control = ExecutionControl {
+                timeout: timeout.map(Duration::from_secs),
+                cancellation: Some(interrupt_token())
+            };
+
+            match service.concat_with_control(request, &control, render_progress) {
+                Ok(response) => {
+                    match response.method {
+                        ConcatMethod::Copy => info!("Inputs match, streams were copied"),
+                        ConcatMethod::Normalize => response.mismatches.iter().for_each(|mismatch| info!("Inputs normalized: {}", mismatch))
+                    }
+                    info!("Concatenated file of {:.3}s available at [{}]", response.duration.as_secs_f64(), response.output_file)
+                }
+                Err(err) => exit_with_error("An error occurred when concatenating", err)
             }
         },
         Commands::Thumbnail {
             timestamp,
+            percentages,
             source_path,
-            output_path
+            output_path,
+            mode,
+            interval,
+            tile_width,
+            tile_height,
+            columns,
+            vtt,
+            format,
+            duration,
+            fps,
+            width,
+            height,
+            quality
         } => {
-            let request = ThumbnailRequest::new(
-                timestamp,
-                source_path,
-                output_path
-            );
             let service = FFMPEGThumbnailService::new()
-                .expect("Cannot build service");
-
-            if request.is_none() {
-                error!("You didn't specify all the required options!")
-            } else {
-                match service.extract_thumbnail(request.unwrap()) {
-                    Ok(_) => info!("Thumbnail extracted successfully"),
-                    Err(err) => error!("An error occurred when extracting thumbnail: {}", err)
+                .unwrap_or_else(|err| exit_with_error("Cannot build service", err));
+            let selection = match (percentages, interval) {
+                (Some(percentages), _) => parse_percentages(percentages)
+                    .unwrap_or_else(|err| exit_with_error("Invalid thumbnail percentages", err)),
+                (None, Some(interval)) => ThumbnailSelection::Interval(
+                    seconds(*interval).unwrap_or_else(|err| exit_with_error("Invalid thumbnail interval", err))
+                ),
+                (None, None) => timestamp.as_deref().map(parse_timestamps).unwrap_or_default()
+            };
+
+            match parse_arg(mode.as_deref(), parse_thumbnail_mode, "Invalid thumbnail mode").unwrap_or(ThumbnailMode::Single) {
+                ThumbnailMode::Sprite => {
+                    let input_file = source_path.clone().unwrap_or_else(|| exit_with_error(
+                        "You didn't specify all the required options!",
+                        AtiumError::InvalidRequest("source path is required".to_string())
+                    ));
+                    let request = SpriteRequest {
+                        input_file,
+                        output_file: output_path.clone(),
+                        vtt_file: vtt.clone(),
+                        interval: seconds(interval.unwrap_or(10.0)).unwrap_or_else(|err| exit_with_error("Invalid sprite interval", err)),
+                        tile_width: tile_width.unwrap_or(160),
+                        tile_height: *tile_height,
+                        columns: columns.unwrap_or(10)
+                    };
+
+                    match service.extract_sprite(request) {
+                        Ok(response) => info!(
+                            "Sprite of {} tiles extracted at [{}] with WebVTT track [{}]",
+                            response.tiles, response.sprite, response.vtt
+                        ),
+                        Err(err) => exit_with_error("An error occurred when extracting sprite", err)
+                    }
+                },
+                ThumbnailMode::Preview => {
+                    let input_file = source_path.clone().unwrap_or_else(|| exit_with_error(
+                        "You didn't specify all the required options!",
+                        AtiumError::InvalidRequest("source path is required".to_string())
+                    ));
+                    let request = PreviewRequest {
+                        selection,
+                        input_file,
+                        output_file: output_path.clone(),
+                        format: parse_arg(format.as_deref(), parse_preview_format, "Invalid preview format").unwrap_or(PreviewFormat::Gif),
+                        duration: seconds(duration.unwrap_or(3.0)).unwrap_or_else(|err| exit_with_error("Invalid preview duration", err)),
+                        fps: fps.unwrap_or(10),
+                        width: width.unwrap_or(320)
+                    };
+
+                    match service.extract_preview(request) {
+                        Ok(response) => {
+                            if let Some(frame) = response.scored_frame {
+                                info!("Selected frame at {:.3}s with score {:.3}", frame.timestamp.as_secs_f64(), frame.score)
+                            }
+                            response.outputs.iter()
+                                .zip(response.starts.iter())
+                                .for_each(|(output, start)| info!("Preview from {:.3}s extracted at [{}]", start.as_secs_f64(), output))
+                        },
+                        Err(err) => exit_with_error("An error occurred when extracting preview", err)
+                    }
+                },
+                ThumbnailMode::Single => {
+                    let request = ThumbnailRequest::new(
+                        timestamp,
+                        source_path,
+                        output_path
+                    );
+
+                    match request {
+                        None => exit_with_error(
+                            "You didn't specify all the required options!",
+                            AtiumError::InvalidRequest("source and output paths are required".to_string())
+                        ),
+                        Some(mut request) => {
+                            request.selection = selection;
+                            request.image = parse_image_output(format.as_deref(), output_path.as_deref(), *width, *height, *quality)
+                                .unwrap_or_else(|err| exit_with_error("Invalid thumbnail format", err));
+
+                            match service.extract_thumbnail(request) {
+                                Ok(response) => {
+                                    if let Some(frame) = response.scored_frame {
+                                        info!("Selected frame at {:.3}s with score {:.3}", frame.timestamp.as_secs_f64(), frame.score)
+                                    }
+                                    response.outputs.iter().for_each(|output| info!("Thumbnail extracted at [{}]", output))
+                                },
+                                Err(err) => exit_with_error("An error occurred when extracting thumbnail", err)
+                            }
+                        }
+                    }
                 }
             }
+        },
+        Commands::Batch {
+            manifest,
+            format,
+            workers,
+            report,
+            timeout
+        } => {
+            let format = manifest_format(format.as_deref(), manifest)
+                .unwrap_or_else(|err| exit_with_error("Invalid manifest format", err));
+            let jobs = load_manifest_file(manifest, format)
+                .unwrap_or_else(|err| exit_with_error("Cannot load manifest", err));
+            let service = BatchService::new()
+                .unwrap_or_else(|err| exit_with_error("Cannot build service", err));
+            let control = ExecutionControl {
+                timeout: timeout.map(Duration::from_secs),
+                cancellation: Some(interrupt_token())
+            };
+
+            let summary = service.run(BatchRequest { jobs, workers: workers.unwrap_or(2) }, &control);
+
+            summary.jobs.iter()
+                .filter(|job| job.status != JobStatus::Succeeded)
+                .for_each(|job| error!("Job [{}] on [{}] {:?}: {}", job.id, job.input, job.status, job.error.as_deref().unwrap_or("")));
+            info!(
+                "Batch done in {:.1}s: {} succeeded, {} failed, {} cancelled",
+                summary.duration_secs, summary.succeeded, summary.failed, summary.cancelled
+            );
+
+            if let Some(report) = report {
+                summary.write_to(report)
+                    .unwrap_or_else(|err| exit_with_error("Cannot write report", err));
+                info!("Report written to [{}]", report)
+            }
 
+            if !summary.is_success() {
+                process::exit(BATCH_FAILURE_EXIT_CODE)
+            }
+
+        },
+        Commands::Package { format: PackageFormat::Hls {
+            input,
+            output_dir,
+            ladder,
+            codec,
+            segment_type,
+            segment_duration,
+            preset,
+            audio_bitrate,
+            per_rung,
+            timeout
+        } } => {
+            let codec = parse_arg(codec.as_deref(), parse_codec, "Invalid codec").unwrap_or(OutputCodec::H264);
+            let service = FFMPEGPackagingService::new()
+                .unwrap_or_else(|err| exit_with_error("Cannot build service", err));
+            let request = HlsRequest {
+                input: input.clone(),
+                output_dir: output_dir.clone(),
+                ladder: load_ladder(ladder.as_deref()),
+                codec,
+                segment_type: parse_arg(segment_type.as_deref(), parse_segment_type, "Invalid segment type").unwrap_or_else(|| SegmentType::default_for(codec)),
+                segment_duration: segment_duration.unwrap_or(6),
+                preset: preset.clone(),
+                audio_bitrate: audio_bitrate.unwrap_or(128),
+                per_rung: *per_rung
+            };
+            let control = ExecutionControl {
+                timeout: timeout.map(Duration::from_secs),
+                cancellation: Some(interrupt_token())
+            };
+
+            match service.package_hls_with_control(request, &control, render_progress) {
+                Ok(response) => {
+                    for variant in &response.variants {
+                        info!(
+                            "Variant [{}] {}x{} at {} bit/s available at [{}]",
+                            variant.name, variant.resolution.0, variant.resolution.1, variant.bandwidth, variant.playlist
+                        )
+                    }
+                    info!("Master playlist available at [{}]", response.master_playlist)
+                }
+                Err(err) => exit_with_error("An error occurred when packaging", err)
+            }
         }
     }
 }